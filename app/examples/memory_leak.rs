@@ -1,29 +1,52 @@
 #![no_main]
 
-use std::{time::Duration, thread};
+use std::alloc::System;
+use std::thread;
 
-use esp_idf_hal::Heap;
+use esp_idf_hal::{Heap, HeapCaps, heap::{FreeRtos, LeakTracker}};
+
+#[global_allocator]
+static ALLOCATOR: LeakTracker<System, FreeRtos> = LeakTracker::new(System);
 
 #[no_mangle]
 fn app_main() {
-  eprintln!("Total Memory: {}", Heap::total_size());
+  ALLOCATOR.set_low_heap_threshold(16 * 1024);
 
-  let size_before = Heap::free_size();
-  eprintln!("Free Memory (Main Thread): {}", size_before);
+  let stats_before = Heap::stats(HeapCaps::default());
+  eprintln!("Heap (Main Thread): {:?}", stats_before);
 
   thread::Builder::new().stack_size(8192).spawn(|| {
-    eprintln!("Free Memory (Thread 1): {}", Heap::free_size());
+    ALLOCATOR.set_tag("thread_1");
+    eprintln!("Free Memory (Thread 1): {}", Heap::stats(HeapCaps::default()).free);
 
     thread::Builder::new().stack_size(8192).spawn(|| {
-      eprintln!("Free Memory (Thread 2): {}", Heap::free_size());
-    }).unwrap();
-  }).unwrap();
+      ALLOCATOR.set_tag("thread_2");
+      eprintln!("Free Memory (Thread 2): {}", Heap::stats(HeapCaps::default()).free);
+    }).unwrap().join().unwrap();
+  }).unwrap().join().unwrap();
+
+  if let Some((free, report)) = ALLOCATOR.take_low_heap_report() {
+    eprintln!("Free heap dropped to {} bytes.\n{}", free, report);
+  }
+
+  let report = ALLOCATOR.report();
+  eprintln!("{}", report);
+
+  assert_eq!(report.tag("thread_1").outstanding_bytes, 0, "thread 1 leaked memory");
+  assert_eq!(report.tag("thread_2").outstanding_bytes, 0, "thread 2 leaked memory");
+
+  let leaked = thread::Builder::new().stack_size(8192).spawn(|| {
+    ALLOCATOR.set_tag("leaky_thread");
+    Box::leak(vec![0u8; 1024].into_boxed_slice()).as_ptr() as usize
+  }).unwrap().join().unwrap();
 
-  thread::sleep(Duration::from_secs(1));
+  let report = ALLOCATOR.report();
+  assert_eq!(report.tag("leaky_thread").outstanding_bytes, 1024, "leak was not detected");
+  assert!(report.largest().any(|allocation| allocation.address == leaked), "leak is not among the largest allocations");
 
-  let size_after = Heap::free_size();
-  eprintln!("Free Memory (Main Thread): {}", size_after);
+  let stats_after = Heap::stats(HeapCaps::default());
+  eprintln!("Heap (Main Thread): {:?}", stats_after);
+  eprintln!("Minimum Free Memory: {}", stats_after.minimum_free);
 
-  let leaked_memory = if size_after < size_before { size_before - size_after } else { 0 };
-  eprintln!("Leaked Memory: {}", leaked_memory);
+  println!("Success: memory leak checks");
 }
@@ -1,6 +1,11 @@
 #![no_main]
 
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
 use esp_idf_hal::nvs::*;
+use esp_idf_hal::wifi::{Password, Ssid};
+use macaddr::MacAddr6;
 
 #[no_mangle]
 fn app_main() {
@@ -41,4 +46,47 @@ fn app_main() {
   test_set_get!(nvs, u64, 64u64);
   test_set_get!(nvs, String, "String");
   test_set_get!(nvs, Vec<u8>, vec![1, 2, 3, 4]);
+  test_set_get!(nvs, f32, 32.5f32);
+  test_set_get!(nvs, f64, -64.25f64);
+  test_set_get!(nvs, [u8; 4], [1, 2, 3, 4]);
+  test_set_get!(nvs, Option<u32>, 32u32);
+  test_set_get!(nvs, Ipv4Addr, [192, 168, 4, 1]);
+  test_set_get!(nvs, MacAddr6, [0x24, 0x0a, 0xc4, 0x00, 0x00, 0x01]);
+
+  nvs.set("duration", Duration::from_millis(1500)).expect("failed setting Duration");
+  assert_eq!(nvs.get::<Duration>("duration").expect("failed getting Duration"), Duration::from_millis(1500));
+
+  let ssid: Ssid = "thirty-two-characters-long-ssid!".parse().unwrap();
+  nvs.set("ssid", ssid).expect("failed setting Ssid");
+  assert_eq!(nvs.get::<Ssid>("ssid").expect("failed getting Ssid"), ssid);
+
+  let password: Password = "password".parse().unwrap();
+  nvs.set("password", password).expect("failed setting Password");
+  assert_eq!(nvs.get::<Password>("password").expect("failed getting Password").as_str(), password.as_str());
+
+  println!("Success: Ssid and Password");
+
+  nvs.set("option", None::<u32>).expect("failed removing Option<u32>");
+  assert_eq!(nvs.get::<Option<u32>>("option").expect("failed getting Option<u32>"), None);
+
+  assert!(matches!(nvs.get::<[u8; 3]>("[u8; 4]"), Err(NvsError::TypeMismatch)), "got [u8; 3] from a 4 byte blob");
+  assert!(matches!(nvs.get::<u8>("missing"), Err(NvsError::NotFound)));
+  assert!(matches!(nvs.set("sixteen_chars_ky", 0u8), Err(NvsError::KeyTooLong(16))));
+  assert!(matches!(nvs.set("", 0u8), Err(NvsError::InvalidName)));
+
+  nvs.set(esp_idf_hal::nvs_key!("fifteen_chars_k"), 15u8).expect("failed setting key with 15 characters");
+
+  let res = nvs.migrate(&[
+    &|tx: &mut Transaction<'_>| tx.set("migrated", 1u8),
+    &|tx: &mut Transaction<'_>| {
+      tx.set("migrated", 2u8)?;
+      tx.set("invalid\0key", 0u8)
+    },
+  ]);
+
+  assert!(matches!(res, Err(MigrationError::Failed { version: 2, .. })), "second migration did not fail");
+  assert_eq!(nvs.schema_version().unwrap(), 1);
+  assert_eq!(nvs.get::<u8>("migrated").unwrap(), 1, "failed migration was not rolled back");
+
+  println!("Success: migrations");
 }
@@ -2,9 +2,7 @@
 
 use std::time::Duration;
 
-use futures::executor::block_on;
-
-use esp_idf_hal::wifi::*;
+use esp_idf_hal::{executor::block_on, wifi::*};
 
 #[no_mangle]
 fn app_main() {
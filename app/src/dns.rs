@@ -1,13 +1,11 @@
 use std::io;
-use std::net::{Ipv4Addr, UdpSocket, SocketAddr};
-use std::time::Duration;
-use std::thread;
+use std::net::{Ipv4Addr, SocketAddr};
 
-use esp_idf_hal::interface::Interface;
+use esp_idf_hal::{executor::UdpSocket, interface::Interface};
 
 use dnsparse::{Header, HeaderKind, Answer, QueryKind, QueryClass, Message, OpCode, ResponseCode};
 
-pub fn handle_request(socket: &UdpSocket, src: SocketAddr, request: Message, ip: &Ipv4Addr) -> io::Result<usize> {
+pub async fn handle_request(socket: &UdpSocket, src: SocketAddr, request: Message, ip: &Ipv4Addr) -> io::Result<usize> {
   let response_header = Header::builder()
     .id(request.header().id())
     .kind(HeaderKind::Response)
@@ -48,30 +46,24 @@ pub fn handle_request(socket: &UdpSocket, src: SocketAddr, request: Message, ip:
     }
   }
 
-  socket.send_to(&response, src)
+  socket.send_to(&response, src).await
 }
 
-pub fn server() {
+pub async fn server() {
   println!("Starting DNS server …");
 
   let socket = UdpSocket::bind("0.0.0.0:53").unwrap();
-  socket.set_read_timeout(Some(Duration::from_secs(30))).unwrap();
-  socket.set_write_timeout(Some(Duration::from_secs(30))).unwrap();
 
   let ip = *Interface::Ap.ip_info().ip();
   println!("IP: {:?}", ip);
 
   loop {
-    thread::yield_now();
-
     let mut buf = Message::BUFFER;
 
-    let (len, src) = match socket.recv_from(&mut buf) {
+    let (len, src) = match socket.recv_from(&mut buf).await {
       Ok(ok) => ok,
       Err(err) => {
-        if err.kind() != std::io::ErrorKind::WouldBlock {
-          eprintln!("Receiving DNS request failed: {}", err);
-        }
+        eprintln!("Receiving DNS request failed: {}", err);
         continue
       }
     };
@@ -83,7 +75,7 @@ pub fn server() {
       continue
     };
 
-    if let Err(err) = handle_request(&socket, src, request, &ip) {
+    if let Err(err) = handle_request(&socket, src, request, &ip).await {
       eprintln!("Error sending response to '{:?}': {}", src, err);
     }
   }
@@ -2,17 +2,12 @@
 #![cfg_attr(not(doc), no_main)]
 
 use std::sync::{Arc, Mutex};
-use std::thread::{self, sleep};
 use std::time::Duration;
 use std::net::{Ipv4Addr, SocketAddrV4};
-use std::net::TcpListener;
 
-use embedded_hal::digital::v2::OutputPin;
 use macaddr::MacAddr;
 
-use esp_idf_hal::{*, interface::*, nvs::*, wifi::*};
-
-use futures::executor::block_on;
+use esp_idf_hal::{*, executor::{block_on, TcpListener}, interface::*, ledc::*, nvs::*, watchdog::*, wifi::*};
 
 mod wifi_manager;
 use wifi_manager::*;
@@ -29,12 +24,10 @@ pub fn app_main() {
 }
 
 async fn rust_blink_and_write() -> Result<!, EspError> {
-    use esp32_hal::{target, gpio::GpioExt};
-
-    let dp = unsafe { target::Peripherals::steal() };
-    let pins = dp.GPIO.split();
+    let pins = gpio::Pins::take().unwrap();
 
-    let mut gpio = pins.gpio22.into_open_drain_output();
+    let led_timer = ledc::Timer::new(TimerNum::Timer0, TimerConfig::new().frequency(5_000))?;
+    let led = Arc::new(StatusLed::new(led_timer.channel(ChannelNum::Channel0, pins.gpio22)?, true)?);
 
     let mut nvs = NonVolatileStorage::default();
 
@@ -42,11 +35,13 @@ async fn rust_blink_and_write() -> Result<!, EspError> {
 
     println!("AP started.");
 
-    let namespace = nvs.namespace("wifi")?;
+    let namespace = nvs.namespace_migrated("wifi", &[
+      &wifi_manager::store_credentials_as_strings,
+    ]).expect("failed to migrate WiFi settings");
     println!("namespace: {:?}", namespace);
 
-    let t = thread::Builder::new()
-      .name("hello_thread".into())
+    let t = task::Builder::new()
+      .name("hello_thread")
       .stack_size(2048)
       .spawn(|| {
         println!("HELLO, WORLD!");
@@ -54,29 +49,13 @@ async fn rust_blink_and_write() -> Result<!, EspError> {
       });
 
     println!("Thread spawn result: {:?}", t);
-    println!("Thread join result: {:?}", t.map(|t| t.join().unwrap()));
-
-    thread::Builder::new()
-      .name("dns_thread".into())
-      .stack_size(8192)
-      .spawn(dns::server)
-      .unwrap();
-
-    thread::Builder::new()
-      .name("blink_thread".into())
-      .stack_size(1024)
-      .spawn(move || {
-        loop {
-          gpio.set_low().unwrap();
-          sleep(Duration::from_millis(100));
-          gpio.set_high().unwrap();
-          sleep(Duration::from_secs(3));
-        }
-      })
-      .unwrap();
+    if let Ok(t) = t {
+      let (result, high_water_mark) = t.join_with_high_water_mark();
+      println!("Thread join result: {:?} (stack high-water mark: {} bytes)", result.unwrap(), high_water_mark);
+    }
 
-    thread::Builder::new()
-      .name("server_thread".into())
+    task::Builder::new()
+      .name("server_thread")
       .stack_size(8192)
       .spawn(move || block_on(async {
         let mac = MacAddr::from(Interface::Ap);
@@ -87,58 +66,70 @@ async fn rust_blink_and_write() -> Result<!, EspError> {
           .ssid(ap_ssid)
           .build();
 
-        let wifi_storage = namespace;
+        let wifi_settings = Settings::<WifiSettings>::new(namespace).expect("failed to load WiFi settings");
 
-        let ssid = wifi_storage.get::<String>("ssid").ok().and_then(|s| Ssid::from_bytes(s.as_bytes()).ok());
-        let password = wifi_storage.get::<String>("password").ok().and_then(|s| Password::from_bytes(s.as_bytes()).ok());
-
-        if let (Some(ssid), Some(password)) = (ssid, password) {
-          match wifi_manager::connect_ssid_password(&mut wifi, ssid, password).await {
+        if let WifiSettings { ssid: Some(ssid), password: Some(password) } = wifi_settings.get() {
+          match wifi_manager::connect_ssid_password(&mut wifi, &led, ssid, password).await {
             Ok(_) => (),
             Err(_) => {
               println!("Starting Access Point '{}' …", ap_config.ssid());
               wifi.start_ap(ap_config).expect("Failed to start access point");
+              led.set_pattern(Pattern::Provisioning);
             },
           };
         } else {
           println!("Starting Access Point '{}' …", ap_config.ssid());
           wifi.start_ap(ap_config).expect("Failed to start access point");
+          led.set_pattern(Pattern::Provisioning);
         }
 
-        let stream = TcpListener::bind(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 80)).expect("failed starting TCP listener");
+        executor::spawn(dns::server());
+
+        let listener = TcpListener::bind(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 80)).expect("failed starting TCP listener");
 
         let wifi = Arc::new(Mutex::new(wifi));
-        let wifi_storage = Arc::new(Mutex::new(wifi_storage));
 
-        loop {
-          thread::yield_now();
+        {
+          let wifi = Arc::clone(&wifi);
+          let led = Arc::clone(&led);
+          let updates = wifi_settings.subscribe();
+
+          task::Builder::new()
+            .name("wifi_thread")
+            .stack_size(8192)
+            .spawn(move || block_on(async {
+              for settings in updates {
+                if let WifiSettings { ssid: Some(ssid), password: Some(password) } = settings {
+                  reconnect(&mut *wifi.lock().unwrap(), &led, ssid, password).await;
+                }
+              }
+            }))
+            .unwrap();
+        }
 
-          let client = stream.accept().and_then(|(client, addr)| {
-            client.set_read_timeout(Some(Duration::from_secs(30)))?;
-            client.set_write_timeout(Some(Duration::from_secs(30)))?;
-            Ok((client, addr))
-          });
+        // Feeding the watchdog from a task on the executor ensures that it is not blocked.
+        let watchdog = Watchdog::subscribe().expect("failed to subscribe to task watchdog");
+        let mut feed_interval = timer::Interval::new(Duration::from_secs(1)).expect("failed to create watchdog timer");
+        executor::spawn(async move {
+          loop {
+            watchdog.feed();
+            feed_interval.tick().await;
+          }
+        });
 
-          match client {
+        loop {
+          match listener.accept().await {
             Ok((client, addr)) => {
-              let wifi_storage = Arc::clone(&wifi_storage);
-              let wifi = Arc::clone(&wifi);
-
-              thread::Builder::new()
-                .stack_size(8192)
-                .spawn(move || block_on(async {
-                  handle_request(client, addr, wifi_storage, wifi).await
-                }))
-                .unwrap();
+              executor::spawn(handle_request(client, addr, wifi_settings.clone(), Arc::clone(&wifi)));
             },
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => (),
             Err(e) => eprintln!("Client error: {}", e),
           }
         }
       }))
       .unwrap();
 
+    let mut interval = timer::Interval::new(Duration::from_secs(5))?;
     loop {
-      sleep(Duration::from_secs(5))
+      interval.tick().await;
     }
   }
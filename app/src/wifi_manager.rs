@@ -1,10 +1,35 @@
-use std::io::{self, Read, Write};
-use std::net::{SocketAddr, TcpStream};
-use std::sync::{Arc, Mutex};
+use std::ffi::CString;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
 use std::str;
 use std::time::Duration;
 
-use esp_idf_hal::{nvs::NameSpace, wifi::*};
+use esp_idf_hal::{executor::{self, TcpStream}, ledc::{Pattern, StatusLed}, nvs::{NameSpace, NvsError, Persist, Settings, Transaction}, nvs_key, wifi::*};
+
+const SSID_KEY: &str = nvs_key!("ssid");
+const PASSWORD_KEY: &str = nvs_key!("password");
+
+/// WiFi credentials stored in the `wifi` namespace.
+#[derive(Debug, Clone, Default)]
+pub struct WifiSettings {
+  pub ssid: Option<Ssid>,
+  pub password: Option<Password>,
+}
+
+impl Persist for WifiSettings {
+  fn load(wifi_storage: &NameSpace) -> Result<Self, NvsError> {
+    let ssid = wifi_storage.get::<Option<Ssid>>(SSID_KEY)?;
+    let password = wifi_storage.get::<Option<Password>>(PASSWORD_KEY)?;
+
+    Ok(Self { ssid, password })
+  }
+
+  fn store(&self, wifi_storage: &mut Transaction<'_>) -> Result<(), NvsError> {
+    wifi_storage.set(SSID_KEY, &self.ssid)?;
+    wifi_storage.set(PASSWORD_KEY, &self.password)
+  }
+}
 
 /// Try parsing `Ssid` and `Password` from URL parameters.
 fn ssid_and_password(params: &[u8]) -> (Option<Ssid>, Option<Password>) {
@@ -22,19 +47,30 @@ fn ssid_and_password(params: &[u8]) -> (Option<Ssid>, Option<Password>) {
   (ssid, password)
 }
 
-fn write_ok(client: &mut TcpStream) -> io::Result<()> {
+fn write_ok(client: &mut Vec<u8>) -> io::Result<()> {
   writeln!(client, "HTTP/1.1 200 OK")?;
   writeln!(client, "Content-Type: text/html")?;
   writeln!(client)
 }
 
-fn write_template(client: &mut TcpStream) -> io::Result<()> {
+fn write_template(client: &mut Vec<u8>) -> io::Result<()> {
   write_ok(client)?;
   writeln!(client, "{}", include_str!("index.html"))
 }
 
-async fn handle_index(wifi: Arc<Mutex<Wifi>>, mut client: TcpStream) -> io::Result<()> {
-  write_template(&mut client)?;
+/// Lock `wifi` without blocking the executor while another task or thread holds the lock.
+async fn lock_wifi(wifi: &Mutex<Wifi>) -> MutexGuard<'_, Wifi> {
+  loop {
+    match wifi.try_lock() {
+      Ok(guard) => return guard,
+      Err(TryLockError::Poisoned(err)) => return err.into_inner(),
+      Err(TryLockError::WouldBlock) => executor::sleep(Duration::from_millis(50)).await,
+    }
+  }
+}
+
+async fn handle_index(wifi: Arc<Mutex<Wifi>>, client: &mut Vec<u8>) -> io::Result<()> {
+  write_template(client)?;
 
   writeln!(client, r##"
     <script type='text/javascript'>
@@ -58,7 +94,7 @@ async fn handle_index(wifi: Arc<Mutex<Wifi>>, mut client: TcpStream) -> io::Resu
 
   writeln!(client, "<datalist id='ssids'>")?;
 
-  let wifi = &mut *wifi.lock().unwrap();
+  let mut wifi = lock_wifi(&wifi).await;
   match wifi.scan(&scan_config).await {
     Ok(mut aps) => {
       aps.sort_by(|a, b| a.ssid().cmp(b.ssid()));
@@ -79,7 +115,7 @@ async fn handle_index(wifi: Arc<Mutex<Wifi>>, mut client: TcpStream) -> io::Resu
   Ok(())
 }
 
-fn handle_hotspot_detect(mut client: TcpStream) -> io::Result<()> {
+fn handle_hotspot_detect(client: &mut Vec<u8>) -> io::Result<()> {
   writeln!(client, "HTTP/1.1 303 See Other")?;
   writeln!(client, "Location: /")?;
   writeln!(client, "Content-Type: text/plain")?;
@@ -87,39 +123,46 @@ fn handle_hotspot_detect(mut client: TcpStream) -> io::Result<()> {
   writeln!(client, "Redirecting …")
 }
 
-fn handle_connection_error(mut client: TcpStream, message: &str) -> io::Result<()> {
-  write_template(&mut client)?;
+fn handle_connection_error(client: &mut Vec<u8>, message: &str) -> io::Result<()> {
+  write_template(client)?;
   writeln!(client, "<p class='error'>Failed to connect.{} <a href='./'>Retry?</a></p>", message)
 }
 
-fn handle_connection_success(mut client: TcpStream, message: &str) -> io::Result<()> {
-  write_template(&mut client)?;
+fn handle_connection_success(client: &mut Vec<u8>, message: &str) -> io::Result<()> {
+  write_template(client)?;
   writeln!(client, "<p class='success'>Success.{}</p>", message)
 }
 
-fn handle_not_found(mut client: TcpStream) -> io::Result<()> {
+fn handle_not_found(client: &mut Vec<u8>) -> io::Result<()> {
   writeln!(client, "HTTP/1.1 404 Not Found")?;
   writeln!(client)
 }
 
-fn handle_internal_error(mut client: TcpStream) -> io::Result<()> {
+fn handle_internal_error(client: &mut Vec<u8>) -> io::Result<()> {
   writeln!(client, "HTTP/1.1 500 INTERNAL SERVER ERROR")?;
   writeln!(client)
 }
 
 pub async fn handle_request(
   mut client: TcpStream, addr: SocketAddr,
-  wifi_storage: Arc<Mutex<NameSpace>>,
+  wifi_settings: Settings<WifiSettings>,
   wifi: Arc<Mutex<Wifi>>,
 ) {
   println!("Handling request from {} …", addr);
 
+  let mut response = Vec::new();
+
   let mut buf: [u8; 1024] = [0; 1024];
-  let len = match client.read(&mut buf) {
-    Ok(len) => len,
-    Err(err) => {
+  let len = match executor::timeout(Duration::from_secs(30), client.read(&mut buf)).await {
+    Ok(Ok(len)) => len,
+    Ok(Err(err)) => {
       eprintln!("Error reading from client: {:?}", err);
-      let _ = handle_internal_error(client);
+      let _ = handle_internal_error(&mut response);
+      let _ = client.write_all(&response).await;
+      return;
+    },
+    Err(_) => {
+      eprintln!("Timed out reading from client.");
       return;
     },
   };
@@ -134,41 +177,32 @@ pub async fn handle_request(
       println!("{} {} - {} bytes", method, path, len);
 
       match (method, path) {
-        ("GET", "/") => handle_index(Arc::clone(&wifi), client).await,
-        ("GET", "/hotspot-detect.html") => handle_hotspot_detect(client),
+        ("GET", "/") => handle_index(Arc::clone(&wifi), &mut response).await,
+        ("GET", "/hotspot-detect.html") => handle_hotspot_detect(&mut response),
         ("POST", "/connect") => {
           let body = &buf[header_len..len];
 
           if let (Some(ssid), Some(password)) = ssid_and_password(body) {
-            let mut wifi_storage = wifi_storage.lock().unwrap();
-
-            wifi_storage.set::<&str>("ssid", &ssid.as_str()).expect("Failed saving SSID");
-            wifi_storage.set::<&str>("password", &password.as_str()).expect("Failed saving password");
-
-            let wifi = &mut *wifi.lock().unwrap();
-            let ap_config = wifi.as_ap().unwrap().config();
-
             let message = format!(" Connecting to “{}” …", ssid.as_str());
-            let res = handle_connection_success(client, &message);
+            let res = handle_connection_success(&mut response, &message);
 
-            wifi.stop_ap();
-            match connect_ssid_password(wifi, ssid.clone(), password).await {
-              Ok(_) => (),
-              Err(err) => {
-                eprintln!("Failed to connect to {}: {}", ssid.as_str(), err);
-                wifi.start_ap(ap_config).expect("Failed to start access point");
-              }
-            }
+            // Connecting is handled by the subscriber in `wifi_thread`.
+            wifi_settings.set(WifiSettings { ssid: Some(ssid), password: Some(password) }).expect("Failed saving WiFi settings");
 
             res
           } else {
-            handle_connection_error(client, " SSID is empty.")
+            handle_connection_error(&mut response, " SSID is empty.")
           }
         },
-        _ => handle_not_found(client),
+        _ => handle_not_found(&mut response),
       }
     }
-    _ => handle_internal_error(client),
+    _ => handle_internal_error(&mut response),
+  };
+
+  let res = match res {
+    Ok(()) => executor::timeout(Duration::from_secs(30), client.write_all(&response)).await.unwrap_or_else(|err| Err(err.into())),
+    Err(err) => Err(err),
   };
 
   if let Err(err) = res {
@@ -176,22 +210,65 @@ pub async fn handle_request(
   }
 }
 
+/// Migration converting the `ssid` and `password` keys from blobs to NVS strings.
+///
+/// Fails if a value contains a `NUL` byte, so that the credentials are kept as they are.
+pub fn store_credentials_as_strings(wifi_storage: &mut Transaction<'_>) -> Result<(), NvsError> {
+  for key in &[SSID_KEY, PASSWORD_KEY] {
+    let value = match wifi_storage.get::<Vec<u8>>(key) {
+      Ok(value) => value,
+      // Not stored, or already stored as a string.
+      Err(NvsError::NotFound) | Err(NvsError::TypeMismatch) => continue,
+      Err(err) => return Err(err),
+    };
+
+    let value = CString::new(value).map_err(|_| NvsError::TypeMismatch)?;
+    wifi_storage.remove(key)?;
+    wifi_storage.set(key, value)?;
+  }
+
+  Ok(())
+}
+
+/// Stop the access point and connect to `ssid`, restarting the access point if connecting fails.
+pub async fn reconnect(wifi: &mut Wifi, led: &StatusLed, ssid: Ssid, password: Password) {
+  let ap_config = wifi.as_ap().map(|ap| ap.config());
+
+  wifi.stop_ap();
+  match connect_ssid_password(wifi, led, ssid, password).await {
+    Ok(_) => (),
+    Err(err) => {
+      eprintln!("Failed to connect to {}: {}", ssid.as_str(), err);
+
+      if let Some(ap_config) = ap_config {
+        wifi.start_ap(ap_config).expect("Failed to start access point");
+        led.set_pattern(Pattern::Provisioning);
+      }
+    }
+  }
+}
+
 /// Try to connect to an access point with the given `ssid` and `password` in station mode, otherwise revert to access point mode.
-pub async fn connect_ssid_password(wifi: &mut Wifi, ssid: Ssid, password: Password) -> Result<ConnectionInfo, WifiError> {
+pub async fn connect_ssid_password(wifi: &mut Wifi, led: &StatusLed, ssid: Ssid, password: Password) -> Result<ConnectionInfo, WifiError> {
   let sta_config = StaConfig::builder()
     .ssid(ssid)
     .password(password)
     .build();
 
   eprintln!("Connecting to '{}' with password '{}' …", sta_config.ssid(), sta_config.password());
+  led.set_pattern(Pattern::Connecting);
 
   match wifi.connect_sta(sta_config).await {
     Ok(connection_info) => {
       eprintln!("Connected to '{}' ({}) on channel {} with IP '{}'.",
                 connection_info.ssid(), connection_info.bssid(),
                 connection_info.channel(), connection_info.ip_info().ip());
+      led.set_pattern(Pattern::Connected);
       Ok(connection_info)
     },
-    Err(err) => Err(err),
+    Err(err) => {
+      led.set_pattern(Pattern::Error);
+      Err(err)
+    },
   }
 }
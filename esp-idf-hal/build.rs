@@ -1,11 +1,228 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A `#define` from an SDK header.
+struct Define {
+  value: String,
+  description: String,
+}
+
+fn parse_define(line: &str) -> Option<(String, Define)> {
+  let line = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix("define")?;
+
+  let line = line.trim_start();
+  let name_end = line.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(line.len());
+  let (name, rest) = line.split_at(name_end);
+
+  // Skip function-like macros.
+  if name.is_empty() || rest.starts_with('(') {
+    return None
+  }
+
+  let (value, comment) = match (rest.find("/*"), rest.find("//")) {
+    (Some(i), Some(j)) => rest.split_at(i.min(j)),
+    (Some(i), None) | (None, Some(i)) => rest.split_at(i),
+    (None, None) => (rest, ""),
+  };
+
+  let description = comment
+    .trim_start_matches(&['/', '*', '!', '<'][..])
+    .trim_end()
+    .trim_end_matches("*/")
+    .trim();
+
+  Some((name.to_owned(), Define { value: value.trim().to_owned(), description: description.to_owned() }))
+}
+
+fn collect_defines(dir: &Path, defines: &mut HashMap<String, Define>) -> io::Result<()> {
+  let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+  entries.sort_by_key(|entry| entry.file_name());
+
+  for entry in entries {
+    let path = entry.path();
+
+    if entry.file_type()?.is_dir() {
+      if entry.file_name() != "test" {
+        collect_defines(&path, defines)?;
+      }
+    } else if path.extension() == Some("h".as_ref()) {
+      for line in fs::read_to_string(&path).unwrap_or_default().lines() {
+        if let Some((name, define)) = parse_define(line) {
+          if name.starts_with("ESP_ERR_") || name == "ESP_FAIL" || name == "ESP_OK" {
+            defines.entry(name).or_insert(define);
+          }
+        }
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Evaluates the simple integer expressions used for error codes,
+/// e.g. `(ESP_ERR_NVS_BASE + 0x01)` or `-1`.
+struct Evaluator<'d> {
+  defines: &'d HashMap<String, Define>,
+  depth: usize,
+}
+
+impl Evaluator<'_> {
+  fn tokenize(value: &str) -> Vec<String> {
+    let value = value.replace("(esp_err_t)", "");
+
+    let mut tokens = Vec::new();
+    let mut chars = value.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+      if c.is_whitespace() {
+        chars.next();
+      } else if c.is_ascii_alphanumeric() || c == '_' {
+        let mut token = String::new();
+        while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_') {
+          token.push(c);
+          chars.next();
+        }
+        tokens.push(token);
+      } else {
+        tokens.push(c.to_string());
+        chars.next();
+      }
+    }
+
+    tokens
+  }
+
+  fn eval(&mut self, value: &str) -> Option<i64> {
+    let tokens = Self::tokenize(value);
+    let mut pos = 0;
+    let result = self.expr(&tokens, &mut pos)?;
+    if pos == tokens.len() { Some(result) } else { None }
+  }
+
+  fn expr(&mut self, tokens: &[String], pos: &mut usize) -> Option<i64> {
+    let mut value = self.term(tokens, pos)?;
+
+    while let Some(op) = tokens.get(*pos) {
+      match op.as_str() {
+        "+" => { *pos += 1; value += self.term(tokens, pos)? },
+        "-" => { *pos += 1; value -= self.term(tokens, pos)? },
+        _ => break,
+      }
+    }
+
+    Some(value)
+  }
+
+  fn term(&mut self, tokens: &[String], pos: &mut usize) -> Option<i64> {
+    let token = tokens.get(*pos)?;
+    *pos += 1;
+
+    match token.as_str() {
+      "-" => self.term(tokens, pos).map(|v| -v),
+      "(" => {
+        let value = self.expr(tokens, pos)?;
+        if tokens.get(*pos)? != ")" {
+          return None
+        }
+        *pos += 1;
+        Some(value)
+      },
+      token if token.starts_with(|c: char| c.is_ascii_digit()) => {
+        let token = token.trim_end_matches(&['u', 'U', 'l', 'L'][..]);
+        match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+          Some(hex) => i64::from_str_radix(hex, 16).ok(),
+          None => token.parse().ok(),
+        }
+      },
+      name => {
+        if self.depth > 16 {
+          return None
+        }
+
+        let define = self.defines.get(name)?;
+        self.depth += 1;
+        let value = self.eval(&define.value);
+        self.depth -= 1;
+        value
+      },
+    }
+  }
+}
+
+/// Generate a table of `(code, name, description)` sorted by code, the same way
+/// `tools/gen_esp_err_to_name.py` in ESP-IDF does, which is only compiled for `target_device`.
+fn generate_table(sdk_path: &Path, target_device: &str, out: &mut String) {
+  let mut defines = HashMap::new();
+
+  let components = sdk_path.join("components");
+  if components.is_dir() {
+    collect_defines(&components, &mut defines).expect("failed reading SDK headers");
+  }
+
+  let mut names = defines.keys().filter(|name| !name.ends_with("_BASE")).collect::<Vec<_>>();
+  names.sort();
+
+  let mut table = BTreeMap::new();
+
+  for name in names {
+    let code = match (Evaluator { defines: &defines, depth: 0 }).eval(&defines[name].value) {
+      Some(code) if i32::try_from(code).is_ok() => code,
+      _ => continue,
+    };
+
+    table.entry(code).or_insert((name, &defines[name].description));
+  }
+
+  // Other targets, e.g. documentation builds, use the ESP32 table.
+  let cfg = match target_device {
+    "esp32" => r#"not(target_device = "esp8266")"#.to_owned(),
+    target_device => format!("target_device = {:?}", target_device),
+  };
+
+  writeln!(out, "#[cfg({})]", cfg).unwrap();
+  writeln!(out, "pub(crate) static ESP_ERR_TABLE: &[(esp_err_t, &str, &str)] = &[").unwrap();
+  for (code, (name, description)) in table {
+    writeln!(out, "  ({}, {:?}, {:?}),", code, name, description).unwrap();
+  }
+  writeln!(out, "];").unwrap();
+}
 
 fn main() {
   let target_device = match env::var("TARGET").expect("TARGET not set").as_ref() {
-    "xtensa-esp32-none-elf" => "esp32",
-    "xtensa-esp8266-none-elf" => "esp8266",
-    _ => return,
+    "xtensa-esp32-none-elf" => Some("esp32"),
+    "xtensa-esp8266-none-elf" => Some("esp8266"),
+    _ => None,
   };
 
-  println!(r#"cargo:rustc-cfg=target_device="{}""#, target_device);
+  if let Some(target_device) = target_device {
+    println!(r#"cargo:rustc-cfg=target_device="{}""#, target_device);
+  }
+
+  println!("cargo:rerun-if-changed=build.rs");
+
+  let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set"));
+  let mut out = String::new();
+
+  for &(device, sdk) in &[("esp32", "../esp-idf"), ("esp8266", "../ESP8266_RTOS_SDK")] {
+    let components = manifest_dir.join(sdk).join("components");
+
+    if components.is_dir() {
+      println!("cargo:rerun-if-changed={}", components.display());
+    } else if target_device.unwrap_or("esp32") == device {
+      println!(
+        "cargo:warning={} not found, error names and descriptions are limited to those known by `EspErrorKind`",
+        components.display(),
+      );
+    }
+
+    generate_table(&manifest_dir.join(sdk), device, &mut out);
+  }
+
+  let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR not set"));
+  fs::write(out_dir.join("esp_err_table.rs"), out).expect("failed writing esp_err table");
 }
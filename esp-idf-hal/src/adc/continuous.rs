@@ -0,0 +1,166 @@
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+use core::time::Duration;
+
+use embedded_hal::adc::Channel;
+use esp_idf_bindgen::*;
+
+use crate::EspError;
+use crate::sync;
+
+use super::{Adc1, Attenuation, Calibration, CalibrationSource, Width};
+
+/// The built-in ADC mode is only supported by I2S0.
+const I2S_PORT: i2s_port_t = i2s_port_t::I2S_NUM_0;
+
+/// Samples contain the channel number in the upper 4 bits.
+const SAMPLE_MASK: u16 = 0x0fff;
+
+/// Configuration for a [`ContinuousAdc`](struct.ContinuousAdc.html).
+#[derive(Debug, Clone)]
+pub struct ContinuousConfig {
+  sample_rate: u32,
+  attenuation: Attenuation,
+  dma_buffer_count: usize,
+  dma_buffer_len: usize,
+}
+
+impl Default for ContinuousConfig {
+  fn default() -> Self {
+    Self { sample_rate: 20_000, attenuation: Attenuation::Db11, dma_buffer_count: 4, dma_buffer_len: 256 }
+  }
+}
+
+impl ContinuousConfig {
+  /// 20 kHz with an attenuation of 11 dB, using four DMA buffers of 256 samples.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sample rate in Hz.
+  pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+    self.sample_rate = sample_rate;
+    self
+  }
+
+  pub fn attenuation(mut self, attenuation: Attenuation) -> Self {
+    self.attenuation = attenuation;
+    self
+  }
+
+  /// Number of DMA buffers, between 2 and 128.
+  pub fn dma_buffer_count(mut self, count: usize) -> Self {
+    self.dma_buffer_count = count;
+    self
+  }
+
+  /// Number of samples per DMA buffer, between 8 and 1024.
+  pub fn dma_buffer_len(mut self, len: usize) -> Self {
+    self.dma_buffer_len = len;
+    self
+  }
+}
+
+/// ADC1 sampling a single pin continuously into DMA buffers, using the built-in ADC mode of I2S0.
+///
+/// Samples are 12 bits wide. If they are not read fast enough, the oldest DMA buffer is overwritten.
+///
+/// ```ignore
+/// let mut adc = ContinuousAdc::new(units.adc1, pins.gpio34.into_analog()?, adc::ContinuousConfig::new())?;
+///
+/// let mut samples = [0; 256];
+/// let n = adc.read(&mut samples, None)?;
+/// ```
+#[derive(Debug)]
+pub struct ContinuousAdc<P> {
+  calibration: Calibration,
+  unit: Adc1,
+  pin: P,
+}
+
+impl<P> ContinuousAdc<P>
+where
+  P: Channel<Adc1, ID = adc1_channel_t>,
+{
+  /// Install the I2S driver and start sampling `pin`.
+  pub fn new(unit: Adc1, pin: P, config: ContinuousConfig) -> Result<Self, EspError> {
+    let mode = i2s_mode_t::I2S_MODE_MASTER as u32 | i2s_mode_t::I2S_MODE_RX as u32 | i2s_mode_t::I2S_MODE_ADC_BUILT_IN as u32;
+
+    // `mode` is a combination of flags, which is not a valid `i2s_mode_t`, so the
+    // configuration is only written through raw pointers and never read as a whole.
+    let mut i2s_config = MaybeUninit::<i2s_config_t>::zeroed();
+    unsafe {
+      let c = i2s_config.as_mut_ptr();
+      ptr::addr_of_mut!((*c).mode).cast::<u32>().write(mode);
+      ptr::addr_of_mut!((*c).sample_rate).write(config.sample_rate as _);
+      ptr::addr_of_mut!((*c).bits_per_sample).write(i2s_bits_per_sample_t::I2S_BITS_PER_SAMPLE_16BIT);
+      ptr::addr_of_mut!((*c).channel_format).write(i2s_channel_fmt_t::I2S_CHANNEL_FMT_ONLY_LEFT);
+      ptr::addr_of_mut!((*c).communication_format).write(i2s_comm_format_t::I2S_COMM_FORMAT_STAND_I2S);
+      ptr::addr_of_mut!((*c).dma_buf_count).write(config.dma_buffer_count as _);
+      ptr::addr_of_mut!((*c).dma_buf_len).write(config.dma_buffer_len as _);
+    }
+
+    esp_ok!(i2s_driver_install(I2S_PORT, i2s_config.as_ptr(), 0, ptr::null_mut()))?;
+
+    let start = || -> Result<(), EspError> {
+      esp_ok!(i2s_set_adc_mode(adc_unit_t::ADC_UNIT_1, P::channel()))?;
+      esp_ok!(adc1_config_width(Width::Bits12.into()))?;
+      esp_ok!(adc1_config_channel_atten(P::channel(), config.attenuation.into()))?;
+      esp_ok!(i2s_adc_enable(I2S_PORT))
+    };
+
+    if let Err(err) = start() {
+      let _ = esp_ok!(i2s_driver_uninstall(I2S_PORT));
+      return Err(err)
+    }
+
+    let calibration = Calibration::new(adc_unit_t::ADC_UNIT_1, config.attenuation, Width::Bits12);
+
+    Ok(Self { calibration, unit, pin })
+  }
+
+  /// Read raw samples into `buf`, waiting at most `timeout` for them, and return the number of samples read.
+  ///
+  /// `None` means waiting until `buf` is full.
+  pub fn read(&mut self, buf: &mut [u16], timeout: Option<Duration>) -> Result<usize, EspError> {
+    let mut bytes_read = 0;
+    esp_ok!(i2s_read(I2S_PORT, buf.as_mut_ptr() as *mut _, mem::size_of_val(buf), &mut bytes_read, sync::ticks(timeout)))?;
+
+    let samples = &mut buf[..bytes_read / mem::size_of::<u16>()];
+    for sample in samples.iter_mut() {
+      *sample &= SAMPLE_MASK;
+    }
+
+    Ok(samples.len())
+  }
+
+  /// Convert a raw sample to millivolts, calibrated using the values stored in eFuse.
+  pub fn to_millivolts(&self, raw: u16) -> u16 {
+    self.calibration.to_millivolts(raw)
+  }
+
+  /// The source of the calibration values used by [`to_millivolts`](#method.to_millivolts).
+  pub fn calibration_source(&self) -> CalibrationSource {
+    self.calibration.source
+  }
+
+  /// Stop sampling and release the unit and the pin.
+  pub fn release(self) -> (Adc1, P) {
+    let this = mem::ManuallyDrop::new(self);
+    stop();
+
+    // SAFETY: `this` is not used or dropped afterwards.
+    unsafe { (ptr::read(&this.unit), ptr::read(&this.pin)) }
+  }
+}
+
+fn stop() {
+  let _ = esp_ok!(i2s_adc_disable(I2S_PORT));
+  let _ = esp_ok!(i2s_driver_uninstall(I2S_PORT));
+}
+
+impl<P> Drop for ContinuousAdc<P> {
+  fn drop(&mut self) {
+    stop();
+  }
+}
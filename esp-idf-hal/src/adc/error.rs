@@ -0,0 +1,37 @@
+use std::fmt;
+
+use crate::EspError;
+
+/// The error type for ADC readings.
+#[derive(Debug, Clone)]
+pub enum AdcError {
+  /// ADC2 is used by the WiFi driver, which makes it unavailable while a
+  /// [`Wifi`](../wifi/struct.Wifi.html) instance exists.
+  Adc2Unavailable,
+  /// Any other error.
+  Esp(EspError),
+}
+
+impl From<EspError> for AdcError {
+  fn from(esp_error: EspError) -> Self {
+    Self::Esp(esp_error)
+  }
+}
+
+impl fmt::Display for AdcError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Adc2Unavailable => "ADC2 is unavailable while WiFi is active".fmt(f),
+      Self::Esp(esp_error) => esp_error.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for AdcError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Esp(esp_error) => Some(esp_error),
+      _ => None,
+    }
+  }
+}
@@ -0,0 +1,457 @@
+//! Analog readings using the IDF `adc` and `i2s` drivers.
+//!
+//! Pins are converted into [`Analog`](../gpio/struct.Analog.html) mode and read using an
+//! [`Adc`](struct.Adc.html), either as raw values or calibrated to millivolts using the
+//! reference voltage or two-point values stored in eFuse.
+//!
+//! ```ignore
+//! let pins = gpio::Pins::take().unwrap();
+//! let units = adc::Units::take().unwrap();
+//!
+//! let mut adc = Adc::new(units.adc1, adc::Config::new())?;
+//! let mut battery = pins.gpio35.into_analog()?;
+//!
+//! let millivolts = adc.read_millivolts(&mut battery)?;
+//! ```
+//!
+//! A single ADC1 channel can also be sampled continuously using DMA, see
+//! [`ContinuousAdc`](struct.ContinuousAdc.html).
+//!
+//! ADC2 is shared with the WiFi driver, so reading from ADC2 fails with
+//! [`AdcError::Adc2Unavailable`](enum.AdcError.html#variant.Adc2Unavailable)
+//! while a [`Wifi`](../wifi/struct.Wifi.html) instance exists.
+
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embedded_hal::adc::{Channel, OneShot};
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+use crate::gpio::{self, sealed::SealedPin, Analog};
+use crate::wifi;
+
+mod continuous;
+pub use continuous::*;
+mod error;
+pub use error::*;
+
+/// Default reference voltage in millivolts, used for calibration if none is stored in eFuse.
+const DEFAULT_VREF: u32 = 1100;
+
+/// Maximum number of channels of an ADC unit.
+const MAX_CHANNELS: usize = 10;
+
+/// Input attenuation, which determines the measurable voltage range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attenuation {
+  /// Up to about 800 mV.
+  Db0,
+  /// Up to about 1100 mV.
+  Db2_5,
+  /// Up to about 1350 mV.
+  Db6,
+  /// Up to about 2600 mV.
+  Db11,
+}
+
+impl Attenuation {
+  fn index(self) -> usize {
+    self as usize
+  }
+}
+
+impl From<Attenuation> for adc_atten_t {
+  fn from(attenuation: Attenuation) -> Self {
+    match attenuation {
+      Attenuation::Db0 => adc_atten_t::ADC_ATTEN_DB_0,
+      Attenuation::Db2_5 => adc_atten_t::ADC_ATTEN_DB_2_5,
+      Attenuation::Db6 => adc_atten_t::ADC_ATTEN_DB_6,
+      Attenuation::Db11 => adc_atten_t::ADC_ATTEN_DB_11,
+    }
+  }
+}
+
+/// Resolution of readings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+  Bits9,
+  Bits10,
+  Bits11,
+  Bits12,
+}
+
+impl From<Width> for adc_bits_width_t {
+  fn from(width: Width) -> Self {
+    match width {
+      Width::Bits9 => adc_bits_width_t::ADC_WIDTH_BIT_9,
+      Width::Bits10 => adc_bits_width_t::ADC_WIDTH_BIT_10,
+      Width::Bits11 => adc_bits_width_t::ADC_WIDTH_BIT_11,
+      Width::Bits12 => adc_bits_width_t::ADC_WIDTH_BIT_12,
+    }
+  }
+}
+
+/// The source of the calibration values used to convert readings to millivolts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationSource {
+  /// Two-point values stored in eFuse.
+  TwoPoint,
+  /// Reference voltage stored in eFuse.
+  Vref,
+  /// The default reference voltage of 1100 mV.
+  Default,
+}
+
+impl From<esp_adc_cal_value_t> for CalibrationSource {
+  fn from(value: esp_adc_cal_value_t) -> Self {
+    match value {
+      esp_adc_cal_value_t::ESP_ADC_CAL_VAL_EFUSE_TP => Self::TwoPoint,
+      esp_adc_cal_value_t::ESP_ADC_CAL_VAL_EFUSE_VREF => Self::Vref,
+      _ => Self::Default,
+    }
+  }
+}
+
+pub(crate) mod sealed {
+  use esp_idf_bindgen::*;
+
+  use super::{AdcError, Attenuation, Width};
+  use crate::EspError;
+
+  pub trait Unit {
+    type Channel: Copy;
+
+    const UNIT: adc_unit_t;
+
+    fn index(channel: Self::Channel) -> usize;
+
+    fn configure_width(width: Width) -> Result<(), EspError>;
+
+    fn configure_attenuation(channel: Self::Channel, attenuation: Attenuation) -> Result<(), EspError>;
+
+    fn read_raw(channel: Self::Channel, width: Width) -> Result<u16, AdcError>;
+  }
+}
+
+/// An ADC unit, i.e. [`Adc1`](struct.Adc1.html) or [`Adc2`](struct.Adc2.html).
+pub trait Unit: sealed::Unit {}
+
+/// ADC1, which can be used at any time.
+#[derive(Debug)]
+pub struct Adc1 {
+  _private: (),
+}
+
+impl sealed::Unit for Adc1 {
+  type Channel = adc1_channel_t;
+
+  const UNIT: adc_unit_t = adc_unit_t::ADC_UNIT_1;
+
+  fn index(channel: Self::Channel) -> usize {
+    channel as usize
+  }
+
+  fn configure_width(width: Width) -> Result<(), EspError> {
+    esp_ok!(adc1_config_width(width.into()))
+  }
+
+  fn configure_attenuation(channel: Self::Channel, attenuation: Attenuation) -> Result<(), EspError> {
+    esp_ok!(adc1_config_channel_atten(channel, attenuation.into()))
+  }
+
+  fn read_raw(channel: Self::Channel, _width: Width) -> Result<u16, AdcError> {
+    match unsafe { adc1_get_raw(channel) } {
+      -1 => Err(EspError::from(EspErrorKind::Generic(GenericErrorKind::InvalidArg)).into()),
+      raw => Ok(raw as u16),
+    }
+  }
+}
+
+impl Unit for Adc1 {}
+
+/// ADC2, which is unavailable while WiFi is active.
+#[derive(Debug)]
+pub struct Adc2 {
+  _private: (),
+}
+
+impl sealed::Unit for Adc2 {
+  type Channel = adc2_channel_t;
+
+  const UNIT: adc_unit_t = adc_unit_t::ADC_UNIT_2;
+
+  fn index(channel: Self::Channel) -> usize {
+    channel as usize
+  }
+
+  /// ADC2 sets the width for each reading.
+  fn configure_width(_width: Width) -> Result<(), EspError> {
+    Ok(())
+  }
+
+  fn configure_attenuation(channel: Self::Channel, attenuation: Attenuation) -> Result<(), EspError> {
+    esp_ok!(adc2_config_channel_atten(channel, attenuation.into()))
+  }
+
+  fn read_raw(channel: Self::Channel, width: Width) -> Result<u16, AdcError> {
+    // The driver only returns an error if the WiFi driver is using ADC2 at this moment,
+    // otherwise readings are unreliable while WiFi is active.
+    if wifi::is_active() {
+      return Err(AdcError::Adc2Unavailable)
+    }
+
+    let mut raw = 0;
+    match esp_ok!(adc2_get_raw(channel, width.into(), &mut raw)) {
+      Ok(()) => Ok(raw as u16),
+      Err(err) if err.kind() == EspErrorKind::Generic(GenericErrorKind::Timeout) => Err(AdcError::Adc2Unavailable),
+      Err(err) => Err(err.into()),
+    }
+  }
+}
+
+impl Unit for Adc2 {}
+
+/// Both ADC units.
+///
+/// The width of ADC1 readings is configured globally, so each unit can only be used by one
+/// [`Adc`](struct.Adc.html) or [`ContinuousAdc`](struct.ContinuousAdc.html) at a time.
+#[derive(Debug)]
+pub struct Units {
+  pub adc1: Adc1,
+  pub adc2: Adc2,
+}
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl Units {
+  /// Take both units. Returns `None` if they were already taken.
+  pub fn take() -> Option<Self> {
+    if TAKEN.swap(true, Ordering::AcqRel) {
+      return None
+    }
+
+    Some(unsafe { Self::steal() })
+  }
+
+  /// Get both units, even if they were already taken.
+  ///
+  /// # Safety
+  ///
+  /// The caller must ensure that each unit is only used once.
+  pub unsafe fn steal() -> Self {
+    Self { adc1: Adc1 { _private: () }, adc2: Adc2 { _private: () } }
+  }
+}
+
+/// Configuration for an [`Adc`](struct.Adc.html).
+#[derive(Debug, Clone)]
+pub struct Config {
+  width: Width,
+  attenuation: Attenuation,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self { width: Width::Bits12, attenuation: Attenuation::Db11 }
+  }
+}
+
+impl Config {
+  /// 12-bit readings with an attenuation of 11 dB.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn width(mut self, width: Width) -> Self {
+    self.width = width;
+    self
+  }
+
+  /// Attenuation of channels which are not configured using [`Adc::set_attenuation`](struct.Adc.html#method.set_attenuation).
+  pub fn attenuation(mut self, attenuation: Attenuation) -> Self {
+    self.attenuation = attenuation;
+    self
+  }
+}
+
+/// Calibration characteristics for one attenuation.
+struct Calibration {
+  source: CalibrationSource,
+  characteristics: esp_adc_cal_characteristics_t,
+}
+
+impl Calibration {
+  fn new(unit: adc_unit_t, attenuation: Attenuation, width: Width) -> Self {
+    let mut characteristics = MaybeUninit::<esp_adc_cal_characteristics_t>::uninit();
+
+    let source = unsafe {
+      esp_adc_cal_characterize(unit, attenuation.into(), width.into(), DEFAULT_VREF, characteristics.as_mut_ptr())
+    };
+
+    Self { source: source.into(), characteristics: unsafe { characteristics.assume_init() } }
+  }
+
+  fn to_millivolts(&self, raw: u16) -> u16 {
+    unsafe { esp_adc_cal_raw_to_voltage(u32::from(raw), &self.characteristics) as u16 }
+  }
+}
+
+// SAFETY: The characteristics only point to static lookup tables.
+unsafe impl Send for Calibration {}
+
+impl fmt::Debug for Calibration {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Calibration").field("source", &self.source).finish()
+  }
+}
+
+/// An ADC unit in one-shot mode.
+#[derive(Debug)]
+pub struct Adc<U: Unit> {
+  width: Width,
+  default_attenuation: Attenuation,
+  attenuations: [Option<Attenuation>; MAX_CHANNELS],
+  calibrations: [Option<Calibration>; 4],
+  unit: U,
+}
+
+impl<U: Unit> Adc<U> {
+  pub fn new(unit: U, config: Config) -> Result<Self, EspError> {
+    U::configure_width(config.width)?;
+
+    Ok(Self {
+      width: config.width,
+      default_attenuation: config.attenuation,
+      attenuations: Default::default(),
+      calibrations: Default::default(),
+      unit,
+    })
+  }
+
+  /// Release the unit, e.g. to sample it continuously.
+  pub fn release(self) -> U {
+    self.unit
+  }
+
+  /// Configure the attenuation of `pin`.
+  pub fn set_attenuation<P>(&mut self, _pin: &P, attenuation: Attenuation) -> Result<(), EspError>
+  where
+    P: Channel<U, ID = U::Channel>,
+  {
+    U::configure_attenuation(P::channel(), attenuation)?;
+    self.attenuations[U::index(P::channel())] = Some(attenuation);
+    Ok(())
+  }
+
+  /// The attenuation of `P`, configuring the default if it has not been configured yet.
+  fn attenuation<P>(&mut self) -> Result<Attenuation, EspError>
+  where
+    P: Channel<U, ID = U::Channel>,
+  {
+    let index = U::index(P::channel());
+
+    match self.attenuations[index] {
+      Some(attenuation) => Ok(attenuation),
+      None => {
+        U::configure_attenuation(P::channel(), self.default_attenuation)?;
+        self.attenuations[index] = Some(self.default_attenuation);
+        Ok(self.default_attenuation)
+      },
+    }
+  }
+
+  /// Read the raw value of `pin`.
+  pub fn read_raw<P>(&mut self, _pin: &mut P) -> Result<u16, AdcError>
+  where
+    P: Channel<U, ID = U::Channel>,
+  {
+    self.attenuation::<P>()?;
+    U::read_raw(P::channel(), self.width)
+  }
+
+  /// Read the voltage of `pin` in millivolts, calibrated using the values stored in eFuse.
+  pub fn read_millivolts<P>(&mut self, pin: &mut P) -> Result<u16, AdcError>
+  where
+    P: Channel<U, ID = U::Channel>,
+  {
+    let attenuation = self.attenuation::<P>()?;
+    let raw = self.read_raw(pin)?;
+    let calibration = self.calibration(attenuation);
+
+    Ok(calibration.to_millivolts(raw))
+  }
+
+  /// The source of the calibration values used for `attenuation`.
+  pub fn calibration_source(&mut self, attenuation: Attenuation) -> CalibrationSource {
+    self.calibration(attenuation).source
+  }
+
+  fn calibration(&mut self, attenuation: Attenuation) -> &Calibration {
+    let width = self.width;
+
+    self.calibrations[attenuation.index()].get_or_insert_with(|| Calibration::new(U::UNIT, attenuation, width))
+  }
+}
+
+impl<U: Unit, P> OneShot<U, u16, P> for Adc<U>
+where
+  P: Channel<U, ID = U::Channel>,
+{
+  type Error = AdcError;
+
+  fn read(&mut self, pin: &mut P) -> nb::Result<u16, Self::Error> {
+    self.read_raw(pin).map_err(nb::Error::Other)
+  }
+}
+
+macro_rules! analog_pins {
+  ($($Unit:ident($Channel:ident) { $($Gpio:ident => $channel:ident,)* })*) => {
+    $(
+      $(
+        impl<MODE> gpio::$Gpio<MODE> {
+          #[doc = concat!("Configure the pin as an analog input for ", stringify!($Unit), ".")]
+          pub fn into_analog(self) -> Result<gpio::$Gpio<Analog>, EspError> {
+            esp_ok!(gpio_set_direction(self.gpio_num(), gpio_mode_t::GPIO_MODE_DISABLE))?;
+            esp_ok!(gpio_set_pull_mode(self.gpio_num(), gpio_pull_mode_t::GPIO_FLOATING))?;
+            Ok(self.with_mode())
+          }
+        }
+
+        impl Channel<$Unit> for gpio::$Gpio<Analog> {
+          type ID = $Channel;
+
+          fn channel() -> Self::ID {
+            $Channel::$channel
+          }
+        }
+      )*
+    )*
+  };
+}
+
+analog_pins! {
+  Adc1(adc1_channel_t) {
+    Gpio36 => ADC1_CHANNEL_0,
+    Gpio37 => ADC1_CHANNEL_1,
+    Gpio38 => ADC1_CHANNEL_2,
+    Gpio39 => ADC1_CHANNEL_3,
+    Gpio32 => ADC1_CHANNEL_4,
+    Gpio33 => ADC1_CHANNEL_5,
+    Gpio34 => ADC1_CHANNEL_6,
+    Gpio35 => ADC1_CHANNEL_7,
+  }
+  Adc2(adc2_channel_t) {
+    Gpio4 => ADC2_CHANNEL_0,
+    Gpio0 => ADC2_CHANNEL_1,
+    Gpio2 => ADC2_CHANNEL_2,
+    Gpio15 => ADC2_CHANNEL_3,
+    Gpio13 => ADC2_CHANNEL_4,
+    Gpio12 => ADC2_CHANNEL_5,
+    Gpio14 => ADC2_CHANNEL_6,
+    Gpio27 => ADC2_CHANNEL_7,
+    Gpio25 => ADC2_CHANNEL_8,
+    Gpio26 => ADC2_CHANNEL_9,
+  }
+}
@@ -0,0 +1,188 @@
+use core::fmt;
+
+use esp_idf_bindgen::esp_err_t;
+
+macro_rules! error_kinds {
+  ($(
+    $(#[$meta:meta])*
+    $kind:ident($sub:ident) {
+      $($variant:ident = $code:literal => $name:literal,)*
+    }
+  )*) => {
+    $(
+      $(#[$meta])*
+      #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+      #[non_exhaustive]
+      pub enum $sub {
+        $(
+          #[doc = $name]
+          $variant,
+        )*
+      }
+
+      impl $sub {
+        const fn from_code(code: esp_err_t) -> Option<Self> {
+          match code {
+            $($code => Some(Self::$variant),)*
+            _ => None,
+          }
+        }
+
+        /// The numeric error code.
+        pub const fn code(self) -> esp_err_t {
+          match self {
+            $(Self::$variant => $code,)*
+          }
+        }
+
+        /// The name of the error code, e.g. `ESP_ERR_NO_MEM`.
+        pub const fn name(self) -> &'static str {
+          match self {
+            $(Self::$variant => $name,)*
+          }
+        }
+      }
+    )*
+
+    /// The kind of an [`EspError`](struct.EspError.html), grouped by subsystem.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[non_exhaustive]
+    pub enum EspErrorKind {
+      $(
+        $(#[$meta])*
+        $kind($sub),
+      )*
+      /// An error code not known to this crate.
+      Other(esp_err_t),
+    }
+
+    impl EspErrorKind {
+      /// Look up the kind of the error `code`.
+      pub const fn from_code(code: esp_err_t) -> Self {
+        $(
+          if let Some(kind) = $sub::from_code(code) {
+            return Self::$kind(kind)
+          }
+        )*
+
+        Self::Other(code)
+      }
+
+      /// The numeric error code.
+      pub const fn code(self) -> esp_err_t {
+        match self {
+          $(Self::$kind(kind) => kind.code(),)*
+          Self::Other(code) => code,
+        }
+      }
+
+      /// The name of the error code, or `UNKNOWN ERROR` like `esp_err_to_name`.
+      pub const fn name(self) -> &'static str {
+        match self {
+          $(Self::$kind(kind) => kind.name(),)*
+          Self::Other(_) => "UNKNOWN ERROR",
+        }
+      }
+    }
+  };
+}
+
+error_kinds! {
+  /// Generic errors defined in `esp_err.h`.
+  Generic(GenericErrorKind) {
+    Fail            =     -1 => "ESP_FAIL",
+    NoMem           = 0x0101 => "ESP_ERR_NO_MEM",
+    InvalidArg      = 0x0102 => "ESP_ERR_INVALID_ARG",
+    InvalidState    = 0x0103 => "ESP_ERR_INVALID_STATE",
+    InvalidSize     = 0x0104 => "ESP_ERR_INVALID_SIZE",
+    NotFound        = 0x0105 => "ESP_ERR_NOT_FOUND",
+    NotSupported    = 0x0106 => "ESP_ERR_NOT_SUPPORTED",
+    Timeout         = 0x0107 => "ESP_ERR_TIMEOUT",
+    InvalidResponse = 0x0108 => "ESP_ERR_INVALID_RESPONSE",
+    InvalidCrc      = 0x0109 => "ESP_ERR_INVALID_CRC",
+    InvalidVersion  = 0x010a => "ESP_ERR_INVALID_VERSION",
+    InvalidMac      = 0x010b => "ESP_ERR_INVALID_MAC",
+  }
+
+  /// Non-volatile storage errors defined in `nvs.h`.
+  Nvs(NvsErrorKind) {
+    NotInitialized  = 0x1101 => "ESP_ERR_NVS_NOT_INITIALIZED",
+    NotFound        = 0x1102 => "ESP_ERR_NVS_NOT_FOUND",
+    TypeMismatch    = 0x1103 => "ESP_ERR_NVS_TYPE_MISMATCH",
+    ReadOnly        = 0x1104 => "ESP_ERR_NVS_READ_ONLY",
+    NotEnoughSpace  = 0x1105 => "ESP_ERR_NVS_NOT_ENOUGH_SPACE",
+    InvalidName     = 0x1106 => "ESP_ERR_NVS_INVALID_NAME",
+    InvalidHandle   = 0x1107 => "ESP_ERR_NVS_INVALID_HANDLE",
+    RemoveFailed    = 0x1108 => "ESP_ERR_NVS_REMOVE_FAILED",
+    KeyTooLong      = 0x1109 => "ESP_ERR_NVS_KEY_TOO_LONG",
+    PageFull        = 0x110a => "ESP_ERR_NVS_PAGE_FULL",
+    InvalidState    = 0x110b => "ESP_ERR_NVS_INVALID_STATE",
+    InvalidLength   = 0x110c => "ESP_ERR_NVS_INVALID_LENGTH",
+    NoFreePages     = 0x110d => "ESP_ERR_NVS_NO_FREE_PAGES",
+    ValueTooLong    = 0x110e => "ESP_ERR_NVS_VALUE_TOO_LONG",
+    PartNotFound    = 0x110f => "ESP_ERR_NVS_PART_NOT_FOUND",
+    NewVersionFound = 0x1110 => "ESP_ERR_NVS_NEW_VERSION_FOUND",
+  }
+
+  /// Over-the-air update errors defined in `esp_ota_ops.h`.
+  Ota(OtaErrorKind) {
+    PartitionConflict      = 0x1501 => "ESP_ERR_OTA_PARTITION_CONFLICT",
+    SelectInfoInvalid      = 0x1502 => "ESP_ERR_OTA_SELECT_INFO_INVALID",
+    ValidateFailed         = 0x1503 => "ESP_ERR_OTA_VALIDATE_FAILED",
+    SmallSecVer            = 0x1504 => "ESP_ERR_OTA_SMALL_SEC_VER",
+    RollbackFailed         = 0x1505 => "ESP_ERR_OTA_ROLLBACK_FAILED",
+    RollbackInvalidState   = 0x1506 => "ESP_ERR_OTA_ROLLBACK_INVALID_STATE",
+  }
+
+  /// WiFi errors defined in `esp_wifi.h`.
+  Wifi(WifiErrorKind) {
+    NotInit     = 0x3001 => "ESP_ERR_WIFI_NOT_INIT",
+    NotStarted  = 0x3002 => "ESP_ERR_WIFI_NOT_STARTED",
+    NotStopped  = 0x3003 => "ESP_ERR_WIFI_NOT_STOPPED",
+    If          = 0x3004 => "ESP_ERR_WIFI_IF",
+    Mode        = 0x3005 => "ESP_ERR_WIFI_MODE",
+    State       = 0x3006 => "ESP_ERR_WIFI_STATE",
+    Conn        = 0x3007 => "ESP_ERR_WIFI_CONN",
+    Nvs         = 0x3008 => "ESP_ERR_WIFI_NVS",
+    Mac         = 0x3009 => "ESP_ERR_WIFI_MAC",
+    Ssid        = 0x300a => "ESP_ERR_WIFI_SSID",
+    Password    = 0x300b => "ESP_ERR_WIFI_PASSWORD",
+    Timeout     = 0x300c => "ESP_ERR_WIFI_TIMEOUT",
+    WakeFail    = 0x300d => "ESP_ERR_WIFI_WAKE_FAIL",
+    WouldBlock  = 0x300e => "ESP_ERR_WIFI_WOULD_BLOCK",
+    NotConnect  = 0x300f => "ESP_ERR_WIFI_NOT_CONNECT",
+    Post        = 0x3012 => "ESP_ERR_WIFI_POST",
+    InitState   = 0x3013 => "ESP_ERR_WIFI_INIT_STATE",
+    StopState   = 0x3014 => "ESP_ERR_WIFI_STOP_STATE",
+  }
+
+  /// Network interface errors defined in `esp_netif_types.h`, or `tcpip_adapter.h` on the ESP8266.
+  Netif(NetifErrorKind) {
+    InvalidParams      = 0x5001 => "ESP_ERR_ESP_NETIF_INVALID_PARAMS",
+    IfNotReady         = 0x5002 => "ESP_ERR_ESP_NETIF_IF_NOT_READY",
+    DhcpcStartFailed   = 0x5003 => "ESP_ERR_ESP_NETIF_DHCPC_START_FAILED",
+    DhcpAlreadyStarted = 0x5004 => "ESP_ERR_ESP_NETIF_DHCP_ALREADY_STARTED",
+    DhcpAlreadyStopped = 0x5005 => "ESP_ERR_ESP_NETIF_DHCP_ALREADY_STOPPED",
+    NoMem              = 0x5006 => "ESP_ERR_ESP_NETIF_NO_MEM",
+    DhcpNotStopped     = 0x5007 => "ESP_ERR_ESP_NETIF_DHCP_NOT_STOPPED",
+    DriverAttachFailed = 0x5008 => "ESP_ERR_ESP_NETIF_DRIVER_ATTACH_FAILED",
+    InitFailed         = 0x5009 => "ESP_ERR_ESP_NETIF_INIT_FAILED",
+    DnsNotConfigured   = 0x500a => "ESP_ERR_ESP_NETIF_DNS_NOT_CONFIGURED",
+  }
+
+  /// SPI flash errors defined in `esp_spi_flash.h`.
+  Flash(FlashErrorKind) {
+    OpFail           = 0x6001 => "ESP_ERR_FLASH_OP_FAIL",
+    OpTimeout        = 0x6002 => "ESP_ERR_FLASH_OP_TIMEOUT",
+    NotInitialised   = 0x6003 => "ESP_ERR_FLASH_NOT_INITIALISED",
+    UnsupportedHost  = 0x6004 => "ESP_ERR_FLASH_UNSUPPORTED_HOST",
+    UnsupportedChip  = 0x6005 => "ESP_ERR_FLASH_UNSUPPORTED_CHIP",
+    Protected        = 0x6006 => "ESP_ERR_FLASH_PROTECTED",
+  }
+}
+
+impl fmt::Display for EspErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.name().fmt(f)
+  }
+}
@@ -0,0 +1,99 @@
+use esp_idf_bindgen::esp_err_t;
+
+mod kind;
+pub use kind::*;
+
+mod table {
+  use esp_idf_bindgen::esp_err_t;
+
+  include!(concat!(env!("OUT_DIR"), "/esp_err_table.rs"));
+}
+
+fn lookup(code: esp_err_t) -> Option<&'static (esp_err_t, &'static str, &'static str)> {
+  let index = table::ESP_ERR_TABLE.binary_search_by_key(&code, |&(code, _, _)| code).ok()?;
+  Some(&table::ESP_ERR_TABLE[index])
+}
+
+/// An error code returned by an ESP-IDF function.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EspError { pub(crate) code: esp_err_t }
+
+impl EspError {
+  /// Create an error from a raw error code.
+  pub const fn from_code(code: esp_err_t) -> Self {
+    Self { code }
+  }
+
+  /// The raw error code.
+  pub const fn code(&self) -> esp_err_t {
+    self.code
+  }
+
+  /// The kind of this error.
+  pub const fn kind(&self) -> EspErrorKind {
+    EspErrorKind::from_code(self.code)
+  }
+
+  /// The name of the error code, e.g. `ESP_ERR_NO_MEM`.
+  ///
+  /// Names are read from the SDK headers at build time, falling back to
+  /// [`EspErrorKind::name`](enum.EspErrorKind.html#method.name) if the SDK is unavailable.
+  pub fn name(&self) -> &'static str {
+    match lookup(self.code) {
+      Some(&(_, name, _)) => name,
+      None => self.kind().name(),
+    }
+  }
+
+  /// The description of the error code from the SDK headers, e.g. `Out of memory`.
+  pub fn description(&self) -> Option<&'static str> {
+    match lookup(self.code) {
+      Some(&(_, _, description)) if !description.is_empty() => Some(description),
+      _ => None,
+    }
+  }
+}
+
+impl From<EspErrorKind> for EspError {
+  fn from(kind: EspErrorKind) -> Self {
+    Self { code: kind.code() }
+  }
+}
+
+impl From<!> for EspError {
+  fn from(_: !) -> Self {
+    loop {}
+  }
+}
+
+impl core::fmt::Debug for EspError {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("EspError")
+      .field("code", &self.code)
+      .field("name", &self.name())
+      .field("description", &self.description())
+      .finish()
+  }
+}
+
+impl core::fmt::Display for EspError {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self.description() {
+      Some(description) => write!(f, "{}: {}", self.name(), description),
+      None => self.name().fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for EspError {}
+
+macro_rules! esp_ok {
+  ($err:expr) => {{
+    let code = unsafe { $err };
+    if code == ::esp_idf_bindgen::ESP_OK as ::esp_idf_bindgen::esp_err_t {
+      Ok(())
+    } else {
+      Err($crate::esp_error::EspError { code })
+    }
+  }}
+}
@@ -0,0 +1,240 @@
+//! A single-task `async` executor.
+//!
+//! [`block_on`](fn.block_on.html) runs a future and all futures [`spawn`](fn.spawn.html)ed from it
+//! on the current task. While no future can make progress, the task sleeps in `select` until a
+//! socket becomes ready, a [timer](fn.sleep.html) expires or a waker is called from another task.
+//!
+//! ```ignore
+//! executor::block_on(async {
+//!   executor::spawn(dns_server());
+//!
+//!   let listener = TcpListener::bind("0.0.0.0:80")?;
+//!
+//!   loop {
+//!     let (client, addr) = listener.accept().await?;
+//!     executor::spawn(handle_client(client, addr));
+//!   }
+//! })
+//! ```
+
+use core::cell::RefCell;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::io;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::Wake;
+
+use esp_idf_bindgen::xTaskGetCurrentTaskHandle;
+
+mod reactor;
+use reactor::{Interest, Notifier, Reactor};
+
+mod timer;
+pub use timer::{sleep, sleep_until, timeout, Elapsed, Sleep, Timeout};
+
+mod net;
+pub use net::{TcpListener, TcpStream, UdpSocket};
+
+thread_local! {
+  static CURRENT: RefCell<Option<Rc<Runtime>>> = RefCell::new(None);
+}
+
+/// Task ID of the future passed to `block_on`.
+const MAIN_TASK: usize = usize::max_value();
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// State shared with wakers, which may be called from other tasks.
+struct Shared {
+  ready: Mutex<VecDeque<usize>>,
+  notifier: Notifier,
+  task: usize,
+}
+
+impl Shared {
+  fn schedule(&self, id: usize) {
+    {
+      let mut ready = self.ready.lock().unwrap_or_else(|err| err.into_inner());
+
+      if ready.contains(&id) {
+        return
+      }
+
+      ready.push_back(id);
+    }
+
+    // The executor checks the ready queue before waiting, so it only needs
+    // to be interrupted when woken from another task.
+    if unsafe { xTaskGetCurrentTaskHandle() } as usize != self.task {
+      self.notifier.notify();
+    }
+  }
+
+  fn next(&self) -> Option<usize> {
+    self.ready.lock().unwrap_or_else(|err| err.into_inner()).pop_front()
+  }
+
+  fn is_idle(&self) -> bool {
+    self.ready.lock().unwrap_or_else(|err| err.into_inner()).is_empty()
+  }
+}
+
+struct TaskWaker {
+  id: usize,
+  shared: Arc<Shared>,
+}
+
+impl Wake for TaskWaker {
+  fn wake(self: Arc<Self>) {
+    self.shared.schedule(self.id)
+  }
+
+  fn wake_by_ref(self: &Arc<Self>) {
+    self.shared.schedule(self.id)
+  }
+}
+
+struct Runtime {
+  shared: Arc<Shared>,
+  reactor: Reactor,
+  tasks: RefCell<Vec<Option<(Task, Waker)>>>,
+  spawned: RefCell<Vec<Task>>,
+}
+
+impl Runtime {
+  fn new() -> io::Result<Self> {
+    let shared = Arc::new(Shared {
+      ready: Mutex::new(VecDeque::new()),
+      notifier: Notifier::new()?,
+      task: unsafe { xTaskGetCurrentTaskHandle() } as usize,
+    });
+
+    Ok(Self {
+      shared,
+      reactor: Reactor::new(),
+      tasks: RefCell::new(Vec::new()),
+      spawned: RefCell::new(Vec::new()),
+    })
+  }
+
+  fn waker(&self, id: usize) -> Waker {
+    Waker::from(Arc::new(TaskWaker { id, shared: Arc::clone(&self.shared) }))
+  }
+
+  /// Move tasks spawned while polling into the task list and schedule them.
+  fn insert_spawned(&self) {
+    for task in self.spawned.borrow_mut().drain(..) {
+      let mut tasks = self.tasks.borrow_mut();
+
+      let id = match tasks.iter().position(Option::is_none) {
+        Some(id) => id,
+        None => {
+          tasks.push(None);
+          tasks.len() - 1
+        },
+      };
+
+      tasks[id] = Some((task, self.waker(id)));
+      self.shared.schedule(id);
+    }
+  }
+
+  fn poll_task(&self, id: usize) {
+    // The task is taken out of the list while polling, so it can spawn other tasks.
+    let (mut task, waker) = match self.tasks.borrow_mut().get_mut(id).and_then(Option::take) {
+      Some(task) => task,
+      None => return,
+    };
+
+    if task.as_mut().poll(&mut Context::from_waker(&waker)).is_pending() {
+      self.tasks.borrow_mut()[id] = Some((task, waker));
+    }
+  }
+}
+
+impl fmt::Debug for Runtime {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Runtime").field("tasks", &self.tasks.borrow().iter().filter(|t| t.is_some()).count()).finish()
+  }
+}
+
+fn with_runtime<R>(f: impl FnOnce(&Runtime) -> R) -> R {
+  try_with_runtime(f).expect("must be called from within `esp_idf_hal::executor::block_on`")
+}
+
+fn try_with_runtime<R>(f: impl FnOnce(&Runtime) -> R) -> Option<R> {
+  CURRENT.try_with(|current| current.borrow().as_ref().map(|runtime| f(runtime))).ok().flatten()
+}
+
+/// Drops all remaining tasks and resets the current runtime when `block_on` returns or unwinds.
+struct RuntimeGuard(Rc<Runtime>);
+
+impl Drop for RuntimeGuard {
+  fn drop(&mut self) {
+    // Tasks are dropped while the runtime is still current, so they can deregister their sockets and timers.
+    let tasks = self.0.tasks.replace(Vec::new());
+    drop(tasks);
+    let spawned = self.0.spawned.replace(Vec::new());
+    drop(spawned);
+
+    CURRENT.with(|current| current.borrow_mut().take());
+  }
+}
+
+/// Run `future` and all tasks spawned from it on the current task until `future` completes.
+///
+/// Tasks which have not completed when `future` completes are dropped.
+///
+/// # Panics
+///
+/// Panics when called from within another `block_on` on the same task.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+  assert!(try_with_runtime(|_| ()).is_none(), "`block_on` cannot be nested");
+
+  let runtime = Rc::new(Runtime::new().expect("failed to create executor"));
+  CURRENT.with(|current| current.borrow_mut().replace(Rc::clone(&runtime)));
+
+  let _guard = RuntimeGuard(Rc::clone(&runtime));
+
+  let mut future = Box::pin(future);
+  let main_waker = runtime.waker(MAIN_TASK);
+  runtime.shared.schedule(MAIN_TASK);
+
+  loop {
+    while let Some(id) = runtime.shared.next() {
+      if id == MAIN_TASK {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&main_waker)) {
+          return output
+        }
+      } else {
+        runtime.poll_task(id);
+      }
+
+      runtime.insert_spawned();
+    }
+
+    runtime.shared.notifier.clear();
+
+    if runtime.shared.is_idle() {
+      runtime.reactor.turn(&runtime.shared.notifier);
+    }
+  }
+}
+
+/// Spawn `future` onto the executor of the current task.
+///
+/// The future does not need to be `Send`, since it always runs on the current task.
+///
+/// # Panics
+///
+/// Panics when not called from within [`block_on`](fn.block_on.html).
+pub fn spawn<F>(future: F)
+where
+  F: Future<Output = ()> + 'static,
+{
+  with_runtime(|runtime| runtime.spawned.borrow_mut().push(Box::pin(future)))
+}
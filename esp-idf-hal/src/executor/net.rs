@@ -0,0 +1,165 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::io::{self, Read, Write};
+use std::net::{self, Shutdown, SocketAddr, ToSocketAddrs};
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use super::{try_with_runtime, with_runtime, Interest};
+
+struct PollFn<F>(F);
+
+impl<T, F: FnMut(&mut Context) -> Poll<T> + Unpin> Future for PollFn<F> {
+  type Output = T;
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+    (self.0)(cx)
+  }
+}
+
+/// Retry the non-blocking operation `op` each time `fd` becomes ready for `interest`.
+async fn io<R>(fd: RawFd, interest: Interest, mut op: impl FnMut() -> io::Result<R> + Unpin) -> io::Result<R> {
+  PollFn(|cx: &mut Context| match op() {
+    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+      with_runtime(|runtime| runtime.reactor.register(fd, interest, cx.waker()));
+      Poll::Pending
+    },
+    res => Poll::Ready(res),
+  }).await
+}
+
+fn deregister(fd: RawFd) {
+  try_with_runtime(|runtime| runtime.reactor.deregister(fd));
+}
+
+/// An asynchronous TCP socket server.
+#[derive(Debug)]
+pub struct TcpListener {
+  inner: net::TcpListener,
+}
+
+impl TcpListener {
+  pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+    Self::from_std(net::TcpListener::bind(addr)?)
+  }
+
+  /// Convert a `std` listener, which is switched to non-blocking mode.
+  pub fn from_std(listener: net::TcpListener) -> io::Result<Self> {
+    listener.set_nonblocking(true)?;
+    Ok(Self { inner: listener })
+  }
+
+  pub fn local_addr(&self) -> io::Result<SocketAddr> {
+    self.inner.local_addr()
+  }
+
+  /// Wait for a new connection.
+  pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+    let (stream, addr) = io(self.inner.as_raw_fd(), Interest::Read, || self.inner.accept()).await?;
+    Ok((TcpStream::from_std(stream)?, addr))
+  }
+}
+
+impl Drop for TcpListener {
+  fn drop(&mut self) {
+    deregister(self.inner.as_raw_fd());
+  }
+}
+
+/// An asynchronous TCP stream, e.g. accepted by a [`TcpListener`](struct.TcpListener.html).
+#[derive(Debug)]
+pub struct TcpStream {
+  inner: net::TcpStream,
+}
+
+impl TcpStream {
+  /// Convert a `std` stream, which is switched to non-blocking mode.
+  pub fn from_std(stream: net::TcpStream) -> io::Result<Self> {
+    stream.set_nonblocking(true)?;
+    Ok(Self { inner: stream })
+  }
+
+  pub fn local_addr(&self) -> io::Result<SocketAddr> {
+    self.inner.local_addr()
+  }
+
+  pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+    self.inner.peer_addr()
+  }
+
+  pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+    self.inner.set_nodelay(nodelay)
+  }
+
+  pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+    self.inner.shutdown(how)
+  }
+
+  /// Read into `buf`, returning the number of bytes read, or `0` once the peer closed the connection.
+  pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let inner = &mut self.inner;
+    io(inner.as_raw_fd(), Interest::Read, || inner.read(buf)).await
+  }
+
+  /// Write from `buf`, returning the number of bytes written.
+  pub async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let inner = &mut self.inner;
+    io(inner.as_raw_fd(), Interest::Write, || inner.write(buf)).await
+  }
+
+  /// Write all of `buf`.
+  pub async fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+      match self.write(buf).await? {
+        0 => return Err(io::ErrorKind::WriteZero.into()),
+        n => buf = &buf[n..],
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl Drop for TcpStream {
+  fn drop(&mut self) {
+    deregister(self.inner.as_raw_fd());
+  }
+}
+
+/// An asynchronous UDP socket.
+#[derive(Debug)]
+pub struct UdpSocket {
+  inner: net::UdpSocket,
+}
+
+impl UdpSocket {
+  pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+    Self::from_std(net::UdpSocket::bind(addr)?)
+  }
+
+  /// Convert a `std` socket, which is switched to non-blocking mode.
+  pub fn from_std(socket: net::UdpSocket) -> io::Result<Self> {
+    socket.set_nonblocking(true)?;
+    Ok(Self { inner: socket })
+  }
+
+  pub fn local_addr(&self) -> io::Result<SocketAddr> {
+    self.inner.local_addr()
+  }
+
+  /// Receive a datagram, returning its length and source address.
+  pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+    io(self.inner.as_raw_fd(), Interest::Read, || self.inner.recv_from(buf)).await
+  }
+
+  /// Send a datagram to `addr`, returning the number of bytes sent.
+  pub async fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+    io(self.inner.as_raw_fd(), Interest::Write, || self.inner.send_to(buf, addr)).await
+  }
+}
+
+impl Drop for UdpSocket {
+  fn drop(&mut self) {
+    deregister(self.inner.as_raw_fd());
+  }
+}
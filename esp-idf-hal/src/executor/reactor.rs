@@ -0,0 +1,165 @@
+use core::cell::RefCell;
+use core::cmp;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Waker;
+use std::io;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Instant;
+
+use super::timer::TimerWheel;
+
+/// Readiness a task is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Interest {
+  Read,
+  Write,
+}
+
+#[derive(Debug)]
+struct Registration {
+  fd: RawFd,
+  interest: Interest,
+  waker: Waker,
+}
+
+/// Interrupts `select` when a task is woken from another task.
+///
+/// lwIP has no `eventfd` or pipes, so this uses a UDP socket on the loopback interface which is connected to itself.
+#[derive(Debug)]
+pub(super) struct Notifier {
+  socket: UdpSocket,
+  notified: AtomicBool,
+}
+
+impl Notifier {
+  pub fn new() -> io::Result<Self> {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+    socket.connect(socket.local_addr()?)?;
+    socket.set_nonblocking(true)?;
+
+    Ok(Self { socket, notified: AtomicBool::new(false) })
+  }
+
+  pub fn notify(&self) {
+    if !self.notified.swap(true, Ordering::AcqRel) {
+      let _ = self.socket.send(&[0]);
+    }
+  }
+
+  /// Reset the notification, must be called before checking for ready tasks.
+  pub fn clear(&self) {
+    self.notified.store(false, Ordering::Release);
+
+    let mut buf = [0; 16];
+    while self.socket.recv(&mut buf).is_ok() {}
+  }
+}
+
+/// Waits for sockets to become ready and timers to expire.
+#[derive(Debug)]
+pub(super) struct Reactor {
+  pub timers: RefCell<TimerWheel>,
+  registrations: RefCell<Vec<Registration>>,
+}
+
+impl Reactor {
+  pub fn new() -> Self {
+    Self { timers: RefCell::new(TimerWheel::new()), registrations: RefCell::new(Vec::new()) }
+  }
+
+  /// Wake `waker` once `fd` is ready for `interest`.
+  pub fn register(&self, fd: RawFd, interest: Interest, waker: &Waker) {
+    let mut registrations = self.registrations.borrow_mut();
+
+    match registrations.iter_mut().find(|r| r.fd == fd && r.interest == interest) {
+      Some(registration) => {
+        if !registration.waker.will_wake(waker) {
+          registration.waker = waker.clone();
+        }
+      },
+      None => registrations.push(Registration { fd, interest, waker: waker.clone() }),
+    }
+  }
+
+  /// Remove all registrations for `fd`, must be called before it is closed.
+  pub fn deregister(&self, fd: RawFd) {
+    self.registrations.borrow_mut().retain(|r| r.fd != fd);
+  }
+
+  /// Wait until a registered socket is ready, the next timer expires or the `notifier` is notified,
+  /// then wake all corresponding tasks.
+  pub fn turn(&self, notifier: &Notifier) {
+    let timeout = self.timers.borrow().next_deadline().map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+    let mut read_fds = FdSet::new();
+    let mut write_fds = FdSet::new();
+
+    let notifier_fd = notifier.socket.as_raw_fd();
+    read_fds.insert(notifier_fd);
+    let mut max_fd = notifier_fd;
+
+    for registration in self.registrations.borrow().iter() {
+      match registration.interest {
+        Interest::Read => read_fds.insert(registration.fd),
+        Interest::Write => write_fds.insert(registration.fd),
+      }
+
+      max_fd = cmp::max(max_fd, registration.fd);
+    }
+
+    let mut tv = timeout.map(|timeout| libc::timeval {
+      tv_sec: timeout.as_secs() as _,
+      tv_usec: timeout.subsec_micros() as _,
+    });
+    let tv_ptr = tv.as_mut().map_or(ptr::null_mut(), |tv| tv as *mut _);
+
+    let res = unsafe { libc::select(max_fd + 1, read_fds.as_mut_ptr(), write_fds.as_mut_ptr(), ptr::null_mut(), tv_ptr) };
+
+    let mut ready = Vec::new();
+
+    self.registrations.borrow_mut().retain(|registration| {
+      // On error, e.g. if a socket was closed without deregistering, wake all tasks so they can retry.
+      let is_ready = res < 0 || match registration.interest {
+        Interest::Read => read_fds.contains(registration.fd),
+        Interest::Write => write_fds.contains(registration.fd),
+      };
+
+      if is_ready {
+        ready.push(registration.waker.clone());
+      }
+
+      !is_ready
+    });
+
+    let expired = self.timers.borrow_mut().advance(Instant::now());
+
+    for waker in ready.into_iter().chain(expired) {
+      waker.wake();
+    }
+  }
+}
+
+struct FdSet(MaybeUninit<libc::fd_set>);
+
+impl FdSet {
+  fn new() -> Self {
+    let mut set = MaybeUninit::uninit();
+    unsafe { libc::FD_ZERO(set.as_mut_ptr()) };
+    Self(set)
+  }
+
+  fn insert(&mut self, fd: RawFd) {
+    unsafe { libc::FD_SET(fd, self.0.as_mut_ptr()) }
+  }
+
+  fn contains(&self, fd: RawFd) -> bool {
+    unsafe { libc::FD_ISSET(fd, self.0.as_ptr()) }
+  }
+
+  fn as_mut_ptr(&mut self) -> *mut libc::fd_set {
+    self.0.as_mut_ptr()
+  }
+}
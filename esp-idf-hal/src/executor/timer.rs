@@ -0,0 +1,244 @@
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::io;
+use std::time::{Duration, Instant};
+
+use pin_project::pin_project;
+
+use super::{try_with_runtime, with_runtime};
+
+/// Number of slots in the timer wheel.
+const WHEEL_SIZE: usize = 64;
+/// Resolution of the timer wheel, which matches the default FreeRTOS tick rate.
+const TICK: Duration = Duration::from_millis(10);
+
+/// Identifies a timer in a [`TimerWheel`](struct.TimerWheel.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct TimerId {
+  tick: u64,
+  id: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+  id: u64,
+  tick: u64,
+  waker: Waker,
+}
+
+/// A hashed timer wheel.
+///
+/// Each timer is stored in the slot for its deadline tick modulo the wheel size,
+/// so timers further away than one revolution share slots with nearer ones.
+#[derive(Debug)]
+pub(super) struct TimerWheel {
+  start: Instant,
+  current_tick: u64,
+  next_id: u64,
+  slots: Vec<Vec<Entry>>,
+}
+
+impl TimerWheel {
+  pub fn new() -> Self {
+    Self {
+      start: Instant::now(),
+      current_tick: 0,
+      next_id: 0,
+      slots: (0..WHEEL_SIZE).map(|_| Vec::new()).collect(),
+    }
+  }
+
+  fn slot(tick: u64) -> usize {
+    (tick % WHEEL_SIZE as u64) as usize
+  }
+
+  /// Number of whole ticks elapsed at `instant`.
+  fn elapsed_ticks(&self, instant: Instant) -> u64 {
+    (instant.saturating_duration_since(self.start).as_nanos() / TICK.as_nanos()) as u64
+  }
+
+  /// Add a timer which wakes `waker` at `deadline`, rounded up to the next tick.
+  pub fn insert(&mut self, deadline: Instant, waker: Waker) -> TimerId {
+    let nanos = deadline.saturating_duration_since(self.start).as_nanos();
+    let tick = ((nanos + TICK.as_nanos() - 1) / TICK.as_nanos()) as u64;
+    let tick = tick.max(self.current_tick);
+
+    let id = self.next_id;
+    self.next_id += 1;
+
+    self.slots[Self::slot(tick)].push(Entry { id, tick, waker });
+
+    TimerId { tick, id }
+  }
+
+  /// Update the waker of a timer. Returns `false` if the timer already expired.
+  pub fn update(&mut self, timer: TimerId, waker: &Waker) -> bool {
+    match self.slots[Self::slot(timer.tick)].iter_mut().find(|entry| entry.id == timer.id) {
+      Some(entry) => {
+        if !entry.waker.will_wake(waker) {
+          entry.waker = waker.clone();
+        }
+        true
+      },
+      None => false,
+    }
+  }
+
+  pub fn remove(&mut self, timer: TimerId) {
+    self.slots[Self::slot(timer.tick)].retain(|entry| entry.id != timer.id);
+  }
+
+  /// The deadline of the earliest timer.
+  pub fn next_deadline(&self) -> Option<Instant> {
+    let tick = self.slots.iter().flatten().map(|entry| entry.tick).min()?;
+    Some(self.start + Duration::from_nanos(tick * TICK.as_nanos() as u64))
+  }
+
+  /// Remove all timers which expired at `now`, returning their wakers.
+  pub fn advance(&mut self, now: Instant) -> Vec<Waker> {
+    let target = self.elapsed_ticks(now);
+    let mut expired = Vec::new();
+
+    if target < self.current_tick {
+      return expired
+    }
+
+    let steps = (target - self.current_tick + 1).min(WHEEL_SIZE as u64);
+
+    for tick in self.current_tick..(self.current_tick + steps) {
+      let slot = &mut self.slots[Self::slot(tick)];
+
+      let mut i = 0;
+      while i < slot.len() {
+        if slot[i].tick <= target {
+          expired.push(slot.swap_remove(i).waker);
+        } else {
+          i += 1;
+        }
+      }
+    }
+
+    self.current_tick = target + 1;
+
+    expired
+  }
+}
+
+/// A future which completes at a deadline, returned by [`sleep`](fn.sleep.html).
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct Sleep {
+  deadline: Instant,
+  timer: Option<TimerId>,
+}
+
+impl Sleep {
+  pub fn deadline(&self) -> Instant {
+    self.deadline
+  }
+
+  /// Change the deadline, e.g. to reuse this future for a periodic timer.
+  pub fn reset(&mut self, deadline: Instant) {
+    self.cancel();
+    self.deadline = deadline;
+  }
+
+  fn cancel(&mut self) {
+    if let Some(timer) = self.timer.take() {
+      try_with_runtime(|runtime| runtime.reactor.timers.borrow_mut().remove(timer));
+    }
+  }
+}
+
+impl Future for Sleep {
+  type Output = ();
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    if Instant::now() >= self.deadline {
+      self.cancel();
+      return Poll::Ready(())
+    }
+
+    let deadline = self.deadline;
+    let timer = self.timer;
+
+    self.timer = Some(with_runtime(|runtime| {
+      let mut timers = runtime.reactor.timers.borrow_mut();
+
+      match timer {
+        Some(timer) if timers.update(timer, cx.waker()) => timer,
+        _ => timers.insert(deadline, cx.waker().clone()),
+      }
+    }));
+
+    Poll::Pending
+  }
+}
+
+impl Drop for Sleep {
+  fn drop(&mut self) {
+    self.cancel();
+  }
+}
+
+/// Wait until `duration` has elapsed.
+pub fn sleep(duration: Duration) -> Sleep {
+  sleep_until(Instant::now() + duration)
+}
+
+/// Wait until `deadline`.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+  Sleep { deadline, timer: None }
+}
+
+/// The error returned when a [`Timeout`](struct.Timeout.html) elapses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    "deadline has elapsed".fmt(f)
+  }
+}
+
+impl std::error::Error for Elapsed {}
+
+impl From<Elapsed> for io::Error {
+  fn from(elapsed: Elapsed) -> Self {
+    io::Error::new(io::ErrorKind::TimedOut, elapsed)
+  }
+}
+
+/// A future which fails if the inner future does not complete in time, returned by [`timeout`](fn.timeout.html).
+#[must_use = "futures do nothing unless polled"]
+#[pin_project]
+#[derive(Debug)]
+pub struct Timeout<F> {
+  #[pin]
+  future: F,
+  sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+  type Output = Result<F::Output, Elapsed>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    let this = self.project();
+
+    if let Poll::Ready(output) = this.future.poll(cx) {
+      return Poll::Ready(Ok(output))
+    }
+
+    match Pin::new(this.sleep).poll(cx) {
+      Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+/// Run `future`, failing with [`Elapsed`](struct.Elapsed.html) if it does not complete within `duration`.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+  Timeout { future, sleep: sleep(duration) }
+}
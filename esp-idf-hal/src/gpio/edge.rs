@@ -0,0 +1,130 @@
+use core::future::Future;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use std::sync::Arc;
+
+use esp_idf_bindgen::*;
+
+use crate::EspError;
+use crate::sync::{install_once, pend_wake, PendWake, WakerCell};
+
+/// A signal edge which triggers an interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+  Rising,
+  Falling,
+  Any,
+}
+
+impl From<Edge> for gpio_int_type_t {
+  fn from(edge: Edge) -> Self {
+    match edge {
+      Edge::Rising => gpio_int_type_t::GPIO_INTR_POSEDGE,
+      Edge::Falling => gpio_int_type_t::GPIO_INTR_NEGEDGE,
+      Edge::Any => gpio_int_type_t::GPIO_INTR_ANYEDGE,
+    }
+  }
+}
+
+static ISR_SERVICE_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Install the shared GPIO interrupt service, unless it was already installed, e.g. by C code.
+fn install_isr_service() -> Result<(), EspError> {
+  install_once(&ISR_SERVICE_INSTALLED, || esp_ok!(gpio_install_isr_service(0)))
+}
+
+/// State shared between an [`EdgeFuture`](struct.EdgeFuture.html) and its interrupt handler.
+#[derive(Debug)]
+struct EdgeState {
+  triggered: AtomicBool,
+  waker: WakerCell,
+}
+
+impl PendWake for EdgeState {
+  fn wake(&self) {
+    self.waker.wake();
+  }
+}
+
+extern "C" fn edge_isr(arg: *mut libc::c_void) {
+  // SAFETY: `arg` was created using `Arc::as_ptr` in `EdgeFuture::poll`, and the
+  // future holds a reference until the handler is removed.
+  let state = ManuallyDrop::new(unsafe { Arc::from_raw(arg as *const EdgeState) });
+
+  // Only the first edge needs to wake the future.
+  if !state.triggered.swap(true, Ordering::AcqRel) {
+    pend_wake(&state);
+  }
+}
+
+/// A future returned by `wait_for_edge` on a GPIO pin.
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct EdgeFuture<'p> {
+  num: gpio_num_t,
+  edge: Edge,
+  state: Option<Arc<EdgeState>>,
+  _pin: PhantomData<&'p mut ()>,
+}
+
+impl EdgeFuture<'_> {
+  pub(crate) fn new(num: gpio_num_t, edge: Edge) -> Self {
+    Self { num, edge, state: None, _pin: PhantomData }
+  }
+
+  fn enable(&mut self, waker: &Waker) -> Result<(), EspError> {
+    install_isr_service()?;
+
+    let state = Arc::new(EdgeState { triggered: AtomicBool::new(false), waker: WakerCell::default() });
+    state.waker.register(waker);
+
+    esp_ok!(gpio_set_intr_type(self.num, gpio_int_type_t::GPIO_INTR_DISABLE))?;
+    esp_ok!(gpio_isr_handler_add(self.num, Some(edge_isr), Arc::as_ptr(&state) as *mut _))?;
+    self.state = Some(state);
+
+    esp_ok!(gpio_set_intr_type(self.num, self.edge.into()))?;
+
+    // On the ESP8266, setting the interrupt type also enables it.
+    #[cfg(target_device = "esp32")]
+    esp_ok!(gpio_intr_enable(self.num))?;
+
+    Ok(())
+  }
+}
+
+impl Future for EdgeFuture<'_> {
+  type Output = Result<(), EspError>;
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    let state = match &self.state {
+      Some(state) => state,
+      None => {
+        if let Err(err) = self.enable(cx.waker()) {
+          return Poll::Ready(Err(err))
+        }
+
+        return Poll::Pending
+      },
+    };
+
+    state.waker.register(cx.waker());
+
+    if state.triggered.load(Ordering::Acquire) {
+      return Poll::Ready(Ok(()))
+    }
+
+    Poll::Pending
+  }
+}
+
+impl Drop for EdgeFuture<'_> {
+  fn drop(&mut self) {
+    if self.state.is_some() {
+      let _ = esp_ok!(gpio_set_intr_type(self.num, gpio_int_type_t::GPIO_INTR_DISABLE));
+      let _ = esp_ok!(gpio_isr_handler_remove(self.num));
+    }
+  }
+}
@@ -0,0 +1,357 @@
+//! GPIO pins using the IDF `gpio` driver.
+//!
+//! Each pin has its own type, with its mode as a type parameter. Pins are obtained
+//! once using [`Pins::take`](struct.Pins.html#method.take) and converted into the
+//! required mode, after which they implement the `embedded-hal` digital traits.
+//!
+//! ```ignore
+//! let pins = gpio::Pins::take().unwrap();
+//!
+//! let mut led = pins.gpio22.into_output()?;
+//! led.set_high()?;
+//!
+//! let mut button = pins.gpio0.into_input()?;
+//! button.set_pull(Pull::Up)?;
+//! button.wait_for_edge(Edge::Falling).await?;
+//! ```
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embedded_hal::digital::v2::{toggleable, InputPin, OutputPin, StatefulOutputPin};
+use esp_idf_bindgen::*;
+
+use crate::EspError;
+
+mod edge;
+pub use edge::*;
+
+/// The mode of a pin which has not been configured yet.
+#[derive(Debug)]
+pub struct Unknown;
+
+/// Input mode.
+#[derive(Debug)]
+pub struct Input;
+
+/// Push-pull output mode. The pin can still be read.
+#[derive(Debug)]
+pub struct Output;
+
+/// Open-drain output mode. The pin can still be read.
+#[derive(Debug)]
+pub struct OpenDrain;
+
+/// Analog mode, with the digital input and output disabled.
+#[derive(Debug)]
+pub struct Analog;
+
+pub(crate) mod sealed {
+  use esp_idf_bindgen::gpio_num_t;
+
+  pub trait Sealed {}
+
+  pub trait SealedPin {
+    fn gpio_num(&self) -> gpio_num_t;
+  }
+}
+
+/// Modes in which a pin can be read.
+pub trait InputMode: sealed::Sealed {}
+impl sealed::Sealed for Input {}
+impl InputMode for Input {}
+
+/// Modes in which a pin can be driven.
+pub trait OutputMode: InputMode {}
+impl sealed::Sealed for Output {}
+impl InputMode for Output {}
+impl OutputMode for Output {}
+impl sealed::Sealed for OpenDrain {}
+impl InputMode for OpenDrain {}
+impl OutputMode for OpenDrain {}
+
+/// A GPIO pin in any mode.
+pub trait Pin: sealed::SealedPin {
+  /// The GPIO number of this pin.
+  fn number(&self) -> u8;
+}
+
+/// A GPIO pin which can be used as an output, e.g. by other peripheral drivers.
+pub trait IoPin: Pin {}
+
+/// Internal pull resistor configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pull {
+  Floating,
+  Up,
+  /// Only supported by GPIO 16 on the ESP8266.
+  Down,
+  #[cfg(target_device = "esp32")]
+  UpDown,
+}
+
+impl From<Pull> for gpio_pull_mode_t {
+  fn from(pull: Pull) -> Self {
+    match pull {
+      Pull::Floating => gpio_pull_mode_t::GPIO_FLOATING,
+      Pull::Up => gpio_pull_mode_t::GPIO_PULLUP_ONLY,
+      Pull::Down => gpio_pull_mode_t::GPIO_PULLDOWN_ONLY,
+      #[cfg(target_device = "esp32")]
+      Pull::UpDown => gpio_pull_mode_t::GPIO_PULLUP_PULLDOWN,
+    }
+  }
+}
+
+#[cfg(target_device = "esp32")]
+const MODE_INPUT: gpio_mode_t = gpio_mode_t::GPIO_MODE_INPUT;
+#[cfg(target_device = "esp32")]
+const MODE_OUTPUT: gpio_mode_t = gpio_mode_t::GPIO_MODE_INPUT_OUTPUT;
+#[cfg(target_device = "esp32")]
+const MODE_OPEN_DRAIN: gpio_mode_t = gpio_mode_t::GPIO_MODE_INPUT_OUTPUT_OD;
+
+// The ESP8266 input is always enabled.
+#[cfg(target_device = "esp8266")]
+const MODE_INPUT: gpio_mode_t = gpio_mode_t::GPIO_MODE_INPUT;
+#[cfg(target_device = "esp8266")]
+const MODE_OUTPUT: gpio_mode_t = gpio_mode_t::GPIO_MODE_OUTPUT;
+#[cfg(target_device = "esp8266")]
+const MODE_OPEN_DRAIN: gpio_mode_t = gpio_mode_t::GPIO_MODE_OUTPUT_OD;
+
+/// Reset `num` to a GPIO in `mode` with pull resistors and interrupts disabled.
+fn configure(num: gpio_num_t, mode: gpio_mode_t) -> Result<(), EspError> {
+  let config = gpio_config_t {
+    pin_bit_mask: (1u64 << num as u32) as _,
+    mode,
+    pull_up_en: gpio_pullup_t::GPIO_PULLUP_DISABLE,
+    pull_down_en: gpio_pulldown_t::GPIO_PULLDOWN_DISABLE,
+    intr_type: gpio_int_type_t::GPIO_INTR_DISABLE,
+  };
+
+  esp_ok!(gpio_config(&config))
+}
+
+fn set_pull(num: gpio_num_t, pull: Pull) -> Result<(), EspError> {
+  esp_ok!(gpio_set_pull_mode(num, pull.into()))
+}
+
+fn set_level(num: gpio_num_t, level: bool) -> Result<(), EspError> {
+  esp_ok!(gpio_set_level(num, level as u32))
+}
+
+fn get_level(num: gpio_num_t) -> bool {
+  unsafe { gpio_get_level(num) != 0 }
+}
+
+macro_rules! pin {
+  (@common $Gpio:ident, $num:ident, $n:expr) => {
+    #[doc = concat!("GPIO ", stringify!($n), ".")]
+    pub struct $Gpio<MODE = Unknown> {
+      level: bool,
+      _mode: PhantomData<MODE>,
+    }
+
+    impl<MODE> $Gpio<MODE> {
+      const NUM: gpio_num_t = gpio_num_t::$num;
+
+      pub(crate) fn with_mode<NEW>(self) -> $Gpio<NEW> {
+        $Gpio { level: self.level, _mode: PhantomData }
+      }
+
+      /// Configure the pin as a floating input.
+      pub fn into_input(self) -> Result<$Gpio<Input>, EspError> {
+        configure(Self::NUM, MODE_INPUT)?;
+        Ok(self.with_mode())
+      }
+    }
+
+    impl<MODE: InputMode> $Gpio<MODE> {
+      /// Wait until `edge` occurs on the pin.
+      ///
+      /// The interrupt is only enabled while the returned future is alive.
+      pub fn wait_for_edge(&mut self, edge: Edge) -> EdgeFuture<'_> {
+        EdgeFuture::new(Self::NUM, edge)
+      }
+    }
+
+    impl<MODE> fmt::Debug for $Gpio<MODE> {
+      fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(stringify!($Gpio))
+      }
+    }
+
+    impl<MODE> sealed::SealedPin for $Gpio<MODE> {
+      fn gpio_num(&self) -> gpio_num_t {
+        Self::NUM
+      }
+    }
+
+    impl<MODE> Pin for $Gpio<MODE> {
+      fn number(&self) -> u8 {
+        $n
+      }
+    }
+
+    impl<MODE: InputMode> InputPin for $Gpio<MODE> {
+      type Error = EspError;
+
+      fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(get_level(Self::NUM))
+      }
+
+      fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!get_level(Self::NUM))
+      }
+    }
+  };
+  (input $Gpio:ident, $num:ident, $n:expr) => {
+    pin!(@common $Gpio, $num, $n);
+  };
+  (io $Gpio:ident, $num:ident, $n:expr) => {
+    pin!(@common $Gpio, $num, $n);
+
+    impl<MODE> $Gpio<MODE> {
+      /// Configure the pin as a push-pull output, initially driving it low.
+      pub fn into_output(mut self) -> Result<$Gpio<Output>, EspError> {
+        set_level(Self::NUM, false)?;
+        configure(Self::NUM, MODE_OUTPUT)?;
+        self.level = false;
+        Ok(self.with_mode())
+      }
+
+      /// Configure the pin as an open-drain output, initially releasing it.
+      pub fn into_open_drain_output(mut self) -> Result<$Gpio<OpenDrain>, EspError> {
+        set_level(Self::NUM, true)?;
+        configure(Self::NUM, MODE_OPEN_DRAIN)?;
+        self.level = true;
+        Ok(self.with_mode())
+      }
+
+      /// Configure the internal pull resistors. Converting the pin into another mode resets them.
+      pub fn set_pull(&mut self, pull: Pull) -> Result<(), EspError> {
+        set_pull(Self::NUM, pull)
+      }
+    }
+
+    impl<MODE> IoPin for $Gpio<MODE> {}
+
+    impl<MODE: OutputMode> OutputPin for $Gpio<MODE> {
+      type Error = EspError;
+
+      fn set_low(&mut self) -> Result<(), Self::Error> {
+        set_level(Self::NUM, false)?;
+        self.level = false;
+        Ok(())
+      }
+
+      fn set_high(&mut self) -> Result<(), Self::Error> {
+        set_level(Self::NUM, true)?;
+        self.level = true;
+        Ok(())
+      }
+    }
+
+    /// Returns the level the pin is driven to, which for open-drain
+    /// outputs may differ from the level read using `InputPin`.
+    impl<MODE: OutputMode> StatefulOutputPin for $Gpio<MODE> {
+      fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.level)
+      }
+
+      fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.level)
+      }
+    }
+
+    impl<MODE: OutputMode> toggleable::Default for $Gpio<MODE> {}
+  };
+}
+
+macro_rules! pins {
+  ($($kind:ident $Gpio:ident, $field:ident, $num:ident, $n:expr;)*) => {
+    $(
+      pin!($kind $Gpio, $num, $n);
+    )*
+
+    /// All GPIO pins which are not reserved, e.g. for the SPI flash.
+    #[derive(Debug)]
+    pub struct Pins {
+      $(
+        pub $field: $Gpio,
+      )*
+    }
+
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+
+    impl Pins {
+      /// Take all pins. Returns `None` if they were already taken.
+      pub fn take() -> Option<Self> {
+        if TAKEN.swap(true, Ordering::AcqRel) {
+          return None
+        }
+
+        Some(unsafe { Self::steal() })
+      }
+
+      /// Get all pins, even if they were already taken.
+      ///
+      /// # Safety
+      ///
+      /// The caller must ensure that each pin is only used once.
+      pub unsafe fn steal() -> Self {
+        Self {
+          $(
+            $field: $Gpio { level: false, _mode: PhantomData },
+          )*
+        }
+      }
+    }
+  };
+}
+
+#[cfg(target_device = "esp32")]
+pins! {
+  io Gpio0, gpio0, GPIO_NUM_0, 0;
+  io Gpio1, gpio1, GPIO_NUM_1, 1;
+  io Gpio2, gpio2, GPIO_NUM_2, 2;
+  io Gpio3, gpio3, GPIO_NUM_3, 3;
+  io Gpio4, gpio4, GPIO_NUM_4, 4;
+  io Gpio5, gpio5, GPIO_NUM_5, 5;
+  io Gpio12, gpio12, GPIO_NUM_12, 12;
+  io Gpio13, gpio13, GPIO_NUM_13, 13;
+  io Gpio14, gpio14, GPIO_NUM_14, 14;
+  io Gpio15, gpio15, GPIO_NUM_15, 15;
+  io Gpio16, gpio16, GPIO_NUM_16, 16;
+  io Gpio17, gpio17, GPIO_NUM_17, 17;
+  io Gpio18, gpio18, GPIO_NUM_18, 18;
+  io Gpio19, gpio19, GPIO_NUM_19, 19;
+  io Gpio21, gpio21, GPIO_NUM_21, 21;
+  io Gpio22, gpio22, GPIO_NUM_22, 22;
+  io Gpio23, gpio23, GPIO_NUM_23, 23;
+  io Gpio25, gpio25, GPIO_NUM_25, 25;
+  io Gpio26, gpio26, GPIO_NUM_26, 26;
+  io Gpio27, gpio27, GPIO_NUM_27, 27;
+  io Gpio32, gpio32, GPIO_NUM_32, 32;
+  io Gpio33, gpio33, GPIO_NUM_33, 33;
+  input Gpio34, gpio34, GPIO_NUM_34, 34;
+  input Gpio35, gpio35, GPIO_NUM_35, 35;
+  input Gpio36, gpio36, GPIO_NUM_36, 36;
+  input Gpio37, gpio37, GPIO_NUM_37, 37;
+  input Gpio38, gpio38, GPIO_NUM_38, 38;
+  input Gpio39, gpio39, GPIO_NUM_39, 39;
+}
+
+#[cfg(target_device = "esp8266")]
+pins! {
+  io Gpio0, gpio0, GPIO_NUM_0, 0;
+  io Gpio1, gpio1, GPIO_NUM_1, 1;
+  io Gpio2, gpio2, GPIO_NUM_2, 2;
+  io Gpio3, gpio3, GPIO_NUM_3, 3;
+  io Gpio4, gpio4, GPIO_NUM_4, 4;
+  io Gpio5, gpio5, GPIO_NUM_5, 5;
+  io Gpio12, gpio12, GPIO_NUM_12, 12;
+  io Gpio13, gpio13, GPIO_NUM_13, 13;
+  io Gpio14, gpio14, GPIO_NUM_14, 14;
+  io Gpio15, gpio15, GPIO_NUM_15, 15;
+  io Gpio16, gpio16, GPIO_NUM_16, 16;
+}
@@ -0,0 +1,291 @@
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+
+use super::{Heap, HeapCaps};
+
+fn no_mem() -> EspError {
+  EspErrorKind::Generic(GenericErrorKind::NoMem).into()
+}
+
+/// A `Box` allocated from a heap with specific [`HeapCaps`](struct.HeapCaps.html),
+/// e.g. in DMA-capable memory or in external SPI RAM.
+pub struct CapsBox<T> {
+  ptr: NonNull<T>,
+  caps: HeapCaps,
+  _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for CapsBox<T> {}
+unsafe impl<T: Sync> Sync for CapsBox<T> {}
+
+impl<T> CapsBox<T> {
+  /// Move `value` into memory with the given capabilities.
+  pub fn new(value: T, caps: HeapCaps) -> Result<Self, EspError> {
+    let ptr = Heap::alloc_caps(Layout::new::<T>(), caps).ok_or_else(no_mem)?.cast::<T>();
+    unsafe { ptr::write(ptr.as_ptr(), value) };
+    Ok(Self { ptr, caps, _marker: PhantomData })
+  }
+
+  /// The capabilities this box was allocated with.
+  pub fn caps(this: &Self) -> HeapCaps {
+    this.caps
+  }
+
+  /// Move the value out of the box and free its memory.
+  pub fn into_inner(this: Self) -> T {
+    let this = mem::ManuallyDrop::new(this);
+
+    unsafe {
+      let value = ptr::read(this.ptr.as_ptr());
+      Heap::dealloc_caps(this.ptr.cast(), Layout::new::<T>());
+      value
+    }
+  }
+}
+
+impl<T> Deref for CapsBox<T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    unsafe { self.ptr.as_ref() }
+  }
+}
+
+impl<T> DerefMut for CapsBox<T> {
+  fn deref_mut(&mut self) -> &mut T {
+    unsafe { self.ptr.as_mut() }
+  }
+}
+
+impl<T> Drop for CapsBox<T> {
+  fn drop(&mut self) {
+    unsafe {
+      ptr::drop_in_place(self.ptr.as_ptr());
+      Heap::dealloc_caps(self.ptr.cast(), Layout::new::<T>());
+    }
+  }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CapsBox<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    (**self).fmt(f)
+  }
+}
+
+/// A `Vec` allocated from a heap with specific [`HeapCaps`](struct.HeapCaps.html),
+/// e.g. a DMA buffer or a large cache in external SPI RAM.
+///
+/// Growing the vector moves its contents into a new allocation with the same capabilities.
+pub struct CapsVec<T> {
+  ptr: NonNull<T>,
+  len: usize,
+  capacity: usize,
+  caps: HeapCaps,
+  _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for CapsVec<T> {}
+unsafe impl<T: Sync> Sync for CapsVec<T> {}
+
+impl<T> CapsVec<T> {
+  /// Create an empty vector which allocates with the given capabilities once elements are added.
+  pub fn new(caps: HeapCaps) -> Self {
+    let capacity = if mem::size_of::<T>() == 0 { usize::max_value() } else { 0 };
+    Self { ptr: NonNull::dangling(), len: 0, capacity, caps, _marker: PhantomData }
+  }
+
+  /// Create an empty vector with space for at least `capacity` elements.
+  pub fn with_capacity(capacity: usize, caps: HeapCaps) -> Result<Self, EspError> {
+    let mut vec = Self::new(caps);
+    vec.try_reserve(capacity)?;
+    Ok(vec)
+  }
+
+  /// The capabilities this vector allocates with.
+  pub fn caps(&self) -> HeapCaps {
+    self.caps
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  pub fn as_ptr(&self) -> *const T {
+    self.ptr.as_ptr()
+  }
+
+  pub fn as_mut_ptr(&mut self) -> *mut T {
+    self.ptr.as_ptr()
+  }
+
+  /// Reserve space for at least `additional` more elements.
+  pub fn try_reserve(&mut self, additional: usize) -> Result<(), EspError> {
+    let required = self.len.checked_add(additional).ok_or_else(no_mem)?;
+
+    if required <= self.capacity {
+      return Ok(())
+    }
+
+    let capacity = required.max(self.capacity * 2).max(4);
+    let layout = Layout::array::<T>(capacity).map_err(|_| no_mem())?;
+    let ptr = Heap::alloc_caps(layout, self.caps).ok_or_else(no_mem)?.cast::<T>();
+
+    unsafe {
+      ptr::copy_nonoverlapping(self.ptr.as_ptr(), ptr.as_ptr(), self.len);
+      self.free();
+    }
+
+    self.ptr = ptr;
+    self.capacity = capacity;
+
+    Ok(())
+  }
+
+  /// Append `value`, returning it back if there is not enough memory.
+  pub fn push(&mut self, value: T) -> Result<(), T> {
+    if self.try_reserve(1).is_err() {
+      return Err(value)
+    }
+
+    unsafe { ptr::write(self.ptr.as_ptr().add(self.len), value) };
+    self.len += 1;
+
+    Ok(())
+  }
+
+  pub fn pop(&mut self) -> Option<T> {
+    if self.len == 0 {
+      return None
+    }
+
+    self.len -= 1;
+    Some(unsafe { ptr::read(self.ptr.as_ptr().add(self.len)) })
+  }
+
+  /// Shorten the vector to `len` elements, dropping the rest.
+  pub fn truncate(&mut self, len: usize) {
+    if len >= self.len {
+      return
+    }
+
+    let tail = ptr::slice_from_raw_parts_mut(unsafe { self.ptr.as_ptr().add(len) }, self.len - len);
+    self.len = len;
+    unsafe { ptr::drop_in_place(tail) };
+  }
+
+  pub fn clear(&mut self) {
+    self.truncate(0)
+  }
+
+  /// Free the allocation without dropping any elements.
+  unsafe fn free(&mut self) {
+    if mem::size_of::<T>() != 0 && self.capacity != 0 {
+      Heap::dealloc_caps(self.ptr.cast(), Layout::array::<T>(self.capacity).unwrap());
+    }
+  }
+}
+
+impl<T: Clone> CapsVec<T> {
+  /// Create a vector containing `len` clones of `value`, e.g. a zeroed DMA buffer.
+  pub fn from_elem(value: T, len: usize, caps: HeapCaps) -> Result<Self, EspError> {
+    let mut vec = Self::with_capacity(len, caps)?;
+    vec.resize(len, value)?;
+    Ok(vec)
+  }
+
+  /// Create a vector containing clones of all elements in `slice`.
+  pub fn from_slice(slice: &[T], caps: HeapCaps) -> Result<Self, EspError> {
+    let mut vec = Self::with_capacity(slice.len(), caps)?;
+    vec.extend_from_slice(slice)?;
+    Ok(vec)
+  }
+
+  /// Resize the vector to `len` elements, filling new elements with clones of `value`.
+  pub fn resize(&mut self, len: usize, value: T) -> Result<(), EspError> {
+    if len <= self.len {
+      self.truncate(len);
+      return Ok(())
+    }
+
+    self.try_reserve(len - self.len)?;
+
+    while self.len < len {
+      unsafe { ptr::write(self.ptr.as_ptr().add(self.len), value.clone()) };
+      self.len += 1;
+    }
+
+    Ok(())
+  }
+
+  /// Append clones of all elements in `slice`.
+  pub fn extend_from_slice(&mut self, slice: &[T]) -> Result<(), EspError> {
+    self.try_reserve(slice.len())?;
+
+    for value in slice {
+      unsafe { ptr::write(self.ptr.as_ptr().add(self.len), value.clone()) };
+      self.len += 1;
+    }
+
+    Ok(())
+  }
+}
+
+impl<T> Deref for CapsVec<T> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+  }
+}
+
+impl<T> DerefMut for CapsVec<T> {
+  fn deref_mut(&mut self) -> &mut [T] {
+    unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+  }
+}
+
+impl<T> Drop for CapsVec<T> {
+  fn drop(&mut self) {
+    self.clear();
+    unsafe { self.free() };
+  }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CapsVec<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    (**self).fmt(f)
+  }
+}
+
+/// An [`Allocator`](https://doc.rust-lang.org/core/alloc/trait.Allocator.html) using heaps with
+/// specific [`HeapCaps`](struct.HeapCaps.html), e.g. `Vec::new_in(CapsAllocator(HeapCaps::SPIRAM))`.
+#[cfg(feature = "allocator_api")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapsAllocator(pub HeapCaps);
+
+#[cfg(feature = "allocator_api")]
+unsafe impl core::alloc::Allocator for CapsAllocator {
+  fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+    let ptr = Heap::alloc_caps(layout, self.0).ok_or(core::alloc::AllocError)?;
+    Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+  }
+
+  unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+    Heap::dealloc_caps(ptr, layout)
+  }
+}
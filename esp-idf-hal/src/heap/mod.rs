@@ -0,0 +1,194 @@
+use core::alloc::Layout;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+use std::ffi::CStr;
+use std::marker::PhantomData;
+
+use bitflags::bitflags;
+use esp_idf_bindgen::*;
+
+mod caps;
+pub use caps::*;
+
+pub use leak_tracker::*;
+
+bitflags! {
+  /// Capabilities of a memory region, used to select which heaps to query or allocate from.
+  pub struct HeapCaps: u32 {
+    /// Memory which can run executable code.
+    const EXEC = MALLOC_CAP_EXEC;
+    /// Memory which allows aligned 32-bit data accesses.
+    const BIT32 = MALLOC_CAP_32BIT;
+    /// Memory which allows 8-bit and 16-bit data accesses.
+    const BIT8 = MALLOC_CAP_8BIT;
+    /// Memory which can be accessed by DMA.
+    const DMA = MALLOC_CAP_DMA;
+    /// Memory in external SPI RAM.
+    #[cfg(target_device = "esp32")]
+    const SPIRAM = MALLOC_CAP_SPIRAM;
+    /// Internal memory, i.e. not in external SPI RAM.
+    #[cfg(target_device = "esp32")]
+    const INTERNAL = MALLOC_CAP_INTERNAL;
+  }
+}
+
+/// Alignment guaranteed by `heap_caps_malloc`.
+const MALLOC_ALIGN: usize = 4;
+
+impl Default for HeapCaps {
+  fn default() -> Self {
+    Self::BIT32
+  }
+}
+
+/// Statistics of all heaps matching a set of [`HeapCaps`](struct.HeapCaps.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+  /// Total size in bytes.
+  pub total: usize,
+  /// Currently free bytes.
+  pub free: usize,
+  /// Size of the largest free block, i.e. the largest possible allocation.
+  ///
+  /// On the ESP8266, this is the free size of the largest matching region, which is an upper bound.
+  pub largest_free_block: usize,
+  /// Lowest number of free bytes since boot.
+  pub minimum_free: usize,
+  /// Number of allocated blocks.
+  ///
+  /// Not tracked on the ESP8266, where this is always `0`.
+  pub allocated_blocks: usize,
+}
+
+impl HeapStats {
+  /// Currently allocated bytes.
+  pub fn allocated(&self) -> usize {
+    self.total.saturating_sub(self.free)
+  }
+}
+
+#[derive(Debug)]
+pub struct Heap {
+  _marker: PhantomData<()>,
+}
+
+impl Heap {
+  #[cfg(target_device = "esp32")]
+  pub fn total_size() -> usize {
+    unsafe { heap_caps_get_total_size(MALLOC_CAP_32BIT) as usize }
+  }
+
+  pub fn free_size() -> usize {
+    unsafe { heap_caps_get_free_size(MALLOC_CAP_32BIT) as usize }
+  }
+
+  /// Allocate memory for `layout` from a heap with the given capabilities.
+  ///
+  /// Returns `None` if there is not enough memory with these capabilities. Zero-sized
+  /// layouts do not allocate and return a dangling, well-aligned pointer.
+  pub fn alloc_caps(layout: Layout, caps: HeapCaps) -> Option<NonNull<u8>> {
+    if layout.size() == 0 {
+      return NonNull::new(layout.align() as *mut u8)
+    }
+
+    if layout.align() <= MALLOC_ALIGN {
+      return NonNull::new(unsafe { heap_caps_malloc(layout.size(), caps.bits()) as *mut u8 })
+    }
+
+    // Over-allocate and store the original pointer in front of the aligned one.
+    let header = mem::size_of::<*mut u8>();
+    let size = layout.size().checked_add(layout.align())?.checked_add(header)?;
+
+    let raw = unsafe { heap_caps_malloc(size, caps.bits()) as *mut u8 };
+    if raw.is_null() {
+      return None
+    }
+
+    unsafe {
+      let offset = header + raw.add(header).align_offset(layout.align());
+      let aligned = raw.add(offset);
+      ptr::write_unaligned(aligned.sub(header) as *mut *mut u8, raw);
+      NonNull::new(aligned)
+    }
+  }
+
+  /// Free memory allocated with [`alloc_caps`](#method.alloc_caps).
+  ///
+  /// # Safety
+  ///
+  /// `ptr` must have been returned by [`alloc_caps`](#method.alloc_caps) with the same `layout`.
+  pub unsafe fn dealloc_caps(ptr: NonNull<u8>, layout: Layout) {
+    if layout.size() == 0 {
+      return
+    }
+
+    let raw = if layout.align() <= MALLOC_ALIGN {
+      ptr.as_ptr()
+    } else {
+      ptr::read_unaligned(ptr.as_ptr().sub(mem::size_of::<*mut u8>()) as *const *mut u8)
+    };
+
+    heap_caps_free(raw as *mut _);
+  }
+
+  /// Get statistics of all heaps with the given capabilities.
+  #[cfg(target_device = "esp32")]
+  pub fn stats(caps: HeapCaps) -> HeapStats {
+    let mut info = MaybeUninit::<multi_heap_info_t>::uninit();
+    let info = unsafe {
+      heap_caps_get_info(info.as_mut_ptr(), caps.bits());
+      info.assume_init()
+    };
+
+    HeapStats {
+      total: unsafe { heap_caps_get_total_size(caps.bits()) as usize },
+      free: info.total_free_bytes as usize,
+      largest_free_block: info.largest_free_block as usize,
+      minimum_free: info.minimum_free_bytes as usize,
+      allocated_blocks: info.allocated_blocks as usize,
+    }
+  }
+
+  /// Get statistics of all heaps with the given capabilities.
+  #[cfg(target_device = "esp8266")]
+  pub fn stats(caps: HeapCaps) -> HeapStats {
+    let regions = unsafe { &g_heap_region[..g_heap_region_num as usize] };
+    let regions = regions.iter().filter(|region| region.caps & caps.bits() == caps.bits());
+
+    let (total, largest_free_block) = regions.fold((0, 0), |(total, largest), region| {
+      (total + region.total_size as usize, largest.max(region.free_bytes as usize))
+    });
+
+    HeapStats {
+      total,
+      free: unsafe { heap_caps_get_free_size(caps.bits()) as usize },
+      largest_free_block,
+      minimum_free: unsafe { heap_caps_get_minimum_free_size(caps.bits()) as usize },
+      allocated_blocks: 0,
+    }
+  }
+}
+
+/// [`Platform`](trait.Platform.html) hooks for a [`LeakTracker`](struct.LeakTracker.html),
+/// accounting allocations per FreeRTOS task.
+#[derive(Debug)]
+pub struct FreeRtos;
+
+impl Platform for FreeRtos {
+  fn current_thread() -> usize {
+    unsafe { xTaskGetCurrentTaskHandle() as usize }
+  }
+
+  fn thread_tag() -> Tag {
+    let name = unsafe { CStr::from_ptr(pcTaskGetTaskName(ptr::null_mut())) };
+    Tag::from_bytes(name.to_bytes())
+  }
+
+  fn free_heap() -> Option<usize> {
+    Some(Heap::free_size())
+  }
+
+  fn critical_section<R>(f: impl FnOnce() -> R) -> R {
+    crate::sync::critical_section(f)
+  }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+
+use crate::EspError;
+
+/// The error type for I2C transactions.
+#[derive(Debug, Clone)]
+pub enum I2cError {
+  /// The device did not acknowledge its address or a written byte.
+  Nack,
+  /// The transaction did not complete within the configured timeout.
+  ///
+  /// The IDF driver reports the hardware aborting the transaction, because arbitration was
+  /// lost to another master or SCL was held low for longer than the clock-stretch timeout,
+  /// as a timeout as well, so these cases cannot be distinguished.
+  Timeout,
+  /// Any other error.
+  Esp(EspError),
+}
+
+impl From<EspError> for I2cError {
+  fn from(esp_error: EspError) -> Self {
+    Self::Esp(esp_error)
+  }
+}
+
+impl fmt::Display for I2cError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Nack => "device did not acknowledge".fmt(f),
+      Self::Timeout => "transaction timed out".fmt(f),
+      Self::Esp(esp_error) => esp_error.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for I2cError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Esp(esp_error) => Some(esp_error),
+      _ => None,
+    }
+  }
+}
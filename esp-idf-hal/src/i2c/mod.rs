@@ -0,0 +1,322 @@
+//! I2C master using the IDF `i2c` driver.
+//!
+//! An [`I2c`](struct.I2c.html) bus implements the `embedded-hal` blocking I2C traits,
+//! so it can be used with existing device driver crates:
+//!
+//! ```ignore
+//! let pins = gpio::Pins::take().unwrap();
+//! let i2c = I2c::new(i2c::Port::I2c0, pins.gpio21, pins.gpio22, i2c::Config::new().frequency(400_000))?;
+//!
+//! let mut sensor = Bme280::new_primary(i2c, timer::BlockingDelay);
+//! ```
+
+use core::time::Duration;
+
+use embedded_hal::blocking::i2c::{Operation, Read, Transactional, Write, WriteRead};
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+use crate::gpio::{sealed::SealedPin, IoPin};
+use crate::sync;
+
+mod error;
+pub use error::*;
+
+/// An I2C controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+  I2c0,
+  #[cfg(target_device = "esp32")]
+  I2c1,
+}
+
+impl From<Port> for i2c_port_t {
+  #[cfg(target_device = "esp32")]
+  fn from(port: Port) -> Self {
+    port as i2c_port_t
+  }
+
+  #[cfg(target_device = "esp8266")]
+  fn from(port: Port) -> Self {
+    match port {
+      Port::I2c0 => i2c_port_t::I2C_NUM_0,
+    }
+  }
+}
+
+/// Configuration for an [`I2c`](struct.I2c.html) bus.
+#[derive(Debug, Clone)]
+pub struct Config {
+  frequency: u32,
+  pullups: bool,
+  timeout: Duration,
+  clock_stretch_timeout: Option<Duration>,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      frequency: 100_000,
+      pullups: true,
+      timeout: Duration::from_secs(1),
+      clock_stretch_timeout: None,
+    }
+  }
+}
+
+impl Config {
+  /// 100 kHz with internal pull-ups and a timeout of one second.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// SCL frequency in Hz. The ESP8266 uses a software implementation, which ignores this.
+  pub fn frequency(mut self, frequency: u32) -> Self {
+    self.frequency = frequency;
+    self
+  }
+
+  /// Enable the internal pull-ups on SDA and SCL, which are only sufficient for short buses at low frequencies.
+  pub fn pullups(mut self, enable: bool) -> Self {
+    self.pullups = enable;
+    self
+  }
+
+  /// Maximum duration of a transaction, including waiting for the bus.
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Maximum duration a device may hold SCL low, after which the transaction
+  /// fails with [`I2cError::Timeout`](enum.I2cError.html#variant.Timeout).
+  ///
+  /// On the ESP32, this is limited to about 13 ms. On the ESP8266, it is approximate,
+  /// since it is measured in busy-wait iterations.
+  pub fn clock_stretch_timeout(mut self, timeout: Duration) -> Self {
+    self.clock_stretch_timeout = Some(timeout);
+    self
+  }
+
+  #[cfg(target_device = "esp32")]
+  fn to_i2c_config(&self, sda: gpio_num_t, scl: gpio_num_t) -> i2c_config_t {
+    let mut config: i2c_config_t = unsafe { crate::zeroed_config() };
+    config.mode = i2c_mode_t::I2C_MODE_MASTER;
+    config.sda_io_num = sda as _;
+    config.scl_io_num = scl as _;
+    config.sda_pullup_en = self.pullups;
+    config.scl_pullup_en = self.pullups;
+    config.__bindgen_anon_1.master.clk_speed = self.frequency;
+    config
+  }
+
+  #[cfg(target_device = "esp8266")]
+  fn to_i2c_config(&self, sda: gpio_num_t, scl: gpio_num_t) -> i2c_config_t {
+    let pullup = if self.pullups { gpio_pullup_t::GPIO_PULLUP_ENABLE } else { gpio_pullup_t::GPIO_PULLUP_DISABLE };
+
+    let mut config: i2c_config_t = unsafe { crate::zeroed_config() };
+    config.mode = i2c_mode_t::I2C_MODE_MASTER;
+    config.sda_io_num = sda;
+    config.sda_pullup_en = pullup;
+    config.scl_io_num = scl;
+    config.scl_pullup_en = pullup;
+    // The SDK examples use 300 ticks for about 210 µs.
+    config.clk_stretch_tick = self.clock_stretch_timeout
+      .map_or(300, |timeout| (timeout.as_micros() * 10 / 7).min(u128::from(u32::max_value())) as u32);
+    config
+  }
+}
+
+/// Number of APB clock cycles per microsecond, used for the clock-stretch timeout.
+#[cfg(target_device = "esp32")]
+const APB_CYCLES_PER_US: u128 = 80;
+
+/// Maximum value of the 20-bit I2C timeout register.
+#[cfg(target_device = "esp32")]
+const MAX_TIMEOUT_CYCLES: u128 = 0xfffff;
+
+/// A command link, which is deleted when dropped.
+struct CmdLink(i2c_cmd_handle_t);
+
+impl CmdLink {
+  fn new() -> Result<Self, EspError> {
+    let handle = unsafe { i2c_cmd_link_create() };
+
+    if handle.is_null() {
+      return Err(EspErrorKind::Generic(GenericErrorKind::NoMem).into())
+    }
+
+    Ok(Self(handle))
+  }
+
+  fn start(&mut self) -> Result<(), EspError> {
+    esp_ok!(i2c_master_start(self.0))
+  }
+
+  fn stop(&mut self) -> Result<(), EspError> {
+    esp_ok!(i2c_master_stop(self.0))
+  }
+
+  fn address(&mut self, address: u8, read: bool) -> Result<(), EspError> {
+    esp_ok!(i2c_master_write_byte(self.0, address << 1 | read as u8, true))
+  }
+
+  /// Queue writing `bytes`, which must outlive the command link.
+  fn write(&mut self, bytes: &[u8]) -> Result<(), EspError> {
+    if bytes.is_empty() {
+      return Ok(())
+    }
+
+    esp_ok!(i2c_master_write(self.0, bytes.as_ptr() as *mut u8, bytes.len() as _, true))
+  }
+
+  /// Queue reading into `buffer`, which must outlive the command link.
+  fn read(&mut self, buffer: &mut [u8], last: bool) -> Result<(), EspError> {
+    if buffer.is_empty() {
+      return Ok(())
+    }
+
+    let ack = if last { i2c_ack_type_t::I2C_MASTER_LAST_NACK } else { i2c_ack_type_t::I2C_MASTER_ACK };
+    esp_ok!(i2c_master_read(self.0, buffer.as_mut_ptr(), buffer.len() as _, ack))
+  }
+}
+
+impl Drop for CmdLink {
+  fn drop(&mut self) {
+    unsafe { i2c_cmd_link_delete(self.0) };
+  }
+}
+
+fn is_read(operation: &Operation<'_>) -> bool {
+  matches!(operation, Operation::Read(_))
+}
+
+/// An I2C bus in master mode.
+#[derive(Debug)]
+pub struct I2c {
+  port: Port,
+  timeout: Duration,
+}
+
+impl I2c {
+  /// Install the driver on `port` using the given pins.
+  pub fn new(port: Port, sda: impl IoPin, scl: impl IoPin, config: Config) -> Result<Self, EspError> {
+    esp_ok!(i2c_param_config(port.into(), &config.to_i2c_config(sda.gpio_num(), scl.gpio_num())))?;
+
+    #[cfg(target_device = "esp32")]
+    esp_ok!(i2c_driver_install(port.into(), i2c_mode_t::I2C_MODE_MASTER, 0, 0, 0))?;
+
+    #[cfg(target_device = "esp8266")]
+    esp_ok!(i2c_driver_install(port.into(), i2c_mode_t::I2C_MODE_MASTER))?;
+
+    // Uninstalls the driver if setting the clock-stretch timeout fails.
+    let i2c = Self { port, timeout: config.timeout };
+
+    #[cfg(target_device = "esp32")]
+    {
+      if let Some(timeout) = config.clock_stretch_timeout {
+        let cycles = (timeout.as_micros() * APB_CYCLES_PER_US).min(MAX_TIMEOUT_CYCLES);
+        esp_ok!(i2c_set_timeout(port.into(), cycles as _))?;
+      }
+    }
+
+    Ok(i2c)
+  }
+
+  pub fn port(&self) -> Port {
+    self.port
+  }
+
+  /// Execute `operations` on the device at the 7-bit `address` in a single transaction.
+  ///
+  /// Consecutive operations of the same kind are merged, a repeated start is sent between
+  /// operations of different kinds. Without any operations, only the address is written,
+  /// which can be used to check whether a device is present.
+  pub fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), I2cError> {
+    let mut cmd = CmdLink::new()?;
+
+    if operations.is_empty() {
+      cmd.start()?;
+      cmd.address(address, false)?;
+    }
+
+    let mut previous_read = None;
+    let mut operations = operations;
+
+    while let Some((current, rest)) = operations.split_first_mut() {
+      let read = is_read(current);
+
+      if previous_read != Some(read) {
+        cmd.start()?;
+        cmd.address(address, read)?;
+      }
+
+      match current {
+        Operation::Write(bytes) => cmd.write(bytes)?,
+        Operation::Read(buffer) => {
+          // The last byte read before a stop or repeated start must not be acknowledged.
+          let last = rest.iter().take_while(|op| is_read(op)).all(|op| matches!(op, Operation::Read(buffer) if buffer.is_empty()));
+          cmd.read(buffer, last)?
+        },
+      }
+
+      previous_read = Some(read);
+      operations = rest;
+    }
+
+    cmd.stop()?;
+
+    self.begin(&cmd)
+  }
+
+  fn begin(&mut self, cmd: &CmdLink) -> Result<(), I2cError> {
+    let err = match esp_ok!(i2c_master_cmd_begin(self.port.into(), cmd.0, sync::ticks(Some(self.timeout)))) {
+      Ok(()) => return Ok(()),
+      Err(err) => err,
+    };
+
+    Err(match err.kind() {
+      EspErrorKind::Generic(GenericErrorKind::Fail) => I2cError::Nack,
+      EspErrorKind::Generic(GenericErrorKind::Timeout) => I2cError::Timeout,
+      _ => I2cError::Esp(err),
+    })
+  }
+}
+
+impl Drop for I2c {
+  fn drop(&mut self) {
+    let _ = esp_ok!(i2c_driver_delete(self.port.into()));
+  }
+}
+
+impl Read for I2c {
+  type Error = I2cError;
+
+  fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+    self.transaction(address, &mut [Operation::Read(buffer)])
+  }
+}
+
+impl Write for I2c {
+  type Error = I2cError;
+
+  fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+    self.transaction(address, &mut [Operation::Write(bytes)])
+  }
+}
+
+impl WriteRead for I2c {
+  type Error = I2cError;
+
+  fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+    self.transaction(address, &mut [Operation::Write(bytes), Operation::Read(buffer)])
+  }
+}
+
+impl Transactional for I2c {
+  type Error = I2cError;
+
+  fn exec<'a>(&mut self, address: u8, operations: &mut [Operation<'a>]) -> Result<(), Self::Error> {
+    self.transaction(address, operations)
+  }
+}
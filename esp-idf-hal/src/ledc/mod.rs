@@ -0,0 +1,347 @@
+//! PWM using the LED controller (LEDC).
+//!
+//! A [`Timer`](struct.Timer.html) generates the PWM frequency for any number of
+//! [`Channel`](struct.Channel.html)s, each of which drives one pin with its own duty cycle.
+//! Channels can fade between duty cycles in hardware, without involving the CPU.
+//!
+//! ```ignore
+//! let pins = gpio::Pins::take().unwrap();
+//!
+//! let timer = ledc::Timer::new(ledc::TimerNum::Timer0, ledc::TimerConfig::new().frequency(5_000))?;
+//! let mut led = timer.channel(ledc::ChannelNum::Channel0, pins.gpio22)?;
+//!
+//! led.fade_to(led.max_duty(), Duration::from_secs(1))?;
+//! ```
+
+use core::mem;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use std::sync::Arc;
+
+use embedded_hal::PwmPin;
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+use crate::gpio::IoPin;
+use crate::sync;
+
+mod status;
+pub use status::*;
+
+/// Frequency of the APB clock, which is used as the timer clock source.
+const APB_CLK_FREQ: u32 = 80_000_000;
+
+/// Maximum duty resolution of the timers in bits.
+const MAX_RESOLUTION: u8 = 20;
+
+/// Timer and channel group. Only high speed channels update their duty cycle immediately,
+/// low speed channels update at the end of the current PWM period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeedMode {
+  HighSpeed,
+  LowSpeed,
+}
+
+impl From<SpeedMode> for ledc_mode_t {
+  fn from(speed_mode: SpeedMode) -> Self {
+    match speed_mode {
+      SpeedMode::HighSpeed => ledc_mode_t::LEDC_HIGH_SPEED_MODE,
+      SpeedMode::LowSpeed => ledc_mode_t::LEDC_LOW_SPEED_MODE,
+    }
+  }
+}
+
+/// One of the four timers in each [`SpeedMode`](enum.SpeedMode.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerNum {
+  Timer0,
+  Timer1,
+  Timer2,
+  Timer3,
+}
+
+impl From<TimerNum> for ledc_timer_t {
+  fn from(timer: TimerNum) -> Self {
+    match timer {
+      TimerNum::Timer0 => ledc_timer_t::LEDC_TIMER_0,
+      TimerNum::Timer1 => ledc_timer_t::LEDC_TIMER_1,
+      TimerNum::Timer2 => ledc_timer_t::LEDC_TIMER_2,
+      TimerNum::Timer3 => ledc_timer_t::LEDC_TIMER_3,
+    }
+  }
+}
+
+/// One of the eight channels in each [`SpeedMode`](enum.SpeedMode.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelNum {
+  Channel0,
+  Channel1,
+  Channel2,
+  Channel3,
+  Channel4,
+  Channel5,
+  Channel6,
+  Channel7,
+}
+
+impl From<ChannelNum> for ledc_channel_t {
+  fn from(channel: ChannelNum) -> Self {
+    match channel {
+      ChannelNum::Channel0 => ledc_channel_t::LEDC_CHANNEL_0,
+      ChannelNum::Channel1 => ledc_channel_t::LEDC_CHANNEL_1,
+      ChannelNum::Channel2 => ledc_channel_t::LEDC_CHANNEL_2,
+      ChannelNum::Channel3 => ledc_channel_t::LEDC_CHANNEL_3,
+      ChannelNum::Channel4 => ledc_channel_t::LEDC_CHANNEL_4,
+      ChannelNum::Channel5 => ledc_channel_t::LEDC_CHANNEL_5,
+      ChannelNum::Channel6 => ledc_channel_t::LEDC_CHANNEL_6,
+      ChannelNum::Channel7 => ledc_channel_t::LEDC_CHANNEL_7,
+    }
+  }
+}
+
+fn invalid_arg() -> EspError {
+  EspErrorKind::Generic(GenericErrorKind::InvalidArg).into()
+}
+
+static FADE_SERVICE_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Install the fade service, which is needed for hardware fades and makes duty updates thread-safe.
+fn install_fade_service() -> Result<(), EspError> {
+  sync::install_once(&FADE_SERVICE_INSTALLED, || esp_ok!(ledc_fade_func_install(0)))
+}
+
+/// Configuration for a [`Timer`](struct.Timer.html).
+#[derive(Debug, Clone)]
+pub struct TimerConfig {
+  speed_mode: SpeedMode,
+  frequency: u32,
+  resolution: Option<u8>,
+}
+
+impl Default for TimerConfig {
+  fn default() -> Self {
+    Self { speed_mode: SpeedMode::HighSpeed, frequency: 1_000, resolution: None }
+  }
+}
+
+impl TimerConfig {
+  /// 1 kHz in high speed mode, with the highest possible resolution.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn speed_mode(mut self, speed_mode: SpeedMode) -> Self {
+    self.speed_mode = speed_mode;
+    self
+  }
+
+  /// PWM frequency in Hz.
+  pub fn frequency(mut self, frequency: u32) -> Self {
+    self.frequency = frequency;
+    self
+  }
+
+  /// Duty resolution in bits, between 1 and 20.
+  ///
+  /// By default, the highest resolution possible at the configured frequency is used.
+  pub fn resolution(mut self, bits: u8) -> Self {
+    self.resolution = Some(bits);
+    self
+  }
+
+  fn resolution_bits(&self) -> Result<u8, EspError> {
+    if self.frequency == 0 {
+      return Err(invalid_arg())
+    }
+
+    match self.resolution {
+      Some(bits) if (1..=MAX_RESOLUTION).contains(&bits) => Ok(bits),
+      Some(_) => Err(invalid_arg()),
+      // The timer counts to `2^bits` once per period, so the clock must be at least `frequency * 2^bits`.
+      None => match APB_CLK_FREQ / self.frequency {
+        0 | 1 => Err(invalid_arg()),
+        divider => Ok(((31 - divider.leading_zeros()) as u8).min(MAX_RESOLUTION)),
+      },
+    }
+  }
+}
+
+#[derive(Debug)]
+struct TimerInner {
+  speed_mode: SpeedMode,
+  timer: TimerNum,
+  max_duty: u32,
+}
+
+impl Drop for TimerInner {
+  fn drop(&mut self) {
+    let _ = esp_ok!(ledc_timer_pause(self.speed_mode.into(), self.timer.into()));
+  }
+}
+
+/// A configured LEDC timer. The timer is paused once it and all of its channels are dropped.
+#[derive(Debug, Clone)]
+pub struct Timer {
+  inner: Arc<TimerInner>,
+}
+
+impl Timer {
+  pub fn new(timer: TimerNum, config: TimerConfig) -> Result<Self, EspError> {
+    let bits = config.resolution_bits()?;
+
+    let mut timer_config: ledc_timer_config_t = unsafe { crate::zeroed_config() };
+    timer_config.speed_mode = config.speed_mode.into();
+    // SAFETY: `ledc_timer_bit_t` has a variant for each resolution between 1 and 20 bits.
+    timer_config.__bindgen_anon_1.duty_resolution = unsafe { mem::transmute::<u32, ledc_timer_bit_t>(u32::from(bits)) };
+    timer_config.timer_num = timer.into();
+    timer_config.freq_hz = config.frequency;
+    timer_config.clk_cfg = ledc_clk_cfg_t::LEDC_AUTO_CLK;
+
+    esp_ok!(ledc_timer_config(&timer_config))?;
+
+    Ok(Self { inner: Arc::new(TimerInner { speed_mode: config.speed_mode, timer, max_duty: 1 << bits }) })
+  }
+
+  pub fn speed_mode(&self) -> SpeedMode {
+    self.inner.speed_mode
+  }
+
+  /// Current frequency in Hz.
+  pub fn frequency(&self) -> u32 {
+    unsafe { ledc_get_freq(self.inner.speed_mode.into(), self.inner.timer.into()) }
+  }
+
+  /// Change the frequency, keeping the resolution. Fails if the resolution is too high for `frequency`.
+  pub fn set_frequency(&self, frequency: u32) -> Result<(), EspError> {
+    esp_ok!(ledc_set_freq(self.inner.speed_mode.into(), self.inner.timer.into(), frequency))
+  }
+
+  /// Duty cycle of 100 %.
+  pub fn max_duty(&self) -> u32 {
+    self.inner.max_duty
+  }
+
+  pub fn pause(&self) -> Result<(), EspError> {
+    esp_ok!(ledc_timer_pause(self.inner.speed_mode.into(), self.inner.timer.into()))
+  }
+
+  pub fn resume(&self) -> Result<(), EspError> {
+    esp_ok!(ledc_timer_resume(self.inner.speed_mode.into(), self.inner.timer.into()))
+  }
+
+  /// Configure `channel` to output PWM from this timer on `pin`, starting with a duty cycle of 0 %.
+  pub fn channel(&self, channel: ChannelNum, pin: impl IoPin) -> Result<Channel, EspError> {
+    install_fade_service()?;
+
+    let mut channel_config: ledc_channel_config_t = unsafe { crate::zeroed_config() };
+    channel_config.gpio_num = pin.number().into();
+    channel_config.speed_mode = self.inner.speed_mode.into();
+    channel_config.channel = channel.into();
+    channel_config.intr_type = ledc_intr_type_t::LEDC_INTR_DISABLE;
+    channel_config.timer_sel = self.inner.timer.into();
+    channel_config.duty = 0;
+    channel_config.hpoint = 0;
+
+    esp_ok!(ledc_channel_config(&channel_config))?;
+
+    Ok(Channel { timer: Arc::clone(&self.inner), channel, duty: 0, enabled: true })
+  }
+}
+
+/// A PWM output on a [`Timer`](struct.Timer.html). The output is stopped and driven low when dropped.
+#[derive(Debug)]
+pub struct Channel {
+  timer: Arc<TimerInner>,
+  channel: ChannelNum,
+  duty: u32,
+  enabled: bool,
+}
+
+impl Channel {
+  pub fn channel(&self) -> ChannelNum {
+    self.channel
+  }
+
+  /// Duty cycle of 100 %.
+  pub fn max_duty(&self) -> u32 {
+    self.timer.max_duty
+  }
+
+  /// The last duty cycle set, or the target of the current fade.
+  pub fn duty(&self) -> u32 {
+    self.duty
+  }
+
+  /// Set the duty cycle, clamped to [`max_duty`](#method.max_duty).
+  ///
+  /// Waits until a running fade is complete.
+  pub fn set_duty(&mut self, duty: u32) -> Result<(), EspError> {
+    self.duty = duty.min(self.max_duty());
+    self.enabled = true;
+    esp_ok!(ledc_set_duty_and_update(self.timer.speed_mode.into(), self.channel.into(), self.duty, 0))
+  }
+
+  /// Fade linearly from the current to the given duty cycle over `duration`, without waiting for the fade to complete.
+  ///
+  /// Waits until a running fade is complete.
+  pub fn fade_to(&mut self, duty: u32, duration: Duration) -> Result<(), EspError> {
+    let millis = duration.as_millis().min(i32::max_value() as u128) as libc::c_int;
+
+    if millis == 0 {
+      return self.set_duty(duty)
+    }
+
+    self.duty = duty.min(self.max_duty());
+    self.enabled = true;
+
+    let (speed_mode, channel) = (self.timer.speed_mode.into(), self.channel.into());
+    esp_ok!(ledc_set_fade_with_time(speed_mode, channel, self.duty, millis))?;
+    esp_ok!(ledc_fade_start(speed_mode, channel, ledc_fade_mode_t::LEDC_FADE_NO_WAIT))
+  }
+
+  /// Stop the output and drive it to `idle_level`, see [`enable`](#method.enable).
+  pub fn disable(&mut self, idle_level: bool) -> Result<(), EspError> {
+    self.enabled = false;
+    esp_ok!(ledc_stop(self.timer.speed_mode.into(), self.channel.into(), idle_level as u32))
+  }
+
+  /// Restart the output with the last duty cycle.
+  pub fn enable(&mut self) -> Result<(), EspError> {
+    self.set_duty(self.duty)
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.enabled
+  }
+}
+
+impl Drop for Channel {
+  fn drop(&mut self) {
+    let _ = self.disable(false);
+  }
+}
+
+/// Errors cannot be reported by these methods and are ignored.
+impl PwmPin for Channel {
+  type Duty = u32;
+
+  fn disable(&mut self) {
+    let _ = Channel::disable(self, false);
+  }
+
+  fn enable(&mut self) {
+    let _ = Channel::enable(self);
+  }
+
+  fn get_duty(&self) -> Self::Duty {
+    self.duty()
+  }
+
+  fn get_max_duty(&self) -> Self::Duty {
+    self.max_duty()
+  }
+
+  fn set_duty(&mut self, duty: Self::Duty) {
+    let _ = Channel::set_duty(self, duty);
+  }
+}
@@ -0,0 +1,174 @@
+use core::fmt;
+use core::time::Duration;
+use std::sync::{Arc, Mutex, TryLockError};
+
+use crate::EspError;
+use crate::timer::Timer;
+
+use super::Channel;
+
+/// Resolution of pattern timings.
+const TICK: Duration = Duration::from_millis(50);
+
+/// A status shown by a [`StatusLed`](struct.StatusLed.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+  Off,
+  On,
+  /// Slow breathing, e.g. while an access point for provisioning is running.
+  Provisioning,
+  /// Fast blinking.
+  Connecting,
+  /// A short flash every three seconds.
+  Connected,
+  /// Double flashes.
+  Error,
+}
+
+/// A step of a pattern: fade to `brightness` in percent over `fade_ticks`, then hold it for `hold_ticks`.
+///
+/// Steps with a fade must hold for at least one tick, since a hardware fade may take slightly
+/// longer than its ticks, and starting the next step would then wait for it on the timer task.
+#[derive(Debug, Clone, Copy)]
+struct Step {
+  brightness: u8,
+  fade_ticks: u32,
+  hold_ticks: u32,
+}
+
+const fn step(brightness: u8, fade_ticks: u32, hold_ticks: u32) -> Step {
+  Step { brightness, fade_ticks, hold_ticks }
+}
+
+const OFF: &[Step] = &[step(0, 0, u32::max_value())];
+const ON: &[Step] = &[step(100, 0, u32::max_value())];
+const PROVISIONING: &[Step] = &[step(100, 20, 1), step(0, 20, 4)];
+const CONNECTING: &[Step] = &[step(100, 0, 2), step(0, 0, 2)];
+const CONNECTED: &[Step] = &[step(100, 0, 2), step(0, 0, 60)];
+const ERROR: &[Step] = &[step(100, 0, 2), step(0, 0, 2), step(100, 0, 2), step(0, 0, 20)];
+
+impl Pattern {
+  /// The steps of the pattern, which are repeated.
+  fn steps(self) -> &'static [Step] {
+    match self {
+      Self::Off => OFF,
+      Self::On => ON,
+      Self::Provisioning => PROVISIONING,
+      Self::Connecting => CONNECTING,
+      Self::Connected => CONNECTED,
+      Self::Error => ERROR,
+    }
+  }
+}
+
+#[derive(Debug)]
+struct State {
+  channel: Channel,
+  active_low: bool,
+  pattern: Pattern,
+  next_step: usize,
+  remaining_ticks: u32,
+  fade_ticks: u32,
+}
+
+impl State {
+  fn start(&mut self, pattern: Pattern) {
+    self.pattern = pattern;
+    self.next_step = 0;
+
+    if self.fade_ticks == 0 {
+      self.apply();
+    } else {
+      // A running fade cannot be stopped, so the first step is applied by the timer once it is complete.
+      self.remaining_ticks = self.fade_ticks + 1;
+    }
+  }
+
+  fn apply(&mut self) {
+    let steps = self.pattern.steps();
+    let step = steps[self.next_step];
+    self.next_step = (self.next_step + 1) % steps.len();
+
+    let max_duty = self.channel.max_duty();
+    let mut duty = (u64::from(max_duty) * u64::from(step.brightness) / 100) as u32;
+    if self.active_low {
+      duty = max_duty - duty;
+    }
+
+    // Errors cannot be handled on the timer task, the next step is tried regardless.
+    let _ = self.channel.fade_to(duty, TICK * step.fade_ticks);
+    self.remaining_ticks = step.fade_ticks.saturating_add(step.hold_ticks);
+    self.fade_ticks = step.fade_ticks;
+  }
+
+  fn tick(&mut self) {
+    self.remaining_ticks = self.remaining_ticks.saturating_sub(1);
+    self.fade_ticks = self.fade_ticks.saturating_sub(1);
+
+    if self.remaining_ticks == 0 {
+      self.apply();
+    }
+  }
+}
+
+/// An LED on a PWM [`Channel`](struct.Channel.html) which plays a [`Pattern`](enum.Pattern.html).
+///
+/// Patterns are played using an `esp_timer` and hardware fades, without a dedicated task.
+/// The LED can be shared between tasks, e.g. to show the WiFi state:
+///
+/// ```ignore
+/// let led = Arc::new(StatusLed::new(channel, true)?);
+///
+/// led.set_pattern(Pattern::Connecting);
+/// match wifi.connect_sta(config).await {
+///   Ok(_) => led.set_pattern(Pattern::Connected),
+///   Err(_) => led.set_pattern(Pattern::Error),
+/// }
+/// ```
+pub struct StatusLed {
+  state: Arc<Mutex<State>>,
+  _timer: Timer,
+}
+
+impl StatusLed {
+  /// Create a status LED which is initially off. If `active_low` is set, the LED is lit when the output is low.
+  pub fn new(channel: Channel, active_low: bool) -> Result<Self, EspError> {
+    let mut state = State { channel, active_low, pattern: Pattern::Off, next_step: 0, remaining_ticks: 0, fade_ticks: 0 };
+    state.start(Pattern::Off);
+
+    let state = Arc::new(Mutex::new(state));
+
+    let timer = {
+      let state = Arc::clone(&state);
+      // The tick is skipped while the pattern is being changed, so other timers are not delayed.
+      Timer::new(move || match state.try_lock() {
+        Ok(mut state) => state.tick(),
+        Err(TryLockError::Poisoned(err)) => err.into_inner().tick(),
+        Err(TryLockError::WouldBlock) => (),
+      })?
+    };
+    timer.start_periodic(TICK)?;
+
+    Ok(Self { state, _timer: timer })
+  }
+
+  /// The pattern which is currently playing.
+  pub fn pattern(&self) -> Pattern {
+    self.state.lock().unwrap_or_else(|err| err.into_inner()).pattern
+  }
+
+  /// Play `pattern` from the start, unless it is already playing.
+  pub fn set_pattern(&self, pattern: Pattern) {
+    let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+
+    if state.pattern != pattern {
+      state.start(pattern);
+    }
+  }
+}
+
+impl fmt::Debug for StatusLed {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("StatusLed").field("pattern", &self.pattern()).finish()
+  }
+}
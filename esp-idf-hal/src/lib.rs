@@ -1,5 +1,6 @@
 #![feature(never_type)]
 #![feature(const_cstr_unchecked)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api, nonnull_slice_from_raw_parts))]
 #![warn(missing_debug_implementations)]
 
 use std::ffi::CStr;
@@ -10,10 +11,48 @@ extern crate alloc;
 
 #[macro_use]
 mod esp_error;
-pub use esp_error::EspError;
+pub use esp_error::*;
 
 pub mod interface;
-mod heap;
-pub use heap::Heap;
+pub mod heap;
+pub use heap::{Heap, HeapCaps, HeapStats};
 pub mod wifi;
 pub mod nvs;
+pub mod executor;
+#[cfg(target_device = "esp32")]
+pub mod adc;
+pub mod gpio;
+pub mod i2c;
+#[cfg(target_device = "esp32")]
+pub mod ledc;
+#[cfg(target_device = "esp32")]
+pub mod pcnt;
+#[cfg(target_device = "esp32")]
+pub mod rmt;
+#[cfg(target_device = "esp32")]
+pub mod rtc;
+#[cfg(target_device = "esp32")]
+pub mod sleep;
+pub mod sync;
+pub mod task;
+pub mod timer;
+#[cfg(target_device = "esp32")]
+pub mod touch;
+pub mod uart;
+#[cfg(target_device = "esp32")]
+pub mod spi;
+#[cfg(target_device = "esp32")]
+pub mod watchdog;
+
+/// Create an SDK configuration struct with every field set to zero.
+///
+/// Configuration structs such as `uart_config_t` gain fields between SDK versions. All of them
+/// default to zero, so starting from a zeroed struct and setting only the known fields works
+/// with every supported SDK version.
+///
+/// # Safety
+///
+/// All-zero bytes must be a valid value of `T`, which holds for plain C structs generated by `bindgen`.
+pub(crate) unsafe fn zeroed_config<T>() -> T {
+  core::mem::zeroed()
+}
@@ -0,0 +1,122 @@
+use std::ffi::CString;
+use std::fmt;
+
+use crate::{EspError, EspErrorKind, NvsErrorKind};
+
+/// Maximum length of keys and namespace names, excluding the `NUL` terminator.
+pub const MAX_KEY_LEN: usize = 15;
+
+/// The error type for non-volatile storage operations.
+#[derive(Debug, Clone)]
+pub enum NvsError {
+  /// The key or namespace name is longer than [`MAX_KEY_LEN`](constant.MAX_KEY_LEN.html).
+  KeyTooLong(usize),
+  /// The key or namespace name is empty or contains interior `NUL`-bytes.
+  InvalidName,
+  /// No value is stored for the key.
+  NotFound,
+  /// The stored value does not have the requested type.
+  TypeMismatch,
+  /// There is not enough space left to store the value.
+  NoSpace,
+  /// The partition has no free pages left.
+  PartitionFull,
+  /// Any other error.
+  Esp(EspError),
+}
+
+impl From<EspError> for NvsError {
+  fn from(esp_error: EspError) -> Self {
+    match esp_error.kind() {
+      EspErrorKind::Nvs(NvsErrorKind::NotFound) => Self::NotFound,
+      EspErrorKind::Nvs(NvsErrorKind::TypeMismatch) => Self::TypeMismatch,
+      EspErrorKind::Nvs(NvsErrorKind::NotEnoughSpace) => Self::NoSpace,
+      EspErrorKind::Nvs(NvsErrorKind::NoFreePages) => Self::PartitionFull,
+      EspErrorKind::Nvs(NvsErrorKind::InvalidName) => Self::InvalidName,
+      _ => Self::Esp(esp_error),
+    }
+  }
+}
+
+impl From<NvsError> for EspError {
+  fn from(nvs_error: NvsError) -> Self {
+    let kind = match nvs_error {
+      NvsError::KeyTooLong(..) | NvsError::InvalidName => NvsErrorKind::InvalidName,
+      NvsError::NotFound => NvsErrorKind::NotFound,
+      NvsError::TypeMismatch => NvsErrorKind::TypeMismatch,
+      NvsError::NoSpace => NvsErrorKind::NotEnoughSpace,
+      NvsError::PartitionFull => NvsErrorKind::NoFreePages,
+      NvsError::Esp(esp_error) => return esp_error,
+    };
+
+    EspErrorKind::Nvs(kind).into()
+  }
+}
+
+impl fmt::Display for NvsError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::KeyTooLong(len) => write!(f, "key is {} characters long, maximum is {}", len, MAX_KEY_LEN),
+      Self::InvalidName => "key is empty or contains a NUL-byte".fmt(f),
+      Self::NotFound => "key not found".fmt(f),
+      Self::TypeMismatch => "stored value has a different type".fmt(f),
+      Self::NoSpace => "not enough space".fmt(f),
+      Self::PartitionFull => "partition is full".fmt(f),
+      Self::Esp(esp_error) => esp_error.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for NvsError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Esp(esp_error) => Some(esp_error),
+      _ => None,
+    }
+  }
+}
+
+/// Check whether `key` is a valid key or namespace name.
+#[doc(hidden)]
+pub const fn is_valid_key(key: &str) -> bool {
+  let bytes = key.as_bytes();
+
+  if bytes.is_empty() || bytes.len() > MAX_KEY_LEN {
+    return false
+  }
+
+  let mut i = 0;
+  while i < bytes.len() {
+    if bytes[i] == 0 {
+      return false
+    }
+
+    i += 1;
+  }
+
+  true
+}
+
+/// A key or namespace name checked at compile time, e.g. `nvs_key!("ssid")`.
+///
+/// Keys longer than [`MAX_KEY_LEN`](nvs/constant.MAX_KEY_LEN.html) or containing `NUL`-bytes fail to compile.
+#[macro_export]
+macro_rules! nvs_key {
+  ($key:literal) => {{
+    const KEY: &str = $key;
+    const _: [(); 0 - !$crate::nvs::is_valid_key(KEY) as usize] = [];
+    KEY
+  }};
+}
+
+pub(crate) fn c_key(key: &str) -> Result<CString, NvsError> {
+  if key.len() > MAX_KEY_LEN {
+    return Err(NvsError::KeyTooLong(key.len()))
+  }
+
+  if key.is_empty() {
+    return Err(NvsError::InvalidName)
+  }
+
+  CString::new(key).map_err(|_| NvsError::InvalidName)
+}
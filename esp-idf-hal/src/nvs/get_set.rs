@@ -1,7 +1,11 @@
+use core::num::{NonZeroU8, NonZeroU16};
 use std::ffi::CStr;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use macaddr::MacAddr6;
 
 use esp_idf_bindgen::{
-  esp_err_t,
   nvs_get_i8,
   nvs_set_i8,
   nvs_get_u8,
@@ -22,23 +26,26 @@ use esp_idf_bindgen::{
   nvs_set_blob,
   nvs_get_str,
   nvs_set_str,
-  ESP_ERR_NVS_NOT_FOUND,
+  wifi_scan_method_t,
+  wifi_sort_method_t,
 };
 
+use crate::wifi::{AuthMode, Cipher, Ssid, Password, ScanMethod, ScanThreshold, SortMethod, StaConfig, ApConfig};
+
 use super::*;
 
 /// Trait for retrieving data from non-volatile storage.
 pub trait NvsGet: Sized {
-  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, EspError>;
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError>;
 }
 
 /// Trait for saving data in non-volatile storage.
 pub trait NvsSet {
-  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), EspError>;
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError>;
 }
 
 impl<T> NvsSet for &T where T: NvsSet {
-  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), EspError> {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
     (*self).nvs_set(namespace, key)
   }
 }
@@ -46,13 +53,13 @@ impl<T> NvsSet for &T where T: NvsSet {
 macro_rules! nvs_int {
   ($ty:ty as $as_ty:ty, $set_function:ident, $get_function:ident) => {
     impl NvsSet for $ty {
-      fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), EspError> {
-        esp_ok!($set_function(namespace.handle, key.as_ptr(), *self as $as_ty))
+      fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+        Ok(esp_ok!($set_function(namespace.handle, key.as_ptr(), *self as $as_ty))?)
       }
     }
 
     impl NvsGet for $ty {
-      fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, EspError> {
+      fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
         let mut out_value = <$ty>::default();
         esp_ok!($get_function(namespace.handle, key.as_ptr(), &mut out_value as *mut $ty as *mut $as_ty))?;
         Ok(out_value)
@@ -76,43 +83,46 @@ nvs_int!(u32, nvs_set_u32, nvs_get_u32);
 nvs_int!(u64, nvs_set_u64, nvs_get_u64);
 
 impl NvsSet for &CStr {
-  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), EspError> {
-    esp_ok!(nvs_set_str(namespace.handle, key.as_ptr(), self.as_ptr()))
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    Ok(esp_ok!(nvs_set_str(namespace.handle, key.as_ptr(), self.as_ptr()))?)
   }
 }
 
 impl NvsSet for CString {
-  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), EspError> {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
     self.as_c_str().nvs_set(namespace, key)
   }
 }
 
 impl NvsGet for CString {
-  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, EspError> {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
     let mut len = 0;
     esp_ok!(nvs_get_str(namespace.handle, key.as_ptr(), ptr::null_mut(), &mut len))?;
 
     let mut buffer = vec![0u8; len as usize];
     esp_ok!(nvs_get_str(namespace.handle, key.as_ptr(), buffer.as_mut_ptr() as *mut _, &mut len))?;
 
+    // `len` includes the `NUL` terminator, which `from_vec_unchecked` appends again.
+    buffer.truncate((len as usize).saturating_sub(1));
+
     Ok(unsafe { CString::from_vec_unchecked(buffer) })
   }
 }
 
 impl NvsSet for &[u8] {
-  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), EspError> {
-    esp_ok!(nvs_set_blob(namespace.handle, key.as_ptr(), self.as_ptr() as *const _, self.len() as u32))
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    Ok(esp_ok!(nvs_set_blob(namespace.handle, key.as_ptr(), self.as_ptr() as *const _, self.len() as u32))?)
   }
 }
 
 impl NvsSet for Vec<u8> {
-  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), EspError> {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
     self.as_slice().nvs_set(namespace, key)
   }
 }
 
 impl NvsGet for Vec<u8> {
-  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, EspError> {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
     let mut len = 0;
     esp_ok!(nvs_get_blob(namespace.handle, key.as_ptr(), ptr::null_mut(), &mut len))?;
 
@@ -123,21 +133,387 @@ impl NvsGet for Vec<u8> {
 }
 
 impl NvsSet for &str {
-  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), EspError> {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
     self.as_bytes().nvs_set(namespace, key)
   }
 }
 
 impl NvsSet for String {
-  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), EspError> {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
     self.as_str().nvs_set(namespace, key)
   }
 }
 
 
 impl NvsGet for String {
-  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, EspError> {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
     let buffer = Vec::<u8>::nvs_get(namespace, key)?;
-    String::from_utf8(buffer).map_err(|_| EspError { code: ESP_ERR_NVS_NOT_FOUND as esp_err_t })
+    String::from_utf8(buffer).map_err(|_| NvsError::TypeMismatch)
+  }
+}
+
+/// Stored as `u32` using [`f32::to_bits`](https://doc.rust-lang.org/std/primitive.f32.html#method.to_bits).
+impl NvsSet for f32 {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    self.to_bits().nvs_set(namespace, key)
+  }
+}
+
+impl NvsGet for f32 {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    u32::nvs_get(namespace, key).map(f32::from_bits)
+  }
+}
+
+/// Stored as `u64` using [`f64::to_bits`](https://doc.rust-lang.org/std/primitive.f64.html#method.to_bits).
+impl NvsSet for f64 {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    self.to_bits().nvs_set(namespace, key)
+  }
+}
+
+impl NvsGet for f64 {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    u64::nvs_get(namespace, key).map(f64::from_bits)
+  }
+}
+
+impl<const N: usize> NvsSet for [u8; N] {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    (&self[..]).nvs_set(namespace, key)
+  }
+}
+
+impl<const N: usize> NvsGet for [u8; N] {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    let mut len = 0;
+    esp_ok!(nvs_get_blob(namespace.handle, key.as_ptr(), ptr::null_mut(), &mut len))?;
+
+    if len as usize != N {
+      return Err(NvsError::TypeMismatch)
+    }
+
+    let mut buffer = [0u8; N];
+    esp_ok!(nvs_get_blob(namespace.handle, key.as_ptr(), buffer.as_mut_ptr() as *mut _, &mut len))?;
+    Ok(buffer)
+  }
+}
+
+/// `None` removes the key.
+impl<T: NvsSet> NvsSet for Option<T> {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    match self {
+      Some(value) => value.nvs_set(namespace, key),
+      None => match namespace.remove_cstr(key) {
+        Err(NvsError::NotFound) => Ok(()),
+        res => res,
+      },
+    }
+  }
+}
+
+/// Returns `None` if the key does not exist.
+impl<T: NvsGet> NvsGet for Option<T> {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    match T::nvs_get(namespace, key) {
+      Ok(value) => Ok(Some(value)),
+      Err(NvsError::NotFound) => Ok(None),
+      Err(err) => Err(err),
+    }
+  }
+}
+
+/// Stored as `u32` in host byte order.
+impl NvsSet for Ipv4Addr {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    u32::from(*self).nvs_set(namespace, key)
+  }
+}
+
+impl NvsGet for Ipv4Addr {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    u32::nvs_get(namespace, key).map(Ipv4Addr::from)
+  }
+}
+
+/// Stored as a 6 byte blob.
+impl NvsSet for MacAddr6 {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    self.as_bytes().nvs_set(namespace, key)
+  }
+}
+
+impl NvsGet for MacAddr6 {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    <[u8; 6]>::nvs_get(namespace, key).map(MacAddr6::from)
+  }
+}
+
+/// Stored as `u64` nanoseconds, so durations above roughly 584 years cannot be stored.
+impl NvsSet for Duration {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    let nanos = self.as_nanos();
+
+    if nanos > u128::from(u64::max_value()) {
+      return Err(NvsError::Esp(EspErrorKind::Generic(GenericErrorKind::InvalidArg).into()))
+    }
+
+    (nanos as u64).nvs_set(namespace, key)
+  }
+}
+
+impl NvsGet for Duration {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    u64::nvs_get(namespace, key).map(Duration::from_nanos)
+  }
+}
+
+/// Stored as a string.
+impl NvsSet for Ssid {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    // SAFETY: An `Ssid` never contains `NUL` bytes.
+    unsafe { CString::from_vec_unchecked(self.as_str().into()) }.nvs_set(namespace, key)
+  }
+}
+
+impl NvsGet for Ssid {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    let s = CString::nvs_get(namespace, key)?;
+    Ssid::from_bytes(s.as_bytes()).map_err(|_| NvsError::TypeMismatch)
+  }
+}
+
+/// Stored as a string.
+impl NvsSet for Password {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    // SAFETY: A `Password` never contains `NUL` bytes.
+    unsafe { CString::from_vec_unchecked(self.as_str().into()) }.nvs_set(namespace, key)
+  }
+}
+
+impl NvsGet for Password {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    let s = CString::nvs_get(namespace, key)?;
+    Password::from_bytes(s.as_bytes()).map_err(|_| NvsError::TypeMismatch)
+  }
+}
+
+/// Stable codes for enums stored as part of a WiFi configuration.
+trait NvsCode: Sized {
+  fn nvs_code(self) -> u8;
+  fn from_nvs_code(code: u8) -> Result<Self, NvsError>;
+}
+
+macro_rules! nvs_code {
+  ($ty:ident { $($(#[$attr:meta])* $variant:ident = $code:literal,)* }) => {
+    impl NvsCode for $ty {
+      fn nvs_code(self) -> u8 {
+        match self {
+          $($(#[$attr])* $ty::$variant => $code,)*
+        }
+      }
+
+      fn from_nvs_code(code: u8) -> Result<Self, NvsError> {
+        match code {
+          $($(#[$attr])* $code => Ok($ty::$variant),)*
+          _ => Err(NvsError::TypeMismatch),
+        }
+      }
+    }
+  };
+}
+
+nvs_code!(AuthMode {
+  Open = 0,
+  Wep = 1,
+  WpaPsk = 2,
+  WpaWpa2Psk = 3,
+  Wpa2Psk = 4,
+  #[cfg(target_device = "esp32")]
+  Wpa2Wpa3Psk = 5,
+  #[cfg(target_device = "esp32")]
+  Wpa3Psk = 6,
+  Wpa2Enterprise = 7,
+  WapiPsk = 8,
+});
+
+nvs_code!(Cipher {
+  None = 0,
+  Wep40 = 1,
+  Wep104 = 2,
+  Tkip = 3,
+  Ccmp = 4,
+  TkipCcmp = 5,
+  AesCmac128 = 6,
+  Sms4 = 7,
+  Unknown = 8,
+});
+
+nvs_code!(ScanMethod {
+  Fast = 0,
+  Full = 1,
+});
+
+nvs_code!(SortMethod {
+  BySignal = 0,
+  BySecurity = 1,
+});
+
+/// First byte of a stored [`StaConfig`](../wifi/struct.StaConfig.html).
+const STA_CONFIG_TAG: u8 = b'S';
+/// First byte of a stored [`ApConfig`](../wifi/struct.ApConfig.html).
+const AP_CONFIG_TAG: u8 = b'A';
+
+fn push_str(bytes: &mut Vec<u8>, s: &str) {
+  bytes.push(s.len() as u8);
+  bytes.extend_from_slice(s.as_bytes());
+}
+
+/// Reads the fields of a stored WiFi configuration, failing with `TypeMismatch` if the blob is malformed.
+struct FieldReader<'b>(&'b [u8]);
+
+impl<'b> FieldReader<'b> {
+  fn new(bytes: &'b [u8], tag: u8) -> Result<Self, NvsError> {
+    let mut reader = Self(bytes);
+
+    if reader.u8()? != tag {
+      return Err(NvsError::TypeMismatch)
+    }
+
+    Ok(reader)
+  }
+
+  fn bytes(&mut self, len: usize) -> Result<&'b [u8], NvsError> {
+    if self.0.len() < len {
+      return Err(NvsError::TypeMismatch)
+    }
+
+    let (bytes, rest) = self.0.split_at(len);
+    self.0 = rest;
+    Ok(bytes)
+  }
+
+  fn u8(&mut self) -> Result<u8, NvsError> {
+    self.bytes(1).map(|bytes| bytes[0])
+  }
+
+  fn u16(&mut self) -> Result<u16, NvsError> {
+    self.bytes(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+  }
+
+  fn code<T: NvsCode>(&mut self) -> Result<T, NvsError> {
+    T::from_nvs_code(self.u8()?)
+  }
+
+  fn ssid(&mut self) -> Result<Ssid, NvsError> {
+    let len = self.u8()?;
+    Ssid::from_bytes(self.bytes(len.into())?).map_err(|_| NvsError::TypeMismatch)
+  }
+
+  fn password(&mut self) -> Result<Password, NvsError> {
+    let len = self.u8()?;
+    Password::from_bytes(self.bytes(len.into())?).map_err(|_| NvsError::TypeMismatch)
+  }
+
+  fn finish(self) -> Result<(), NvsError> {
+    if !self.0.is_empty() {
+      return Err(NvsError::TypeMismatch)
+    }
+
+    Ok(())
+  }
+}
+
+/// Stored as a blob containing the SSID, password and connection options, tagged so it
+/// cannot be read as an [`ApConfig`](../wifi/struct.ApConfig.html).
+impl NvsSet for StaConfig {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    // SAFETY: A `StaConfig` always contains a `wifi_sta_config_t`.
+    let sta = unsafe { &self.0.sta };
+
+    let scan_method = match sta.scan_method {
+      wifi_scan_method_t::WIFI_FAST_SCAN => ScanMethod::Fast,
+      _ => ScanMethod::Full,
+    };
+
+    let sort_method = match sta.sort_method {
+      wifi_sort_method_t::WIFI_CONNECT_AP_BY_SIGNAL => SortMethod::BySignal,
+      _ => SortMethod::BySecurity,
+    };
+
+    let mut bytes = vec![STA_CONFIG_TAG];
+    push_str(&mut bytes, self.ssid().as_str());
+    push_str(&mut bytes, self.password().as_str());
+    bytes.push(scan_method.nvs_code());
+    bytes.push(sta.bssid_set as u8);
+    bytes.extend_from_slice(&sta.bssid);
+    bytes.push(sta.channel);
+    bytes.extend_from_slice(&sta.listen_interval.to_le_bytes());
+    bytes.push(sort_method.nvs_code());
+    bytes.push(sta.threshold.rssi as u8);
+    bytes.push(AuthMode::from(sta.threshold.authmode).nvs_code());
+
+    bytes.nvs_set(namespace, key)
+  }
+}
+
+impl NvsGet for StaConfig {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    let bytes = Vec::<u8>::nvs_get(namespace, key)?;
+    let mut reader = FieldReader::new(&bytes, STA_CONFIG_TAG)?;
+
+    let mut builder = StaConfig::builder();
+    builder.ssid = Some(reader.ssid()?);
+    builder.password = reader.password()?;
+    builder.scan_method = reader.code()?;
+    let bssid_set = reader.u8()? != 0;
+    let bssid = reader.bytes(6)?;
+    builder.bssid = if bssid_set { Some([bssid[0], bssid[1], bssid[2], bssid[3], bssid[4], bssid[5]]) } else { None };
+    builder.channel = NonZeroU8::new(reader.u8()?);
+    builder.listen_interval = NonZeroU16::new(reader.u16()?);
+    builder.sort_method = reader.code()?;
+    let rssi = reader.u8()? as i8;
+    builder.threshold = Some(ScanThreshold { rssi, auth_mode: reader.code()? });
+    reader.finish()?;
+
+    Ok(builder.build())
+  }
+}
+
+/// Stored as a blob containing the SSID, password and access point options, tagged so it
+/// cannot be read as a [`StaConfig`](../wifi/struct.StaConfig.html).
+impl NvsSet for ApConfig {
+  fn nvs_set(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    let mut bytes = vec![AP_CONFIG_TAG];
+    push_str(&mut bytes, self.ssid().as_str());
+    push_str(&mut bytes, self.password().as_str());
+    bytes.push(self.channel().map_or(0, NonZeroU8::get));
+    bytes.push(self.auth_mode().nvs_code());
+    bytes.push(self.max_connection().map_or(0, NonZeroU8::get));
+    bytes.push(self.ssid_hidden() as u8);
+    bytes.extend_from_slice(&self.beacon_interval().map_or(0, NonZeroU16::get).to_le_bytes());
+    bytes.push(self.pairwise_cipher().nvs_code());
+
+    bytes.nvs_set(namespace, key)
+  }
+}
+
+impl NvsGet for ApConfig {
+  fn nvs_get(namespace: &NameSpace, key: &CStr) -> Result<Self, NvsError> {
+    let bytes = Vec::<u8>::nvs_get(namespace, key)?;
+    let mut reader = FieldReader::new(&bytes, AP_CONFIG_TAG)?;
+
+    let mut builder = ApConfig::builder();
+    builder.ssid = Some(reader.ssid()?);
+    builder.password = reader.password()?;
+    builder.channel = NonZeroU8::new(reader.u8()?);
+    builder.auth_mode = reader.code()?;
+    builder.max_connection = NonZeroU8::new(reader.u8()?);
+    builder.ssid_hidden = reader.u8()? != 0;
+    builder.beacon_interval = NonZeroU16::new(reader.u16()?);
+    builder.pairwise_cipher = reader.code()?;
+    reader.finish()?;
+
+    Ok(builder.build())
   }
 }
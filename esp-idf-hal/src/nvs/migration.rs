@@ -0,0 +1,229 @@
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::mem;
+
+use super::*;
+
+/// Key under which the schema version of a namespace is stored.
+pub const SCHEMA_VERSION_KEY: &str = crate::nvs_key!("_schema_version");
+
+/// A single migration step, upgrading a namespace by one schema version.
+pub type Migration = dyn Fn(&mut Transaction<'_>) -> Result<(), NvsError>;
+
+/// The error type returned by [`NameSpace::migrate`](struct.NameSpace.html#method.migrate).
+#[derive(Debug, Clone)]
+pub enum MigrationError {
+  /// The stored schema version is newer than the latest known version,
+  /// e.g. after downgrading the firmware.
+  UnknownVersion { stored: u32, latest: u32 },
+  /// The migration to `version` failed and was rolled back.
+  Failed { version: u32, error: NvsError },
+  /// The migration to `version` failed and rolling it back failed as well,
+  /// leaving the namespace in an inconsistent state.
+  RollbackFailed { version: u32, error: NvsError, rollback_error: NvsError },
+  /// Reading or writing the schema version failed.
+  Internal(NvsError),
+}
+
+impl From<NvsError> for MigrationError {
+  fn from(nvs_error: NvsError) -> Self {
+    Self::Internal(nvs_error)
+  }
+}
+
+impl fmt::Display for MigrationError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::UnknownVersion { stored, latest } => write!(f, "stored schema version {} is newer than latest version {}", stored, latest),
+      Self::Failed { version, error } => write!(f, "migration to schema version {} failed: {}", version, error),
+      Self::RollbackFailed { version, error, rollback_error } => {
+        write!(f, "migration to schema version {} failed: {}, rollback failed: {}", version, error, rollback_error)
+      },
+      Self::Internal(nvs_error) => nvs_error.fmt(f),
+    }
+  }
+}
+
+/// A value of any type stored in non-volatile storage, used for restoring values on rollback.
+#[derive(Debug)]
+enum RawValue {
+  U8(u8),
+  I8(i8),
+  U16(u16),
+  I16(i16),
+  U32(u32),
+  I32(i32),
+  U64(u64),
+  I64(i64),
+  Str(CString),
+  Blob(Vec<u8>),
+}
+
+impl RawValue {
+  /// Read the value stored for `key`, trying all types since NVS only finds values by key and type.
+  fn read(namespace: &NameSpace, key: &CStr) -> Result<Option<Self>, NvsError> {
+    macro_rules! try_type {
+      ($ty:ty, $variant:ident) => {
+        match <$ty>::nvs_get(namespace, key) {
+          Ok(value) => return Ok(Some(Self::$variant(value))),
+          Err(NvsError::NotFound) => (),
+          Err(err) => return Err(err),
+        }
+      };
+    }
+
+    try_type!(u8, U8);
+    try_type!(i8, I8);
+    try_type!(u16, U16);
+    try_type!(i16, I16);
+    try_type!(u32, U32);
+    try_type!(i32, I32);
+    try_type!(u64, U64);
+    try_type!(i64, I64);
+    try_type!(CString, Str);
+    try_type!(Vec<u8>, Blob);
+
+    Ok(None)
+  }
+
+  fn write(&self, namespace: &mut NameSpace, key: &CStr) -> Result<(), NvsError> {
+    match self {
+      Self::U8(value) => value.nvs_set(namespace, key),
+      Self::I8(value) => value.nvs_set(namespace, key),
+      Self::U16(value) => value.nvs_set(namespace, key),
+      Self::I16(value) => value.nvs_set(namespace, key),
+      Self::U32(value) => value.nvs_set(namespace, key),
+      Self::I32(value) => value.nvs_set(namespace, key),
+      Self::U64(value) => value.nvs_set(namespace, key),
+      Self::I64(value) => value.nvs_set(namespace, key),
+      Self::Str(value) => value.nvs_set(namespace, key),
+      Self::Blob(value) => value.nvs_set(namespace, key),
+    }
+  }
+}
+
+/// A set of changes to a [`NameSpace`](struct.NameSpace.html) which is rolled back
+/// unless [`commit`](#method.commit) is called.
+///
+/// NVS itself has no transactions, so the previous value of every key is
+/// recorded before it is first changed and written back on rollback.
+#[derive(Debug)]
+pub struct Transaction<'n> {
+  namespace: &'n mut NameSpace,
+  journal: Vec<(CString, Option<RawValue>)>,
+  done: bool,
+}
+
+impl<'n> Transaction<'n> {
+  pub(crate) fn new(namespace: &'n mut NameSpace) -> Self {
+    Self { namespace, journal: Vec::new(), done: false }
+  }
+
+  fn record(&mut self, key: &CStr) -> Result<(), NvsError> {
+    if !self.journal.iter().any(|(k, _)| k.as_c_str() == key) {
+      let previous = RawValue::read(self.namespace, key)?;
+      self.journal.push((key.to_owned(), previous));
+    }
+
+    Ok(())
+  }
+
+  pub fn get<T: NvsGet>(&self, key: &str) -> Result<T, NvsError> {
+    self.namespace.get(key)
+  }
+
+  pub fn set<T: NvsSet>(&mut self, key: &str, value: T) -> Result<(), NvsError> {
+    self.record(&c_key(key)?)?;
+    self.namespace.set(key, value)
+  }
+
+  pub fn remove(&mut self, key: &str) -> Result<(), NvsError> {
+    self.record(&c_key(key)?)?;
+    self.namespace.remove(key)
+  }
+
+  /// Keep all changes made in this transaction.
+  pub fn commit(mut self) -> Result<(), NvsError> {
+    self.done = true;
+    self.namespace.commit()
+  }
+
+  /// Restore all keys changed in this transaction to their previous values.
+  pub fn rollback(mut self) -> Result<(), NvsError> {
+    self.rollback_inner()
+  }
+
+  fn rollback_inner(&mut self) -> Result<(), NvsError> {
+    self.done = true;
+
+    let mut res = Ok(());
+
+    for (key, previous) in mem::take(&mut self.journal).into_iter().rev() {
+      let restored = match self.namespace.remove_cstr(&key) {
+        Err(NvsError::NotFound) | Ok(()) => match previous {
+          Some(value) => value.write(self.namespace, &key),
+          None => Ok(()),
+        },
+        Err(err) => Err(err),
+      };
+
+      if let Err(err) = restored {
+        res = Err(err);
+      }
+    }
+
+    self.namespace.commit().and(res)
+  }
+}
+
+impl Drop for Transaction<'_> {
+  fn drop(&mut self) {
+    if !self.done {
+      let _ = self.rollback_inner();
+    }
+  }
+}
+
+impl NameSpace {
+  /// Start a [`Transaction`](struct.Transaction.html), which is rolled back unless it is committed.
+  pub fn transaction(&mut self) -> Transaction<'_> {
+    Transaction::new(self)
+  }
+
+  /// Stored schema version of this namespace, `0` if it was never migrated.
+  pub fn schema_version(&self) -> Result<u32, NvsError> {
+    match self.get::<u32>(SCHEMA_VERSION_KEY) {
+      Err(NvsError::NotFound) => Ok(0),
+      res => res,
+    }
+  }
+
+  /// Bring this namespace up to date by running all `migrations` after the stored schema version.
+  ///
+  /// The migration at index `i` upgrades from schema version `i` to `i + 1`. Each migration
+  /// runs in its own [`Transaction`](struct.Transaction.html) together with the update of
+  /// the stored version, so a failing migration is rolled back and leaves the namespace
+  /// at the version of the last successful one.
+  pub fn migrate(&mut self, migrations: &[&Migration]) -> Result<u32, MigrationError> {
+    let latest = migrations.len() as u32;
+    let stored = self.schema_version()?;
+
+    if stored > latest {
+      return Err(MigrationError::UnknownVersion { stored, latest })
+    }
+
+    for (version, migration) in (1..).zip(migrations).skip(stored as usize) {
+      let mut transaction = Transaction::new(self);
+
+      match migration(&mut transaction).and_then(|_| transaction.set(SCHEMA_VERSION_KEY, version)) {
+        Ok(()) => transaction.commit()?,
+        Err(error) => return Err(match transaction.rollback() {
+          Ok(()) => MigrationError::Failed { version, error },
+          Err(rollback_error) => MigrationError::RollbackFailed { version, error, rollback_error },
+        }),
+      }
+    }
+
+    Ok(latest)
+  }
+}
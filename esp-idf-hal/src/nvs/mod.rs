@@ -5,9 +5,6 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::ffi::CString;
 
 use esp_idf_bindgen::{
-  esp_err_t,
-  ESP_ERR_NVS_NO_FREE_PAGES,
-  ESP_ERR_NVS_NEW_VERSION_FOUND,
   nvs_open_mode_t,
   nvs_handle_t,
   nvs_flash_init_partition,
@@ -15,15 +12,25 @@ use esp_idf_bindgen::{
   nvs_flash_deinit_partition,
   nvs_open_from_partition,
   nvs_close,
+  nvs_commit,
+  nvs_erase_key,
   NVS_DEFAULT_PART_NAME,
-  ESP_ERR_NVS_INVALID_NAME,
 };
 
 use super::*;
 
+mod error;
+pub use error::*;
+
 mod get_set;
 pub use get_set::*;
 
+mod migration;
+pub use migration::*;
+
+mod settings;
+pub use settings::*;
+
 /// A non-volatile storage partition.
 #[derive(Debug)]
 pub struct NonVolatileStorage {
@@ -37,15 +44,30 @@ pub struct NameSpace {
 }
 
 impl NameSpace {
-  pub fn get<T: NvsGet>(&self, key: &str) -> Result<T, EspError> {
-    let key = CString::new(key).map_err(|_| EspError { code: ESP_ERR_NVS_INVALID_NAME as esp_err_t })?;
+  pub fn get<T: NvsGet>(&self, key: &str) -> Result<T, NvsError> {
+    let key = c_key(key)?;
     T::nvs_get(self, key.as_ref())
   }
 
-  pub fn set<T: NvsSet>(&mut self, key: &str, value: T) -> Result<(), EspError> {
-    let key = CString::new(key).map_err(|_| EspError { code: ESP_ERR_NVS_INVALID_NAME as esp_err_t })?;
+  pub fn set<T: NvsSet>(&mut self, key: &str, value: T) -> Result<(), NvsError> {
+    let key = c_key(key)?;
     value.nvs_set(self, key.as_ref())
   }
+
+  /// Remove the value stored for `key`.
+  pub fn remove(&mut self, key: &str) -> Result<(), NvsError> {
+    let key = c_key(key)?;
+    self.remove_cstr(&key)
+  }
+
+  pub(crate) fn remove_cstr(&mut self, key: &CStr) -> Result<(), NvsError> {
+    Ok(esp_ok!(nvs_erase_key(self.handle, key.as_ptr()))?)
+  }
+
+  /// Make sure all changes are written to flash.
+  pub fn commit(&mut self) -> Result<(), NvsError> {
+    Ok(esp_ok!(nvs_commit(self.handle))?)
+  }
 }
 
 impl Drop for NameSpace {
@@ -60,7 +82,7 @@ static DEFAULT_INSTANCES: AtomicUsize = AtomicUsize::new(0);
 impl NonVolatileStorage {
   /// Open a non-volatile storage partition.
   pub fn open(name: &str) -> Result<NonVolatileStorage, EspError> {
-    let partition_name = CString::new(name).map_err(|_| EspError { code: ESP_ERR_NVS_INVALID_NAME as esp_err_t })?;
+    let partition_name = CString::new(name).map_err(|_| EspError::from(EspErrorKind::Nvs(NvsErrorKind::InvalidName)))?;
     Self::open_cstring(partition_name)
   }
 
@@ -75,8 +97,8 @@ impl NonVolatileStorage {
   }
 
   /// Open a namespace on a non-volatile storage partition.
-  pub fn namespace(&mut self, name: &str) -> Result<NameSpace, EspError> {
-    let name = CString::new(name).map_err(|_| EspError { code: ESP_ERR_NVS_INVALID_NAME as esp_err_t })?;
+  pub fn namespace(&mut self, name: &str) -> Result<NameSpace, NvsError> {
+    let name = c_key(name)?;
 
     let mut handle = MaybeUninit::<nvs_handle_t>::uninit();
 
@@ -90,6 +112,15 @@ impl NonVolatileStorage {
     Ok(NameSpace { handle: unsafe { handle.assume_init() } })
   }
 
+  /// Open a namespace and bring it up to date using the given `migrations`.
+  ///
+  /// See [`NameSpace::migrate`](struct.NameSpace.html#method.migrate).
+  pub fn namespace_migrated(&mut self, name: &str, migrations: &[&Migration]) -> Result<NameSpace, MigrationError> {
+    let mut namespace = self.namespace(name)?;
+    namespace.migrate(migrations)?;
+    Ok(namespace)
+  }
+
   fn init(partition_name: &CStr) -> Result<(), EspError> {
     esp_ok!(nvs_flash_init_partition(partition_name.as_ptr()))
   }
@@ -103,7 +134,7 @@ impl NonVolatileStorage {
       match DEFAULT_INSTANCES.compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst) {
         Ok(_) => {
           let res = match Self::init(DEFAULT_PART_NAME) {
-            Err(err) if err.code == ESP_ERR_NVS_NO_FREE_PAGES as esp_err_t || err.code == ESP_ERR_NVS_NEW_VERSION_FOUND as esp_err_t => {
+            Err(err) if matches!(err.kind(), EspErrorKind::Nvs(NvsErrorKind::NoFreePages) | EspErrorKind::Nvs(NvsErrorKind::NewVersionFound)) => {
               let _ = Self::erase(DEFAULT_PART_NAME);
               Self::init(DEFAULT_PART_NAME)
             },
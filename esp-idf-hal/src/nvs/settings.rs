@@ -0,0 +1,94 @@
+use std::sync::{Arc, Mutex, RwLock, mpsc::{self, Receiver, Sender}};
+
+use super::*;
+
+/// Trait for types which are stored as multiple keys in a [`NameSpace`](struct.NameSpace.html).
+pub trait Persist: Sized {
+  /// Load the value from `namespace`.
+  fn load(namespace: &NameSpace) -> Result<Self, NvsError>;
+
+  /// Store the value in `transaction`, which is only committed if all keys were written.
+  fn store(&self, transaction: &mut Transaction<'_>) -> Result<(), NvsError>;
+}
+
+#[derive(Debug)]
+struct Inner<T> {
+  namespace: Mutex<NameSpace>,
+  value: RwLock<T>,
+  subscribers: Mutex<Vec<Sender<T>>>,
+}
+
+/// A typed configuration kept in RAM and persisted to a [`NameSpace`](struct.NameSpace.html) on every change.
+///
+/// Cloning a `Settings` returns another handle to the same configuration, so it
+/// can be shared between threads. Other threads can [`subscribe`](#method.subscribe)
+/// to be notified whenever the configuration changes.
+#[derive(Debug)]
+pub struct Settings<T> {
+  inner: Arc<Inner<T>>,
+}
+
+impl<T> Clone for Settings<T> {
+  fn clone(&self) -> Self {
+    Self { inner: Arc::clone(&self.inner) }
+  }
+}
+
+impl<T: Persist + Clone> Settings<T> {
+  /// Load settings from `namespace`.
+  pub fn new(namespace: NameSpace) -> Result<Self, NvsError> {
+    let value = T::load(&namespace)?;
+
+    Ok(Self {
+      inner: Arc::new(Inner {
+        namespace: Mutex::new(namespace),
+        value: RwLock::new(value),
+        subscribers: Mutex::new(Vec::new()),
+      }),
+    })
+  }
+
+  /// Get a copy of the current settings.
+  pub fn get(&self) -> T {
+    self.inner.value.read().unwrap().clone()
+  }
+
+  /// Replace the settings, persist them and notify all subscribers.
+  ///
+  /// All keys are written in a single [`Transaction`](struct.Transaction.html). If storing
+  /// fails, it is rolled back and the settings in RAM are left unchanged.
+  pub fn set(&self, value: T) -> Result<(), NvsError> {
+    let mut namespace = self.inner.namespace.lock().unwrap();
+    self.store(&mut namespace, value)
+  }
+
+  /// Modify a copy of the current settings using `f` and [`set`](#method.set) the result.
+  pub fn update(&self, f: impl FnOnce(&mut T)) -> Result<(), NvsError> {
+    let mut namespace = self.inner.namespace.lock().unwrap();
+
+    let mut value = self.get();
+    f(&mut value);
+    self.store(&mut namespace, value)
+  }
+
+  fn store(&self, namespace: &mut NameSpace, value: T) -> Result<(), NvsError> {
+    let mut transaction = namespace.transaction();
+    value.store(&mut transaction)?;
+    transaction.commit()?;
+
+    *self.inner.value.write().unwrap() = value.clone();
+
+    self.inner.subscribers.lock().unwrap().retain(|subscriber| subscriber.send(value.clone()).is_ok());
+
+    Ok(())
+  }
+
+  /// Receive the new settings every time they are changed.
+  ///
+  /// Dropping the returned `Receiver` unsubscribes.
+  pub fn subscribe(&self) -> Receiver<T> {
+    let (sender, receiver) = mpsc::channel();
+    self.inner.subscribers.lock().unwrap().push(sender);
+    receiver
+  }
+}
@@ -0,0 +1,417 @@
+//! Pulse counting using the IDF `pcnt` driver.
+//!
+//! A [`Unit`](struct.Unit.html) counts edges on the pulse pins of its two channels, optionally
+//! changing direction based on the level of a control pin, e.g. for flow meters or quadrature encoders.
+//!
+//! ```ignore
+//! let pins = gpio::Pins::take().unwrap();
+//!
+//! let config = pcnt::Config::new().filter(Some(1000)).threshold0(Some(450));
+//! let mut flow = pcnt::Unit::new(pcnt::UnitNum::Unit0, config, pcnt::ChannelConfig::new(pins.gpio26), None)?;
+//!
+//! let count = flow.wait_for_event().await?;
+//! ```
+
+use core::future::Future;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use core::task::{Context, Poll, Waker};
+use std::sync::Arc;
+
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+use crate::gpio::{self, sealed::SealedPin};
+use crate::sync::{install_once, pend_wake, PendWake, WakerCell};
+
+/// One of the eight pulse counter units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitNum {
+  Unit0,
+  Unit1,
+  Unit2,
+  Unit3,
+  Unit4,
+  Unit5,
+  Unit6,
+  Unit7,
+}
+
+impl From<UnitNum> for pcnt_unit_t {
+  fn from(unit: UnitNum) -> Self {
+    match unit {
+      UnitNum::Unit0 => pcnt_unit_t::PCNT_UNIT_0,
+      UnitNum::Unit1 => pcnt_unit_t::PCNT_UNIT_1,
+      UnitNum::Unit2 => pcnt_unit_t::PCNT_UNIT_2,
+      UnitNum::Unit3 => pcnt_unit_t::PCNT_UNIT_3,
+      UnitNum::Unit4 => pcnt_unit_t::PCNT_UNIT_4,
+      UnitNum::Unit5 => pcnt_unit_t::PCNT_UNIT_5,
+      UnitNum::Unit6 => pcnt_unit_t::PCNT_UNIT_6,
+      UnitNum::Unit7 => pcnt_unit_t::PCNT_UNIT_7,
+    }
+  }
+}
+
+/// How the counter changes on an edge of the pulse pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountMode {
+  Hold,
+  Increment,
+  Decrement,
+}
+
+impl From<CountMode> for pcnt_count_mode_t {
+  fn from(mode: CountMode) -> Self {
+    match mode {
+      CountMode::Hold => pcnt_count_mode_t::PCNT_COUNT_DIS,
+      CountMode::Increment => pcnt_count_mode_t::PCNT_COUNT_INC,
+      CountMode::Decrement => pcnt_count_mode_t::PCNT_COUNT_DEC,
+    }
+  }
+}
+
+/// How a level of the control pin modifies the [`CountMode`](enum.CountMode.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlMode {
+  Keep,
+  Reverse,
+  Disable,
+}
+
+impl From<ControlMode> for pcnt_ctrl_mode_t {
+  fn from(mode: ControlMode) -> Self {
+    match mode {
+      ControlMode::Keep => pcnt_ctrl_mode_t::PCNT_MODE_KEEP,
+      ControlMode::Reverse => pcnt_ctrl_mode_t::PCNT_MODE_REVERSE,
+      ControlMode::Disable => pcnt_ctrl_mode_t::PCNT_MODE_DISABLE,
+    }
+  }
+}
+
+/// Configuration for one of the two channels of a [`Unit`](struct.Unit.html).
+#[derive(Debug, Clone)]
+pub struct ChannelConfig {
+  pulse: gpio_num_t,
+  control: Option<gpio_num_t>,
+  rising: CountMode,
+  falling: CountMode,
+  control_high: ControlMode,
+  control_low: ControlMode,
+}
+
+impl ChannelConfig {
+  /// Count rising edges on `pulse`, without a control pin.
+  pub fn new(pulse: impl gpio::Pin) -> Self {
+    Self {
+      pulse: pulse.gpio_num(),
+      control: None,
+      rising: CountMode::Increment,
+      falling: CountMode::Hold,
+      control_high: ControlMode::Keep,
+      control_low: ControlMode::Keep,
+    }
+  }
+
+  /// Use `pin` as control pin, see [`control_high`](#method.control_high) and [`control_low`](#method.control_low).
+  pub fn control(mut self, pin: impl gpio::Pin) -> Self {
+    self.control = Some(pin.gpio_num());
+    self
+  }
+
+  /// What to do on a rising edge of the pulse pin.
+  pub fn rising(mut self, mode: CountMode) -> Self {
+    self.rising = mode;
+    self
+  }
+
+  /// What to do on a falling edge of the pulse pin.
+  pub fn falling(mut self, mode: CountMode) -> Self {
+    self.falling = mode;
+    self
+  }
+
+  /// How to modify the count mode while the control pin is high.
+  pub fn control_high(mut self, mode: ControlMode) -> Self {
+    self.control_high = mode;
+    self
+  }
+
+  /// How to modify the count mode while the control pin is low.
+  pub fn control_low(mut self, mode: ControlMode) -> Self {
+    self.control_low = mode;
+    self
+  }
+}
+
+/// Configuration for a [`Unit`](struct.Unit.html).
+#[derive(Debug, Clone)]
+pub struct Config {
+  low_limit: i16,
+  high_limit: i16,
+  filter: Option<u16>,
+  thresholds: [Option<i16>; 2],
+  zero_event: bool,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self { low_limit: i16::MIN, high_limit: i16::MAX, filter: None, thresholds: [None, None], zero_event: false }
+  }
+}
+
+impl Config {
+  /// The full counter range, no filter and no threshold events.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The counter resets to zero and triggers an event when reaching `limit`, which must not be positive.
+  pub fn low_limit(mut self, limit: i16) -> Self {
+    self.low_limit = limit.min(0);
+    self
+  }
+
+  /// The counter resets to zero and triggers an event when reaching `limit`, which must not be negative.
+  pub fn high_limit(mut self, limit: i16) -> Self {
+    self.high_limit = limit.max(0);
+    self
+  }
+
+  /// Ignore pulses shorter than `cycles` of the 80 MHz APB clock, at most 1023. `None` disables the filter.
+  pub fn filter(mut self, cycles: Option<u16>) -> Self {
+    self.filter = cycles.map(|cycles| cycles.min(1023));
+    self
+  }
+
+  /// Trigger an event when the counter reaches `value`.
+  pub fn threshold0(mut self, value: Option<i16>) -> Self {
+    self.thresholds[0] = value;
+    self
+  }
+
+  /// Trigger an event when the counter reaches `value`.
+  pub fn threshold1(mut self, value: Option<i16>) -> Self {
+    self.thresholds[1] = value;
+    self
+  }
+
+  /// Trigger an event when the counter crosses zero.
+  pub fn zero_event(mut self, enabled: bool) -> Self {
+    self.zero_event = enabled;
+    self
+  }
+}
+
+/// A configured and running pulse counter unit.
+#[derive(Debug)]
+pub struct Unit {
+  unit: UnitNum,
+}
+
+impl Unit {
+  /// Configure `unit` with one or two channels and start counting from zero.
+  pub fn new(unit: UnitNum, config: Config, channel0: ChannelConfig, channel1: Option<ChannelConfig>) -> Result<Self, EspError> {
+    let channels = [(pcnt_channel_t::PCNT_CHANNEL_0, Some(channel0)), (pcnt_channel_t::PCNT_CHANNEL_1, channel1)];
+
+    for (channel, channel_config) in channels.iter() {
+      let channel_config = match channel_config {
+        Some(channel_config) => channel_config,
+        None => continue,
+      };
+
+      let unit_config = pcnt_config_t {
+        pulse_gpio_num: channel_config.pulse as _,
+        ctrl_gpio_num: channel_config.control.map(|pin| pin as _).unwrap_or(PCNT_PIN_NOT_USED as _),
+        lctrl_mode: channel_config.control_low.into(),
+        hctrl_mode: channel_config.control_high.into(),
+        pos_mode: channel_config.rising.into(),
+        neg_mode: channel_config.falling.into(),
+        counter_h_lim: config.high_limit,
+        counter_l_lim: config.low_limit,
+        unit: unit.into(),
+        channel: *channel,
+      };
+
+      esp_ok!(pcnt_unit_config(&unit_config))?;
+    }
+
+    let unit_num = unit.into();
+
+    // `pcnt_unit_config` starts counting, so pause until everything is configured.
+    esp_ok!(pcnt_counter_pause(unit_num))?;
+
+    if let Some(cycles) = config.filter {
+      esp_ok!(pcnt_set_filter_value(unit_num, cycles))?;
+      esp_ok!(pcnt_filter_enable(unit_num))?;
+    } else {
+      esp_ok!(pcnt_filter_disable(unit_num))?;
+    }
+
+    let thresholds = [pcnt_evt_type_t::PCNT_EVT_THRES_0, pcnt_evt_type_t::PCNT_EVT_THRES_1];
+
+    for (event, threshold) in thresholds.iter().zip(config.thresholds.iter()) {
+      if let Some(value) = threshold {
+        esp_ok!(pcnt_set_event_value(unit_num, *event, *value))?;
+        esp_ok!(pcnt_event_enable(unit_num, *event))?;
+      } else {
+        esp_ok!(pcnt_event_disable(unit_num, *event))?;
+      }
+    }
+
+    if config.zero_event {
+      esp_ok!(pcnt_event_enable(unit_num, pcnt_evt_type_t::PCNT_EVT_ZERO))?;
+    } else {
+      esp_ok!(pcnt_event_disable(unit_num, pcnt_evt_type_t::PCNT_EVT_ZERO))?;
+    }
+
+    esp_ok!(pcnt_event_enable(unit_num, pcnt_evt_type_t::PCNT_EVT_H_LIM))?;
+    esp_ok!(pcnt_event_enable(unit_num, pcnt_evt_type_t::PCNT_EVT_L_LIM))?;
+
+    esp_ok!(pcnt_counter_clear(unit_num))?;
+    esp_ok!(pcnt_counter_resume(unit_num))?;
+
+    Ok(Self { unit })
+  }
+
+  /// The current value of the counter.
+  pub fn counter(&self) -> Result<i16, EspError> {
+    let mut value = 0;
+    esp_ok!(pcnt_get_counter_value(self.unit.into(), &mut value))?;
+    Ok(value)
+  }
+
+  /// Reset the counter to zero.
+  pub fn clear(&mut self) -> Result<(), EspError> {
+    esp_ok!(pcnt_counter_clear(self.unit.into()))
+  }
+
+  /// Stop counting.
+  pub fn pause(&mut self) -> Result<(), EspError> {
+    esp_ok!(pcnt_counter_pause(self.unit.into()))
+  }
+
+  /// Continue counting after [`pause`](#method.pause).
+  pub fn resume(&mut self) -> Result<(), EspError> {
+    esp_ok!(pcnt_counter_resume(self.unit.into()))
+  }
+
+  /// Wait until the counter reaches a threshold, a limit or zero, if enabled in the [`Config`](struct.Config.html).
+  ///
+  /// The future resolves to the counter value read in the interrupt handler. The driver does not report
+  /// which event occurred, and the counter is already reset to zero when reaching a limit.
+  ///
+  /// The interrupt is only enabled while the returned future is alive.
+  pub fn wait_for_event(&mut self) -> EventFuture<'_> {
+    EventFuture { unit: self.unit.into(), state: None, _unit: PhantomData }
+  }
+}
+
+impl Drop for Unit {
+  fn drop(&mut self) {
+    let _ = esp_ok!(pcnt_counter_pause(self.unit.into()));
+  }
+}
+
+static ISR_SERVICE_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Install the shared PCNT interrupt service, unless it was already installed, e.g. by C code.
+fn install_isr_service() -> Result<(), EspError> {
+  install_once(&ISR_SERVICE_INSTALLED, || esp_ok!(pcnt_isr_service_install(0)))
+}
+
+/// State shared between an [`EventFuture`](struct.EventFuture.html) and its interrupt handler.
+#[derive(Debug)]
+struct EventState {
+  unit: pcnt_unit_t,
+  triggered: AtomicBool,
+  value: AtomicI32,
+  waker: WakerCell,
+}
+
+impl PendWake for EventState {
+  fn wake(&self) {
+    self.waker.wake();
+  }
+}
+
+extern "C" fn event_isr(arg: *mut libc::c_void) {
+  // SAFETY: `arg` was created using `Arc::as_ptr` in `EventFuture::poll`, and the
+  // future holds a reference until the handler is removed.
+  let state = ManuallyDrop::new(unsafe { Arc::from_raw(arg as *const EventState) });
+
+  // Only the first event needs to wake the future.
+  if state.triggered.load(Ordering::Acquire) {
+    return
+  }
+
+  let mut value = 0;
+  let _ = unsafe { pcnt_get_counter_value(state.unit, &mut value) };
+
+  state.value.store(i32::from(value), Ordering::Relaxed);
+  state.triggered.store(true, Ordering::Release);
+  pend_wake(&state);
+}
+
+/// A future returned by [`Unit::wait_for_event`](struct.Unit.html#method.wait_for_event).
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct EventFuture<'u> {
+  unit: pcnt_unit_t,
+  state: Option<Arc<EventState>>,
+  _unit: PhantomData<&'u mut Unit>,
+}
+
+impl EventFuture<'_> {
+  fn enable(&mut self, waker: &Waker) -> Result<(), EspError> {
+    install_isr_service()?;
+
+    let state = Arc::new(EventState {
+      unit: self.unit,
+      triggered: AtomicBool::new(false),
+      value: AtomicI32::new(0),
+      waker: WakerCell::default(),
+    });
+    state.waker.register(waker);
+
+    esp_ok!(pcnt_isr_handler_add(self.unit, Some(event_isr), Arc::as_ptr(&state) as *mut _))?;
+    self.state = Some(state);
+
+    esp_ok!(pcnt_intr_enable(self.unit))
+  }
+}
+
+impl Future for EventFuture<'_> {
+  type Output = Result<i16, EspError>;
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    let state = match &self.state {
+      Some(state) => state,
+      None => {
+        if let Err(err) = self.enable(cx.waker()) {
+          return Poll::Ready(Err(err))
+        }
+
+        return Poll::Pending
+      },
+    };
+
+    state.waker.register(cx.waker());
+
+    if state.triggered.load(Ordering::Acquire) {
+      return Poll::Ready(Ok(state.value.load(Ordering::Relaxed) as i16))
+    }
+
+    Poll::Pending
+  }
+}
+
+impl Drop for EventFuture<'_> {
+  fn drop(&mut self) {
+    if self.state.is_some() {
+      let _ = esp_ok!(pcnt_intr_disable(self.unit));
+      let _ = esp_ok!(pcnt_isr_handler_remove(self.unit));
+    }
+  }
+}
@@ -0,0 +1,296 @@
+//! Remote control transceiver (RMT) using the IDF `rmt` driver.
+//!
+//! The RMT sends and receives sequences of [`Item`](struct.Item.html)s, each consisting of
+//! two levels with their durations in ticks of the channel clock. Encoders for
+//! [WS2812](struct.Ws2812.html) LEDs and the [NEC](struct.Nec.html) infrared protocol
+//! convert data to items without depending on the driver, they are re-exported from the
+//! `rmt-encoding` crate.
+//!
+//! ```ignore
+//! let pins = gpio::Pins::take().unwrap();
+//!
+//! // WS2812 LEDs need ticks of at most 100 ns.
+//! let mut tx = rmt::Tx::new(rmt::ChannelNum::Channel0, pins.gpio18, rmt::TxConfig::new().clock_divider(2))?;
+//! let ws2812 = rmt::Ws2812::new(tx.tick_ps());
+//!
+//! let colors = [rmt::Rgb::new(255, 0, 0); 8];
+//! tx.write_iter(ws2812.encode(&colors))?;
+//! ```
+
+use core::mem;
+use core::ptr;
+use core::slice;
+use core::time::Duration;
+
+use esp_idf_bindgen::*;
+
+use crate::EspError;
+use crate::gpio::{sealed::SealedPin, IoPin, Pin};
+use crate::sync;
+
+pub use rmt_encoding::*;
+
+static_assertions::assert_eq_size!(Item, rmt_item32_t);
+
+/// One of the eight RMT channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelNum {
+  Channel0,
+  Channel1,
+  Channel2,
+  Channel3,
+  Channel4,
+  Channel5,
+  Channel6,
+  Channel7,
+}
+
+impl From<ChannelNum> for rmt_channel_t {
+  fn from(channel: ChannelNum) -> Self {
+    match channel {
+      ChannelNum::Channel0 => rmt_channel_t::RMT_CHANNEL_0,
+      ChannelNum::Channel1 => rmt_channel_t::RMT_CHANNEL_1,
+      ChannelNum::Channel2 => rmt_channel_t::RMT_CHANNEL_2,
+      ChannelNum::Channel3 => rmt_channel_t::RMT_CHANNEL_3,
+      ChannelNum::Channel4 => rmt_channel_t::RMT_CHANNEL_4,
+      ChannelNum::Channel5 => rmt_channel_t::RMT_CHANNEL_5,
+      ChannelNum::Channel6 => rmt_channel_t::RMT_CHANNEL_6,
+      ChannelNum::Channel7 => rmt_channel_t::RMT_CHANNEL_7,
+    }
+  }
+}
+
+/// Duration of a tick in picoseconds with the 80 MHz APB clock divided by `clock_divider`.
+fn tick_ps(clock_divider: u8) -> u32 {
+  u32::from(clock_divider) * 12_500
+}
+
+/// Configuration for a [`Tx`](struct.Tx.html) channel.
+#[derive(Debug, Clone)]
+pub struct TxConfig {
+  clock_divider: u8,
+  mem_blocks: u8,
+  carrier: Option<(u32, u8)>,
+  idle_level: Option<bool>,
+}
+
+impl Default for TxConfig {
+  fn default() -> Self {
+    Self { clock_divider: 80, mem_blocks: 1, carrier: None, idle_level: Some(false) }
+  }
+}
+
+impl TxConfig {
+  /// Ticks of 1 µs, one memory block, no carrier and low when idle.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Divider of the 80 MHz APB clock, at least 1.
+  pub fn clock_divider(mut self, divider: u8) -> Self {
+    self.clock_divider = divider.max(1);
+    self
+  }
+
+  /// Number of 64-item memory blocks, taken from the following channels.
+  pub fn mem_blocks(mut self, blocks: u8) -> Self {
+    self.mem_blocks = blocks.max(1);
+    self
+  }
+
+  /// Modulate high levels with a carrier of `frequency` Hz and `duty_percent`, e.g. 38 kHz for infrared.
+  pub fn carrier(mut self, frequency: u32, duty_percent: u8) -> Self {
+    self.carrier = Some((frequency, duty_percent.min(100)));
+    self
+  }
+
+  /// Level of the output while not transmitting. `None` disables the output while idle.
+  pub fn idle_level(mut self, level: Option<bool>) -> Self {
+    self.idle_level = level;
+    self
+  }
+}
+
+/// An RMT channel in transmit mode.
+#[derive(Debug)]
+pub struct Tx {
+  channel: ChannelNum,
+  tick_ps: u32,
+}
+
+impl Tx {
+  /// Install the driver on `channel`, transmitting on `pin`.
+  pub fn new(channel: ChannelNum, pin: impl IoPin, config: TxConfig) -> Result<Self, EspError> {
+    let mut driver_config: rmt_config_t = unsafe { crate::zeroed_config() };
+    driver_config.rmt_mode = rmt_mode_t::RMT_MODE_TX;
+    driver_config.channel = channel.into();
+    driver_config.gpio_num = pin.gpio_num();
+    driver_config.clk_div = config.clock_divider;
+    driver_config.mem_block_num = config.mem_blocks;
+
+    let tx_config = unsafe { &mut driver_config.__bindgen_anon_1.tx_config };
+
+    if let Some((frequency, duty_percent)) = config.carrier {
+      tx_config.carrier_en = true;
+      tx_config.carrier_freq_hz = frequency;
+      tx_config.carrier_duty_percent = duty_percent;
+      tx_config.carrier_level = rmt_carrier_level_t::RMT_CARRIER_LEVEL_HIGH;
+    }
+
+    if let Some(level) = config.idle_level {
+      tx_config.idle_output_en = true;
+      tx_config.idle_level = if level { rmt_idle_level_t::RMT_IDLE_LEVEL_HIGH } else { rmt_idle_level_t::RMT_IDLE_LEVEL_LOW };
+    }
+
+    esp_ok!(rmt_config(&driver_config))?;
+    esp_ok!(rmt_driver_install(channel.into(), 0, 0))?;
+
+    Ok(Self { channel, tick_ps: tick_ps(config.clock_divider) })
+  }
+
+  /// Duration of a tick in picoseconds, used to create encoders.
+  pub fn tick_ps(&self) -> u32 {
+    self.tick_ps
+  }
+
+  /// Send `items`, blocking until they are sent.
+  pub fn write(&mut self, items: &[Item]) -> Result<(), EspError> {
+    let items_ptr = items.as_ptr() as *const rmt_item32_t;
+    esp_ok!(rmt_write_items(self.channel.into(), items_ptr, items.len() as _, true))
+  }
+
+  /// Send the items produced by an encoder, blocking until they are sent.
+  pub fn write_iter(&mut self, items: impl IntoIterator<Item = Item>) -> Result<(), EspError> {
+    let items = items.into_iter().collect::<Vec<_>>();
+    self.write(&items)
+  }
+}
+
+impl Drop for Tx {
+  fn drop(&mut self) {
+    let _ = esp_ok!(rmt_driver_uninstall(self.channel.into()));
+  }
+}
+
+/// Configuration for an [`Rx`](struct.Rx.html) channel.
+#[derive(Debug, Clone)]
+pub struct RxConfig {
+  clock_divider: u8,
+  mem_blocks: u8,
+  idle_threshold: u16,
+  filter_ticks: Option<u8>,
+  buffer_size: usize,
+}
+
+impl Default for RxConfig {
+  fn default() -> Self {
+    Self { clock_divider: 80, mem_blocks: 1, idle_threshold: 12_000, filter_ticks: Some(100), buffer_size: 1024 }
+  }
+}
+
+impl RxConfig {
+  /// Ticks of 1 µs, one memory block, ending a reception after 12 ms without an edge
+  /// and ignoring pulses shorter than 100 APB clock cycles.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Divider of the 80 MHz APB clock, at least 1.
+  pub fn clock_divider(mut self, divider: u8) -> Self {
+    self.clock_divider = divider.max(1);
+    self
+  }
+
+  /// Number of 64-item memory blocks, taken from the following channels.
+  pub fn mem_blocks(mut self, blocks: u8) -> Self {
+    self.mem_blocks = blocks.max(1);
+    self
+  }
+
+  /// Number of ticks without an edge after which a reception ends.
+  pub fn idle_threshold(mut self, ticks: u16) -> Self {
+    self.idle_threshold = ticks;
+    self
+  }
+
+  /// Ignore pulses shorter than `cycles` of the APB clock. `None` disables the filter.
+  pub fn filter(mut self, cycles: Option<u8>) -> Self {
+    self.filter_ticks = cycles;
+    self
+  }
+
+  /// Size of the buffer for received items in bytes.
+  pub fn buffer_size(mut self, size: usize) -> Self {
+    self.buffer_size = size;
+    self
+  }
+}
+
+/// An RMT channel in receive mode.
+#[derive(Debug)]
+pub struct Rx {
+  channel: ChannelNum,
+  tick_ps: u32,
+  ringbuf: RingbufHandle_t,
+}
+
+unsafe impl Send for Rx {}
+
+impl Rx {
+  /// Install the driver on `channel`, receiving on `pin`, and start receiving.
+  pub fn new(channel: ChannelNum, pin: impl Pin, config: RxConfig) -> Result<Self, EspError> {
+    let mut driver_config: rmt_config_t = unsafe { crate::zeroed_config() };
+    driver_config.rmt_mode = rmt_mode_t::RMT_MODE_RX;
+    driver_config.channel = channel.into();
+    driver_config.gpio_num = pin.gpio_num();
+    driver_config.clk_div = config.clock_divider;
+    driver_config.mem_block_num = config.mem_blocks;
+
+    let rx_config = unsafe { &mut driver_config.__bindgen_anon_1.rx_config };
+    rx_config.idle_threshold = config.idle_threshold;
+
+    if let Some(ticks) = config.filter_ticks {
+      rx_config.filter_en = true;
+      rx_config.filter_ticks_thresh = ticks;
+    }
+
+    esp_ok!(rmt_config(&driver_config))?;
+    esp_ok!(rmt_driver_install(channel.into(), config.buffer_size as _, 0))?;
+
+    // Uninstalls the driver if starting fails.
+    let mut rx = Self { channel, tick_ps: tick_ps(config.clock_divider), ringbuf: ptr::null_mut() };
+
+    esp_ok!(rmt_get_ringbuf_handle(channel.into(), &mut rx.ringbuf))?;
+    esp_ok!(rmt_rx_start(channel.into(), true))?;
+
+    Ok(rx)
+  }
+
+  /// Duration of a tick in picoseconds, used to create decoders.
+  pub fn tick_ps(&self) -> u32 {
+    self.tick_ps
+  }
+
+  /// Wait for the items of the next reception. Returns `None` if nothing is received within `timeout`.
+  pub fn receive(&mut self, timeout: Option<Duration>) -> Result<Option<Vec<Item>>, EspError> {
+    let mut size = 0;
+    let data = unsafe { xRingbufferReceive(self.ringbuf, &mut size, sync::ticks(timeout)) };
+
+    if data.is_null() {
+      return Ok(None)
+    }
+
+    // SAFETY: The driver stores whole items, which have the same layout as `rmt_item32_t`.
+    let items = unsafe { slice::from_raw_parts(data as *const Item, size / mem::size_of::<Item>()) }.to_vec();
+    unsafe { vRingbufferReturnItem(self.ringbuf, data) };
+
+    Ok(Some(items))
+  }
+}
+
+impl Drop for Rx {
+  fn drop(&mut self) {
+    let _ = esp_ok!(rmt_rx_stop(self.channel.into()));
+    let _ = esp_ok!(rmt_driver_uninstall(self.channel.into()));
+  }
+}
@@ -0,0 +1,161 @@
+//! State in RTC slow memory which survives deep sleep.
+//!
+//! An [`RtcCell`](struct.RtcCell.html) stores a small [`RtcValue`](trait.RtcValue.html) together
+//! with a magic number and a CRC. Cells declared using [`rtc_persist!`](../macro.rtc_persist.html)
+//! are placed in the `.rtc.data` section, which the bootloader initializes after power-on or a
+//! reset, but not when waking from [deep sleep](../sleep/fn.deep_sleep.html). Reading a cell which
+//! was not written since then, or whose contents are corrupted, returns `None`.
+//!
+//! ```ignore
+//! esp_idf_hal::rtc_persist! {
+//!   static BOOT_COUNT: u32;
+//!   static BSSID: [u8; 6];
+//!   static CHANNEL: u8;
+//! }
+//!
+//! let boot_count = BOOT_COUNT.update(|count| count.unwrap_or(0) + 1);
+//!
+//! let mut config = StaConfig::builder();
+//! config.ssid(ssid).password(password);
+//!
+//! if let (Some(bssid), Some(channel)) = (BSSID.get(), CHANNEL.get()) {
+//!   config.bssid(MacAddr6::from(bssid)).channel(NonZeroU8::new(channel));
+//! }
+//!
+//! let connection = wifi.connect_sta(config.build()).await?;
+//! BSSID.set(connection.bssid().into_array());
+//! CHANNEL.set(connection.channel().get());
+//! ```
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+
+use crate::sync::critical_section;
+
+const MAGIC: u32 = 0x5254_4331;
+
+/// A value which can be stored in an [`RtcCell`](struct.RtcCell.html).
+///
+/// The CRC is computed over the bytes of the value, so all of them must be initialized.
+///
+/// # Safety
+///
+/// The type must not contain padding bytes, and every bit pattern must be a valid value, since
+/// a cell may still contain a value of a different type written by a previous firmware.
+pub unsafe trait RtcValue: Copy {}
+
+macro_rules! impl_rtc_value {
+  ($($ty:ty),*) => {
+    $(unsafe impl RtcValue for $ty {})*
+  };
+}
+
+impl_rtc_value!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: RtcValue, const N: usize> RtcValue for [T; N] {}
+
+/// Bitwise CRC-32 (IEEE), which is fast enough for the small values stored in RTC memory.
+fn crc32(bytes: impl Iterator<Item = u8>) -> u32 {
+  !bytes.fold(!0, |crc, byte| {
+    (0..8).fold(crc ^ u32::from(byte), |crc, _| if crc & 1 == 1 { crc >> 1 ^ 0xedb8_8320 } else { crc >> 1 })
+  })
+}
+
+/// A value with a magic number and CRC, usually declared in RTC slow memory using
+/// [`rtc_persist!`](../macro.rtc_persist.html).
+#[repr(C)]
+pub struct RtcCell<T> {
+  magic: UnsafeCell<u32>,
+  crc: UnsafeCell<u32>,
+  value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: RtcValue + Send> Sync for RtcCell<T> {}
+
+impl<T> RtcCell<T> {
+  /// Create an empty cell.
+  pub const fn new() -> Self {
+    Self { magic: UnsafeCell::new(0), crc: UnsafeCell::new(0), value: UnsafeCell::new(MaybeUninit::uninit()) }
+  }
+}
+
+impl<T> Default for RtcCell<T> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<T: RtcValue> RtcCell<T> {
+  fn value_crc(&self) -> u32 {
+    let bytes = self.value.get() as *const u8;
+
+    // SAFETY: `T` has no padding, so all bytes were initialized by a previous `store`.
+    crc32((0..mem::size_of::<T>()).map(|i| unsafe { ptr::read_volatile(bytes.add(i)) }))
+  }
+
+  /// SAFETY: Must be called in a critical section.
+  unsafe fn load(&self) -> Option<T> {
+    // The CRC is only computed if the magic number shows that the value was stored.
+    if ptr::read_volatile(self.magic.get()) != MAGIC || ptr::read_volatile(self.crc.get()) != self.value_crc() {
+      return None
+    }
+
+    Some(ptr::read_volatile(self.value.get()).assume_init())
+  }
+
+  /// SAFETY: Must be called in a critical section.
+  unsafe fn store(&self, value: T) {
+    // Invalidate the cell first, so that it is not used if a reset interrupts writing.
+    ptr::write_volatile(self.magic.get(), 0);
+    ptr::write_volatile(self.value.get(), MaybeUninit::new(value));
+    ptr::write_volatile(self.crc.get(), self.value_crc());
+    ptr::write_volatile(self.magic.get(), MAGIC);
+  }
+
+  /// The stored value, or `None` if nothing was stored since power-on or a reset.
+  pub fn get(&self) -> Option<T> {
+    critical_section(|| unsafe { self.load() })
+  }
+
+  /// Store `value`.
+  pub fn set(&self, value: T) {
+    critical_section(|| unsafe { self.store(value) })
+  }
+
+  /// Remove the stored value.
+  pub fn clear(&self) {
+    critical_section(|| unsafe { ptr::write_volatile(self.magic.get(), 0) })
+  }
+
+  /// Replace the stored value with the result of `f`, which receives the previous value, and return it.
+  ///
+  /// `f` runs in a critical section, so it must be short and must not block.
+  pub fn update(&self, f: impl FnOnce(Option<T>) -> T) -> T {
+    critical_section(|| {
+      let value = f(unsafe { self.load() });
+      unsafe { self.store(value) };
+      value
+    })
+  }
+}
+
+impl<T: RtcValue + fmt::Debug> fmt::Debug for RtcCell<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("RtcCell").field(&self.get()).finish()
+  }
+}
+
+/// Declare statics of type [`RtcCell`](rtc/struct.RtcCell.html) in RTC slow memory, which keep
+/// their value during deep sleep, e.g. `rtc_persist!(static BOOT_COUNT: u32;)`.
+#[macro_export]
+macro_rules! rtc_persist {
+  ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty;)*) => {
+    $(
+      $(#[$attr])*
+      #[link_section = ".rtc.data"]
+      $vis static $name: $crate::rtc::RtcCell<$ty> = $crate::rtc::RtcCell::new();
+    )*
+  };
+}
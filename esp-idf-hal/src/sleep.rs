@@ -0,0 +1,205 @@
+//! Light and deep sleep using the IDF `esp_sleep` API.
+//!
+//! Execution continues after [`light_sleep`](fn.light_sleep.html), while
+//! [`deep_sleep`](fn.deep_sleep.html) powers down the CPUs and RAM, so the application
+//! starts from the beginning when woken and can query the [`wakeup_cause`](fn.wakeup_cause.html).
+//!
+//! ```ignore
+//! match sleep::wakeup_cause() {
+//!   sleep::WakeupCause::Timer => measure(),
+//!   _ => setup(),
+//! }
+//!
+//! let pins = gpio::Pins::take().unwrap();
+//! let button = pins.gpio33.into_input()?;
+//!
+//! let config = sleep::Config::new().timer(Duration::from_secs(600)).ext0(&button, false);
+//! sleep::deep_sleep(&config, Some(wifi))?;
+//! ```
+
+use core::time::Duration;
+
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+use crate::gpio::{self, sealed::SealedPin};
+use crate::wifi::{self, Wifi};
+
+/// The condition for a wakeup from [`Config::ext1`](struct.Config.html#method.ext1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ext1Mode {
+  /// Wake up when all pins are low.
+  AllLow,
+  /// Wake up when any pin is high.
+  AnyHigh,
+}
+
+impl From<Ext1Mode> for esp_sleep_ext1_wakeup_mode_t {
+  fn from(mode: Ext1Mode) -> Self {
+    match mode {
+      Ext1Mode::AllLow => esp_sleep_ext1_wakeup_mode_t::ESP_EXT1_WAKEUP_ALL_LOW,
+      Ext1Mode::AnyHigh => esp_sleep_ext1_wakeup_mode_t::ESP_EXT1_WAKEUP_ANY_HIGH,
+    }
+  }
+}
+
+/// Wakeup sources for [`light_sleep`](fn.light_sleep.html) and [`deep_sleep`](fn.deep_sleep.html).
+///
+/// The ext0 and ext1 sources only work with RTC GPIOs, i.e. GPIO 0, 2, 4, 12–15, 25–27 and 32–39.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+  timer: Option<Duration>,
+  ext0: Option<(gpio_num_t, bool)>,
+  ext1: Option<(u64, Ext1Mode)>,
+  gpio: Vec<(gpio_num_t, bool)>,
+  touch: bool,
+  ulp: bool,
+}
+
+impl Config {
+  /// No wakeup sources.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Wake up after `duration`.
+  pub fn timer(mut self, duration: Duration) -> Self {
+    self.timer = Some(duration);
+    self
+  }
+
+  /// Wake up when `pin` has the given `level`.
+  pub fn ext0(mut self, pin: &impl gpio::Pin, level: bool) -> Self {
+    self.ext0 = Some((pin.gpio_num(), level));
+    self
+  }
+
+  /// Wake up when the levels of `pins` match `mode`.
+  pub fn ext1(mut self, pins: &[&dyn gpio::Pin], mode: Ext1Mode) -> Self {
+    let mask = pins.iter().fold(0, |mask, pin| mask | 1 << pin.number());
+    self.ext1 = Some((mask, mode));
+    self
+  }
+
+  /// Wake up from light sleep when the input `pin` has the given `level`. Works with all GPIOs,
+  /// but not with deep sleep.
+  pub fn gpio(mut self, pin: &impl gpio::Pin, level: bool) -> Self {
+    self.gpio.push((pin.gpio_num(), level));
+    self
+  }
+
+  /// Wake up when a pad configured by the [`Touch`](../touch/struct.Touch.html) driver is touched.
+  pub fn touch(mut self, enabled: bool) -> Self {
+    self.touch = enabled;
+    self
+  }
+
+  /// Wake up when the ULP coprocessor program triggers a wakeup.
+  pub fn ulp(mut self, enabled: bool) -> Self {
+    self.ulp = enabled;
+    self
+  }
+
+  fn enable(&self) -> Result<(), EspError> {
+    esp_ok!(esp_sleep_disable_wakeup_source(esp_sleep_source_t::ESP_SLEEP_WAKEUP_ALL))?;
+
+    if let Some(duration) = self.timer {
+      let micros = duration.as_micros().min(u128::from(u64::max_value())) as u64;
+      esp_ok!(esp_sleep_enable_timer_wakeup(micros))?;
+    }
+
+    if let Some((num, level)) = self.ext0 {
+      esp_ok!(esp_sleep_enable_ext0_wakeup(num, level as libc::c_int))?;
+    }
+
+    if let Some((mask, mode)) = self.ext1 {
+      esp_ok!(esp_sleep_enable_ext1_wakeup(mask, mode.into()))?;
+    }
+
+    if !self.gpio.is_empty() {
+      for &(num, level) in &self.gpio {
+        let intr_type = if level { gpio_int_type_t::GPIO_INTR_HIGH_LEVEL } else { gpio_int_type_t::GPIO_INTR_LOW_LEVEL };
+        esp_ok!(gpio_wakeup_enable(num, intr_type))?;
+      }
+
+      esp_ok!(esp_sleep_enable_gpio_wakeup())?;
+    }
+
+    if self.touch {
+      esp_ok!(esp_sleep_enable_touchpad_wakeup())?;
+    }
+
+    if self.ulp {
+      esp_ok!(esp_sleep_enable_ulp_wakeup())?;
+    }
+
+    Ok(())
+  }
+}
+
+/// The reason for waking up from the last sleep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeupCause {
+  /// Not woken from sleep, e.g. after power-on or a reset.
+  Reset,
+  Timer,
+  Ext0,
+  /// The pins which caused the wakeup, as a bit mask of GPIO numbers.
+  Ext1(u64),
+  /// The number of the touch pad which caused the wakeup.
+  Touch(u8),
+  Ulp,
+  Gpio,
+  Uart,
+  Other,
+}
+
+/// The reason for waking up from the last light sleep, or from deep sleep after boot.
+pub fn wakeup_cause() -> WakeupCause {
+  match unsafe { esp_sleep_get_wakeup_cause() } {
+    esp_sleep_source_t::ESP_SLEEP_WAKEUP_UNDEFINED => WakeupCause::Reset,
+    esp_sleep_source_t::ESP_SLEEP_WAKEUP_TIMER => WakeupCause::Timer,
+    esp_sleep_source_t::ESP_SLEEP_WAKEUP_EXT0 => WakeupCause::Ext0,
+    esp_sleep_source_t::ESP_SLEEP_WAKEUP_EXT1 => WakeupCause::Ext1(unsafe { esp_sleep_get_ext1_wakeup_status() }),
+    esp_sleep_source_t::ESP_SLEEP_WAKEUP_TOUCHPAD => WakeupCause::Touch(unsafe { esp_sleep_get_touchpad_wakeup_status() } as u8),
+    esp_sleep_source_t::ESP_SLEEP_WAKEUP_ULP => WakeupCause::Ulp,
+    esp_sleep_source_t::ESP_SLEEP_WAKEUP_GPIO => WakeupCause::Gpio,
+    esp_sleep_source_t::ESP_SLEEP_WAKEUP_UART => WakeupCause::Uart,
+    _ => WakeupCause::Other,
+  }
+}
+
+/// Enter light sleep until woken by one of the sources in `config`.
+///
+/// Peripherals and RAM keep their state, but a running WiFi connection may be lost.
+pub fn light_sleep(config: &Config) -> Result<WakeupCause, EspError> {
+  let res = config.enable().and_then(|()| esp_ok!(esp_light_sleep_start()));
+
+  for &(num, _) in &config.gpio {
+    let _ = esp_ok!(gpio_wakeup_disable(num));
+  }
+
+  res.map(|()| wakeup_cause())
+}
+
+/// Enter deep sleep until woken by one of the sources in `config`, which restarts the application.
+///
+/// Pass the [`Wifi`](../wifi/struct.Wifi.html) instance, if any, so that it is stopped and
+/// deinitialized before sleeping. Fails with `ESP_ERR_INVALID_STATE` if another `Wifi` instance
+/// still exists, and with `ESP_ERR_INVALID_ARG` if `config` contains [`gpio`](struct.Config.html#method.gpio)
+/// wakeup sources. Without any wakeup sources, only a reset ends the deep sleep.
+pub fn deep_sleep(config: &Config, wifi: Option<Wifi>) -> Result<!, EspError> {
+  if !config.gpio.is_empty() {
+    return Err(EspErrorKind::Generic(GenericErrorKind::InvalidArg).into())
+  }
+
+  drop(wifi);
+
+  if wifi::is_active() {
+    return Err(EspErrorKind::Generic(GenericErrorKind::InvalidState).into())
+  }
+
+  config.enable()?;
+
+  unsafe { esp_deep_sleep_start() }
+}
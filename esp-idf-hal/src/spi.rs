@@ -0,0 +1,471 @@
+//! SPI master using the IDF `spi_master` driver.
+//!
+//! A [`Bus`](struct.Bus.html) can be shared by multiple [`Device`](struct.Device.html)s,
+//! each with its own chip select, clock speed and mode. The driver arbitrates between
+//! devices, so transactions on different devices can be queued concurrently.
+//!
+//! The driver copies buffers which cannot be accessed by DMA. To avoid this, allocate
+//! buffers using [`dma_buffer`](fn.dma_buffer.html).
+//!
+//! ```ignore
+//! let pins = gpio::Pins::take().unwrap();
+//!
+//! let bus = spi::Bus::new(spi::Host::Spi3, pins.gpio18, spi::BusConfig::new().mosi(pins.gpio23).miso(pins.gpio19))?;
+//! let mut flash = bus.add_device(spi::DeviceConfig::new().frequency(20_000_000).cs(pins.gpio5))?;
+//!
+//! let mut id = spi::dma_buffer(4)?;
+//! id[0] = 0x9f;
+//! let id = flash.transfer_in_place_async(id).await?;
+//! ```
+//!
+//! Asynchronous transfers take ownership of their buffers and return them on completion,
+//! so that they stay valid even if the future is leaked.
+
+use core::future::Future;
+use core::mem::{self, ManuallyDrop};
+use core::pin::Pin as FuturePin;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
+use std::sync::Arc;
+
+use embedded_hal::spi::{Mode, Phase, Polarity, MODE_0};
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+use crate::gpio::{IoPin, Pin};
+use crate::heap::{CapsVec, HeapCaps};
+use crate::sync::{self, pend_wake, PendWake, WakerCell};
+
+/// Allocate a zeroed buffer of `len` bytes in DMA-capable memory.
+///
+/// Buffers received into should have a length which is a multiple of 4, otherwise the driver still copies them.
+pub fn dma_buffer(len: usize) -> Result<CapsVec<u8>, EspError> {
+  CapsVec::from_elem(0, len, HeapCaps::DMA | HeapCaps::INTERNAL)
+}
+
+/// An SPI controller which can be used as a master.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Host {
+  /// SPI2, also known as HSPI.
+  Spi2,
+  /// SPI3, also known as VSPI.
+  Spi3,
+}
+
+impl From<Host> for spi_host_device_t {
+  fn from(host: Host) -> Self {
+    match host {
+      Host::Spi2 => spi_host_device_t::SPI2_HOST,
+      Host::Spi3 => spi_host_device_t::SPI3_HOST,
+    }
+  }
+}
+
+/// `-1`, used for unused pins.
+const NO_PIN: libc::c_int = -1;
+
+/// Maximum transaction size without DMA, i.e. the size of the hardware buffer.
+const MAX_NON_DMA_TRANSFER_SIZE: usize = 64;
+
+/// Default maximum transaction size with DMA, used by the driver if none is configured.
+const DEFAULT_DMA_TRANSFER_SIZE: usize = 4092;
+
+/// Configuration for a [`Bus`](struct.Bus.html).
+#[derive(Debug, Clone)]
+pub struct BusConfig {
+  mosi: libc::c_int,
+  miso: libc::c_int,
+  dma: bool,
+  max_transfer_size: Option<usize>,
+}
+
+impl Default for BusConfig {
+  fn default() -> Self {
+    Self { mosi: NO_PIN, miso: NO_PIN, dma: true, max_transfer_size: None }
+  }
+}
+
+impl BusConfig {
+  /// A bus using DMA, without MOSI and MISO.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn mosi(mut self, mosi: impl IoPin) -> Self {
+    self.mosi = mosi.number().into();
+    self
+  }
+
+  pub fn miso(mut self, miso: impl Pin) -> Self {
+    self.miso = miso.number().into();
+    self
+  }
+
+  /// Use DMA, allowing transactions larger than 64 bytes. Enabled by default.
+  pub fn dma(mut self, enable: bool) -> Self {
+    self.dma = enable;
+    self
+  }
+
+  /// Maximum size of a single transaction in bytes when using DMA, 4092 by default.
+  ///
+  /// Larger writes using the `embedded-hal` traits are split into multiple transactions.
+  pub fn max_transfer_size(mut self, size: usize) -> Self {
+    self.max_transfer_size = Some(size);
+    self
+  }
+}
+
+#[derive(Debug)]
+struct BusInner {
+  host: Host,
+  max_transfer_size: usize,
+}
+
+impl Drop for BusInner {
+  fn drop(&mut self) {
+    let _ = esp_ok!(spi_bus_free(self.host.into()));
+  }
+}
+
+/// An initialized SPI bus. The bus is freed once it and all of its devices are dropped.
+#[derive(Debug, Clone)]
+pub struct Bus {
+  inner: Arc<BusInner>,
+}
+
+impl Bus {
+  /// Initialize the bus on `host` with the given clock pin.
+  pub fn new(host: Host, sclk: impl IoPin, config: BusConfig) -> Result<Self, EspError> {
+    let max_transfer_size = if config.dma {
+      config.max_transfer_size.unwrap_or(DEFAULT_DMA_TRANSFER_SIZE)
+    } else {
+      MAX_NON_DMA_TRANSFER_SIZE
+    };
+
+    let mut bus_config: spi_bus_config_t = unsafe { crate::zeroed_config() };
+    bus_config.sclk_io_num = sclk.number().into();
+    bus_config.mosi_io_num = config.mosi;
+    bus_config.miso_io_num = config.miso;
+    bus_config.quadwp_io_num = NO_PIN;
+    bus_config.quadhd_io_num = NO_PIN;
+    bus_config.max_transfer_sz = max_transfer_size as _;
+    // Without `ESP_INTR_FLAG_IRAM`, the interrupt is disabled while the flash cache is,
+    // which allows the transaction callback to be in flash.
+    bus_config.intr_flags = 0;
+
+    // Use DMA channel 1 for SPI2 and channel 2 for SPI3, so both can use DMA.
+    let dma_channel = match (config.dma, host) {
+      (false, _) => 0,
+      (true, Host::Spi2) => 1,
+      (true, Host::Spi3) => 2,
+    };
+
+    esp_ok!(spi_bus_initialize(host.into(), &bus_config, dma_channel))?;
+
+    Ok(Self { inner: Arc::new(BusInner { host, max_transfer_size }) })
+  }
+
+  pub fn host(&self) -> Host {
+    self.inner.host
+  }
+
+  /// Add a device to the bus.
+  pub fn add_device(&self, config: DeviceConfig) -> Result<Device, EspError> {
+    let mut device_config: spi_device_interface_config_t = unsafe { crate::zeroed_config() };
+    device_config.mode = mode_number(config.mode);
+    device_config.clock_speed_hz = config.frequency as _;
+    device_config.spics_io_num = config.cs;
+    device_config.queue_size = config.queue_size as _;
+    device_config.post_cb = Some(transaction_done);
+
+    let mut handle = ptr::null_mut();
+    esp_ok!(spi_bus_add_device(self.inner.host.into(), &device_config, &mut handle))?;
+
+    Ok(Device { handle, bus: Arc::clone(&self.inner) })
+  }
+}
+
+fn mode_number(mode: Mode) -> u8 {
+  let polarity = match mode.polarity {
+    Polarity::IdleLow => 0,
+    Polarity::IdleHigh => 0b10,
+  };
+
+  let phase = match mode.phase {
+    Phase::CaptureOnFirstTransition => 0,
+    Phase::CaptureOnSecondTransition => 0b01,
+  };
+
+  polarity | phase
+}
+
+/// Configuration for a [`Device`](struct.Device.html).
+#[derive(Debug, Clone)]
+pub struct DeviceConfig {
+  frequency: u32,
+  mode: Mode,
+  cs: libc::c_int,
+  queue_size: usize,
+}
+
+impl Default for DeviceConfig {
+  fn default() -> Self {
+    Self { frequency: 1_000_000, mode: MODE_0, cs: NO_PIN, queue_size: 1 }
+  }
+}
+
+impl DeviceConfig {
+  /// 1 MHz in mode 0, without chip select.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Clock frequency in Hz.
+  pub fn frequency(mut self, frequency: u32) -> Self {
+    self.frequency = frequency;
+    self
+  }
+
+  pub fn mode(mut self, mode: Mode) -> Self {
+    self.mode = mode;
+    self
+  }
+
+  /// Chip select pin, which is driven by the hardware.
+  pub fn cs(mut self, cs: impl IoPin) -> Self {
+    self.cs = cs.number().into();
+    self
+  }
+
+  /// Number of transactions which can be queued on the device.
+  pub fn queue_size(mut self, size: usize) -> Self {
+    self.queue_size = size.max(1);
+    self
+  }
+}
+
+/// State shared between a [`TransferFuture`](struct.TransferFuture.html) and the transaction callback.
+#[derive(Debug, Default)]
+struct TransactionState {
+  done: AtomicBool,
+  waker: WakerCell,
+}
+
+impl PendWake for TransactionState {
+  fn wake(&self) {
+    self.waker.wake();
+  }
+}
+
+/// Called from the SPI interrupt handler after each transaction.
+extern "C" fn transaction_done(trans: *mut spi_transaction_t) {
+  let user = unsafe { (*trans).user };
+
+  // Blocking transactions have no state.
+  if user.is_null() {
+    return
+  }
+
+  // SAFETY: `user` was created using `Arc::as_ptr` in `TransferFuture::poll`, and the future
+  // holds a reference until the transaction result is received or is leaked together with it.
+  let state = ManuallyDrop::new(unsafe { Arc::from_raw(user as *const TransactionState) });
+  state.done.store(true, Ordering::Release);
+  pend_wake(&state);
+}
+
+fn invalid_arg() -> EspError {
+  EspErrorKind::Generic(GenericErrorKind::InvalidArg).into()
+}
+
+/// Create a transaction writing `write` and reading into `read`.
+fn transaction(write: &[u8], read: &mut [u8]) -> Result<spi_transaction_t, EspError> {
+  // In full-duplex mode, `length` bits are written, so `read` may only be longer if nothing is written.
+  if !write.is_empty() && read.len() > write.len() {
+    return Err(invalid_arg())
+  }
+
+  let mut trans: spi_transaction_t = unsafe { mem::zeroed() };
+  trans.length = write.len().max(read.len()) * 8;
+  trans.rxlength = read.len() * 8;
+
+  if !write.is_empty() {
+    trans.__bindgen_anon_1.tx_buffer = write.as_ptr() as *const _;
+  }
+
+  if !read.is_empty() {
+    trans.__bindgen_anon_2.rx_buffer = read.as_mut_ptr() as *mut _;
+  }
+
+  Ok(trans)
+}
+
+/// A device on a [`Bus`](struct.Bus.html).
+#[derive(Debug)]
+pub struct Device {
+  handle: spi_device_handle_t,
+  bus: Arc<BusInner>,
+}
+
+unsafe impl Send for Device {}
+
+impl Device {
+  /// Maximum size of a single transaction in bytes.
+  pub fn max_transfer_size(&self) -> usize {
+    self.bus.max_transfer_size
+  }
+
+  /// Write `write` while reading into `read`, blocking until the transaction is complete.
+  ///
+  /// `read` must not be longer than `write`, unless `write` is empty.
+  pub fn transfer(&mut self, write: &[u8], read: &mut [u8]) -> Result<(), EspError> {
+    let mut trans = transaction(write, read)?;
+    esp_ok!(spi_device_transmit(self.handle, &mut trans))
+  }
+
+  /// Write `buffer` while reading into it, blocking until the transaction is complete.
+  pub fn transfer_in_place(&mut self, buffer: &mut [u8]) -> Result<(), EspError> {
+    let mut trans = transaction(&[], buffer)?;
+    trans.length = buffer.len() * 8;
+    trans.__bindgen_anon_1.tx_buffer = buffer.as_ptr() as *const _;
+    esp_ok!(spi_device_transmit(self.handle, &mut trans))
+  }
+
+  /// Queue a transaction writing `write` while reading into `read`, resolving to both buffers.
+  ///
+  /// `read` must not be longer than `write`, unless `write` is empty. If the returned future
+  /// is dropped before it completes, dropping it blocks until the transaction is complete.
+  pub fn transfer_async(&mut self, write: CapsVec<u8>, mut read: CapsVec<u8>) -> TransferFuture<'_, (CapsVec<u8>, CapsVec<u8>)> {
+    let trans = transaction(&write, &mut read).map(Box::new);
+    TransferFuture::new(self, trans, (write, read))
+  }
+
+  /// Queue a transaction writing `buffer` while reading into it, resolving to the buffer.
+  pub fn transfer_in_place_async(&mut self, mut buffer: CapsVec<u8>) -> TransferFuture<'_, CapsVec<u8>> {
+    let trans = transaction(&[], &mut buffer).map(|mut trans| {
+      trans.length = buffer.len() * 8;
+      trans.__bindgen_anon_1.tx_buffer = buffer.as_ptr() as *const _;
+      Box::new(trans)
+    });
+
+    TransferFuture::new(self, trans, buffer)
+  }
+}
+
+impl Drop for Device {
+  fn drop(&mut self) {
+    let _ = esp_ok!(spi_bus_remove_device(self.handle));
+  }
+}
+
+impl embedded_hal::blocking::spi::Transfer<u8> for Device {
+  type Error = EspError;
+
+  fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+    for chunk in words.chunks_mut(self.max_transfer_size()) {
+      self.transfer_in_place(chunk)?;
+    }
+
+    Ok(words)
+  }
+}
+
+impl embedded_hal::blocking::spi::Write<u8> for Device {
+  type Error = EspError;
+
+  fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+    for chunk in words.chunks(self.max_transfer_size()) {
+      self.transfer(chunk, &mut [])?;
+    }
+
+    Ok(())
+  }
+}
+
+/// A future returned by [`Device::transfer_async`](struct.Device.html#method.transfer_async)
+/// and [`Device::transfer_in_place_async`](struct.Device.html#method.transfer_in_place_async).
+///
+/// Owns the transaction buffers `B` until the transaction is complete.
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct TransferFuture<'d, B> {
+  device: &'d mut Device,
+  trans: Option<Result<Box<spi_transaction_t>, EspError>>,
+  buffers: Option<B>,
+  state: Arc<TransactionState>,
+  queued: bool,
+}
+
+unsafe impl<B: Send> Send for TransferFuture<'_, B> {}
+
+impl<'d, B> TransferFuture<'d, B> {
+  fn new(device: &'d mut Device, trans: Result<Box<spi_transaction_t>, EspError>, buffers: B) -> Self {
+    Self { device, trans: Some(trans), buffers: Some(buffers), state: Arc::new(TransactionState::default()), queued: false }
+  }
+
+  /// Wait for the result of the queued transaction.
+  fn finish(&mut self) -> Result<(), EspError> {
+    self.queued = false;
+
+    let own: *const spi_transaction_t = match &self.trans {
+      Some(Ok(trans)) => &**trans,
+      _ => ptr::null(),
+    };
+
+    // Results of transactions queued by leaked futures may still be pending, skip them.
+    loop {
+      let mut result = ptr::null_mut();
+      esp_ok!(spi_device_get_trans_result(self.device.handle, &mut result, sync::ticks(None)))?;
+
+      if ptr::eq(result, own) {
+        return Ok(())
+      }
+    }
+  }
+}
+
+impl<B: Unpin> Future for TransferFuture<'_, B> {
+  type Output = Result<B, EspError>;
+
+  fn poll(mut self: FuturePin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    // Register the waker before queueing or checking, so completion in between is not missed.
+    self.state.waker.register(cx.waker());
+
+    if !self.queued {
+      let mut trans = match self.trans.take() {
+        Some(Ok(trans)) => trans,
+        Some(Err(err)) => return Poll::Ready(Err(err)),
+        None => panic!("`TransferFuture` polled after completion"),
+      };
+
+      trans.user = Arc::as_ptr(&self.state) as *mut _;
+
+      // The device is borrowed mutably, so its queue only contains transactions of leaked futures.
+      if let Err(err) = esp_ok!(spi_device_queue_trans(self.device.handle, &mut *trans, sync::ticks(None))) {
+        return Poll::Ready(Err(err))
+      }
+
+      self.trans = Some(Ok(trans));
+      self.queued = true;
+    }
+
+    if !self.state.done.load(Ordering::Acquire) {
+      return Poll::Pending
+    }
+
+    // The result is posted by the interrupt handler right after the callback, so this returns immediately.
+    let result = self.finish();
+    self.trans = None;
+    Poll::Ready(result.map(|()| self.buffers.take().unwrap()))
+  }
+}
+
+impl<B> Drop for TransferFuture<'_, B> {
+  fn drop(&mut self) {
+    // The transaction and its buffers must stay valid until the driver is done with them.
+    if self.queued {
+      let _ = self.finish();
+    }
+  }
+}
@@ -0,0 +1,158 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+use core::time::Duration;
+use std::sync::Arc;
+
+use crate::EspError;
+
+use super::*;
+
+#[derive(Debug)]
+struct Shared<T> {
+  queue: Queue<T>,
+  waker: WakerCell,
+  senders: AtomicUsize,
+  receiver_alive: AtomicBool,
+}
+
+impl<T: Send> PendWake for Shared<T> {
+  fn wake(&self) {
+    self.waker.wake()
+  }
+}
+
+/// Create a bounded channel for sending values from tasks, event loop callbacks or
+/// interrupt handlers to an `async` receiver.
+///
+/// ```ignore
+/// let (tx, mut rx) = sync::channel(4)?;
+///
+/// // In a callback:
+/// let _ = tx.send(Event::Done);
+///
+/// // In an `async` function:
+/// while let Some(event) = rx.recv().await {
+///   // …
+/// }
+/// ```
+pub fn channel<T: Send>(capacity: usize) -> Result<(Sender<T>, Receiver<T>), EspError> {
+  let shared = Arc::new(Shared {
+    queue: Queue::new(capacity)?,
+    waker: WakerCell::default(),
+    senders: AtomicUsize::new(1),
+    receiver_alive: AtomicBool::new(true),
+  });
+
+  Ok((Sender { shared: Arc::clone(&shared) }, Receiver { shared }))
+}
+
+/// The sending half of a [`channel`](fn.channel.html).
+#[derive(Debug)]
+pub struct Sender<T> {
+  shared: Arc<Shared<T>>,
+}
+
+impl<T: Send> Sender<T> {
+  /// Send `item` without waiting. Returns the item back if the channel is full or the receiver was dropped.
+  pub fn send(&self, item: T) -> Result<(), T> {
+    if !self.shared.receiver_alive.load(Ordering::Acquire) {
+      return Err(item)
+    }
+
+    self.shared.queue.send(item, Some(Duration::from_secs(0)))?;
+    self.shared.waker.wake();
+
+    Ok(())
+  }
+
+  /// Send `item` from an interrupt handler. Returns the item back if the channel is full or the receiver was dropped.
+  ///
+  /// The receiver is woken by the timer service task. If the timer command queue is full,
+  /// the item is still sent, but the receiver is only woken by the next successful send.
+  pub fn send_from_isr(&self, item: T) -> Result<(), T> {
+    if !self.shared.receiver_alive.load(Ordering::Acquire) {
+      return Err(item)
+    }
+
+    self.shared.queue.send_from_isr(item)?;
+    pend_wake(&self.shared);
+
+    Ok(())
+  }
+}
+
+impl<T> Clone for Sender<T> {
+  fn clone(&self) -> Self {
+    self.shared.senders.fetch_add(1, Ordering::Relaxed);
+    Self { shared: Arc::clone(&self.shared) }
+  }
+}
+
+impl<T> Drop for Sender<T> {
+  fn drop(&mut self) {
+    if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+      self.shared.waker.wake();
+    }
+  }
+}
+
+/// The receiving half of a [`channel`](fn.channel.html).
+#[derive(Debug)]
+pub struct Receiver<T> {
+  shared: Arc<Shared<T>>,
+}
+
+impl<T: Send> Receiver<T> {
+  /// Receive an item without waiting.
+  pub fn try_recv(&mut self) -> Option<T> {
+    self.shared.queue.receive(Some(Duration::from_secs(0)))
+  }
+
+  /// Receive an item, blocking the current task for up to `timeout`.
+  pub fn recv_blocking(&mut self, timeout: Option<Duration>) -> Option<T> {
+    self.shared.queue.receive(timeout)
+  }
+
+  /// Receive the next item. Resolves to `None` once all senders are dropped and the channel is empty.
+  pub fn recv(&mut self) -> Recv<'_, T> {
+    Recv { receiver: self }
+  }
+}
+
+impl<T> Drop for Receiver<T> {
+  fn drop(&mut self) {
+    self.shared.receiver_alive.store(false, Ordering::Release);
+  }
+}
+
+/// A future returned by [`Receiver::recv`](struct.Receiver.html#method.recv).
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct Recv<'r, T> {
+  receiver: &'r mut Receiver<T>,
+}
+
+impl<T: Send> Future for Recv<'_, T> {
+  type Output = Option<T>;
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    if let Some(item) = self.receiver.try_recv() {
+      return Poll::Ready(Some(item))
+    }
+
+    self.receiver.shared.waker.register(cx.waker());
+
+    // Check again, since an item may have been sent before the waker was registered.
+    if let Some(item) = self.receiver.try_recv() {
+      return Poll::Ready(Some(item))
+    }
+
+    if self.receiver.shared.senders.load(Ordering::Acquire) == 0 {
+      return Poll::Ready(self.receiver.try_recv())
+    }
+
+    Poll::Pending
+  }
+}
@@ -0,0 +1,85 @@
+use core::time::Duration;
+
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+
+use super::*;
+
+/// A FreeRTOS event group, i.e. a set of event bits tasks can wait on.
+///
+/// Only the lower 24 bits can be used.
+#[derive(Debug)]
+pub struct EventGroup {
+  handle: EventGroupHandle_t,
+}
+
+unsafe impl Send for EventGroup {}
+unsafe impl Sync for EventGroup {}
+
+impl EventGroup {
+  pub fn new() -> Result<Self, EspError> {
+    let handle = unsafe { xEventGroupCreate() };
+
+    if handle.is_null() {
+      return Err(EspErrorKind::Generic(GenericErrorKind::NoMem).into())
+    }
+
+    Ok(Self { handle })
+  }
+
+  /// Set `bits`, returning the bits at the time this function returns.
+  pub fn set(&self, bits: EventBits_t) -> EventBits_t {
+    unsafe { xEventGroupSetBits(self.handle, bits) }
+  }
+
+  /// Set `bits` from an interrupt handler.
+  ///
+  /// The bits are set by the timer service task, so they are not set immediately.
+  /// Returns an error if the timer command queue is full.
+  pub fn set_from_isr(&self, bits: EventBits_t) -> Result<(), EspError> {
+    let mut woken = 0;
+
+    let res = unsafe {
+      xTimerPendFunctionCallFromISR(Some(vEventGroupSetBitsCallback), self.handle as _, bits, &mut woken)
+    };
+    yield_from_isr(woken);
+
+    if res == PD_TRUE {
+      Ok(())
+    } else {
+      Err(EspErrorKind::Generic(GenericErrorKind::NoMem).into())
+    }
+  }
+
+  /// Clear `bits`, returning the bits before they were cleared.
+  pub fn clear(&self, bits: EventBits_t) -> EventBits_t {
+    unsafe { xEventGroupClearBits(self.handle, bits) }
+  }
+
+  /// The currently set bits.
+  pub fn get(&self) -> EventBits_t {
+    unsafe { xEventGroupClearBits(self.handle, 0) }
+  }
+
+  /// The currently set bits, from an interrupt handler.
+  pub fn get_from_isr(&self) -> EventBits_t {
+    unsafe { xEventGroupGetBitsFromISR(self.handle) }
+  }
+
+  /// Wait up to `timeout` until any of `bits` are set, or all of them if `wait_for_all` is `true`.
+  ///
+  /// Returns the bits at the time the wait ended, which need to be checked to find out if it timed out.
+  /// If `clear_on_exit` is `true` and the wait succeeded, `bits` are cleared.
+  pub fn wait(&self, bits: EventBits_t, clear_on_exit: bool, wait_for_all: bool, timeout: Option<Duration>) -> EventBits_t {
+    unsafe {
+      xEventGroupWaitBits(self.handle, bits, clear_on_exit as _, wait_for_all as _, ticks(timeout))
+    }
+  }
+}
+
+impl Drop for EventGroup {
+  fn drop(&mut self) {
+    unsafe { vEventGroupDelete(self.handle) };
+  }
+}
@@ -0,0 +1,156 @@
+//! FreeRTOS synchronization primitives.
+//!
+//! All primitives can be shared between tasks, and their `*_from_isr` methods can be used
+//! from interrupt handlers. To wait for events from a callback in an `async` context,
+//! use a [`channel`](fn.channel.html).
+
+#[cfg(target_device = "esp32")]
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use std::sync::Arc;
+
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+
+mod queue;
+pub use queue::*;
+
+mod semaphore;
+pub use semaphore::*;
+
+mod event_group;
+pub use event_group::*;
+
+mod notification;
+pub use notification::*;
+
+mod channel;
+pub use channel::*;
+
+mod waker_cell;
+pub(crate) use waker_cell::WakerCell;
+
+// Constants from `queue.h`, which are defined using casts and therefore not generated.
+const QUEUE_TYPE_BASE: u8 = 0;
+const QUEUE_TYPE_BINARY_SEMAPHORE: u8 = 3;
+const QUEUE_SEND_TO_BACK: BaseType_t = 0;
+const QUEUE_SEND_TO_FRONT: BaseType_t = 1;
+
+pub(crate) const PD_TRUE: BaseType_t = 1;
+const PORT_MAX_DELAY: TickType_t = TickType_t::max_value();
+
+/// Convert a timeout to FreeRTOS ticks, rounding up. `None` means waiting indefinitely.
+pub(crate) fn ticks(timeout: Option<Duration>) -> TickType_t {
+  match timeout {
+    None => PORT_MAX_DELAY,
+    Some(timeout) => {
+      let ticks = (timeout.as_micros() * u128::from(configTICK_RATE_HZ) + 999_999) / 1_000_000;
+      ticks.min(u128::from(PORT_MAX_DELAY - 1)) as TickType_t
+    },
+  }
+}
+
+#[cfg(target_device = "esp8266")]
+extern "C" {
+  fn PendSV(req: libc::c_int);
+}
+
+/// Request a context switch when leaving the current interrupt handler if a higher priority task was woken.
+#[inline]
+pub(crate) fn yield_from_isr(higher_priority_task_woken: BaseType_t) {
+  if higher_priority_task_woken != PD_TRUE {
+    return
+  }
+
+  #[cfg(target_device = "esp32")]
+  unsafe { vPortYieldFromISR() };
+
+  #[cfg(target_device = "esp8266")]
+  unsafe { PendSV(1) };
+}
+
+/// The value of `portMUX_FREE_VAL`, which is defined using another macro and therefore not generated.
+#[cfg(target_device = "esp32")]
+const PORT_MUX_FREE_VAL: u32 = 0xb33f_ffff;
+
+#[cfg(target_device = "esp32")]
+static mut CRITICAL_SECTION_MUX: portMUX_TYPE = portMUX_TYPE { owner: PORT_MUX_FREE_VAL, count: 0 };
+
+struct CriticalSectionGuard;
+
+impl Drop for CriticalSectionGuard {
+  fn drop(&mut self) {
+    #[cfg(target_device = "esp32")]
+    unsafe { vPortExitCritical(ptr::addr_of_mut!(CRITICAL_SECTION_MUX)) };
+
+    #[cfg(target_device = "esp8266")]
+    unsafe { vPortExitCritical() };
+  }
+}
+
+/// Run `f` in a FreeRTOS critical section, which disables interrupts on the current core and,
+/// on the ESP32, holds a spinlock shared with the other core.
+///
+/// Unlike a lock which yields while waiting, this cannot starve a lower priority task holding it.
+/// `f` must be short and must not block, allocate or call FreeRTOS functions which may yield.
+pub(crate) fn critical_section<R>(f: impl FnOnce() -> R) -> R {
+  #[cfg(target_device = "esp32")]
+  unsafe { vPortEnterCritical(ptr::addr_of_mut!(CRITICAL_SECTION_MUX)) };
+
+  #[cfg(target_device = "esp8266")]
+  unsafe { vPortEnterCritical() };
+
+  let _guard = CriticalSectionGuard;
+  f()
+}
+
+/// Install a shared driver service such as an interrupt service once, using `installed` to remember it.
+///
+/// `ESP_ERR_INVALID_STATE` means the service was already installed, e.g. by C code, and is not an error.
+pub(crate) fn install_once(installed: &AtomicBool, install: impl FnOnce() -> Result<(), EspError>) -> Result<(), EspError> {
+  if installed.load(Ordering::Acquire) {
+    return Ok(())
+  }
+
+  match install() {
+    Err(err) if err.kind() != EspErrorKind::Generic(GenericErrorKind::InvalidState) => return Err(err),
+    _ => (),
+  }
+
+  installed.store(true, Ordering::Release);
+  Ok(())
+}
+
+/// State which can be woken from an interrupt handler using [`pend_wake`](fn.pend_wake.html).
+pub(crate) trait PendWake: Send + Sync {
+  fn wake(&self);
+}
+
+unsafe extern "C" fn pended_wake<T: PendWake>(target: *mut libc::c_void, _: u32) {
+  // SAFETY: `target` was created using `Arc::into_raw` in `pend_wake`.
+  let target = Arc::from_raw(target as *const T);
+  target.wake();
+}
+
+/// Call `target.wake()` on the timer service task, since wakers must not be called from interrupt handlers.
+///
+/// Returns `false` if the timer command queue is full.
+pub(crate) fn pend_wake<T: PendWake>(target: &Arc<T>) -> bool {
+  let target = Arc::into_raw(Arc::clone(target));
+  let mut woken = 0;
+
+  let res = unsafe {
+    xTimerPendFunctionCallFromISR(Some(pended_wake::<T>), target as *mut _, 0, &mut woken)
+  };
+  yield_from_isr(woken);
+
+  if res != PD_TRUE {
+    // SAFETY: This is not the last reference, since the caller still holds one, so nothing is freed here.
+    drop(unsafe { Arc::from_raw(target) });
+    return false
+  }
+
+  true
+}
@@ -0,0 +1,108 @@
+use core::marker::PhantomData;
+use core::ptr;
+use core::time::Duration;
+
+use esp_idf_bindgen::*;
+
+use super::*;
+
+/// The notification value of the current task.
+///
+/// Other tasks and interrupt handlers can notify the current task using a
+/// [`Notifier`](struct.Notifier.html) created from this.
+#[derive(Debug)]
+pub struct Notification {
+  task: TaskHandle_t,
+  _not_send: PhantomData<*const ()>,
+}
+
+impl Notification {
+  /// Receive notifications for the current task.
+  pub fn new() -> Self {
+    Self { task: unsafe { xTaskGetCurrentTaskHandle() }, _not_send: PhantomData }
+  }
+
+  /// Get a handle which can be used to notify the current task.
+  pub fn notifier(&self) -> Notifier {
+    Notifier { task: self.task }
+  }
+
+  /// Wait up to `timeout` for a notification, returning the notification value and clearing it.
+  pub fn wait(&self, timeout: Option<Duration>) -> Option<u32> {
+    let mut value = 0;
+
+    if unsafe { xTaskNotifyWait(0, u32::max_value(), &mut value, ticks(timeout)) } == PD_TRUE {
+      Some(value)
+    } else {
+      None
+    }
+  }
+
+  /// Wait up to `timeout` for the notification value to be non-zero, using it as a counting semaphore.
+  ///
+  /// Returns the value before it was decremented, or cleared if `clear` is `true`. Returns `0` on timeout.
+  pub fn take(&self, clear: bool, timeout: Option<Duration>) -> u32 {
+    unsafe { ulTaskNotifyTake(clear as _, ticks(timeout)) }
+  }
+}
+
+impl Default for Notification {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// A handle used to notify a task waiting on a [`Notification`](struct.Notification.html).
+///
+/// A notifier only holds the task handle, so it can be moved into other threads and interrupt
+/// handlers. FreeRTOS does not check whether a notified task still exists, so notifying is unsafe.
+#[derive(Debug, Clone, Copy)]
+pub struct Notifier {
+  task: TaskHandle_t,
+}
+
+unsafe impl Send for Notifier {}
+unsafe impl Sync for Notifier {}
+
+impl Notifier {
+  /// Set `bits` in the notification value.
+  ///
+  /// # Safety
+  ///
+  /// The task which created the notifier must not have exited, e.g. join threads holding a
+  /// notifier before returning from the task.
+  pub unsafe fn notify(&self, bits: u32) {
+    xTaskGenericNotify(self.task, bits, eNotifyAction::eSetBits, ptr::null_mut());
+  }
+
+  /// Set `bits` in the notification value from an interrupt handler.
+  ///
+  /// # Safety
+  ///
+  /// See [`notify`](#method.notify).
+  pub unsafe fn notify_from_isr(&self, bits: u32) {
+    let mut woken = 0;
+    xTaskGenericNotifyFromISR(self.task, bits, eNotifyAction::eSetBits, ptr::null_mut(), &mut woken);
+    yield_from_isr(woken);
+  }
+
+  /// Increment the notification value, see [`Notification::take`](struct.Notification.html#method.take).
+  ///
+  /// # Safety
+  ///
+  /// See [`notify`](#method.notify).
+  pub unsafe fn give(&self) {
+    xTaskGenericNotify(self.task, 0, eNotifyAction::eIncrement, ptr::null_mut());
+  }
+
+  /// Increment the notification value from an interrupt handler.
+  ///
+  /// # Safety
+  ///
+  /// See [`notify`](#method.notify).
+  pub unsafe fn give_from_isr(&self) {
+    let mut woken = 0;
+    vTaskNotifyGiveFromISR(self.task, &mut woken);
+    yield_from_isr(woken);
+  }
+}
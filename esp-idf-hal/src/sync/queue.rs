@@ -0,0 +1,122 @@
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::time::Duration;
+
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+
+use super::*;
+
+/// A fixed-capacity FreeRTOS queue.
+///
+/// Items are moved into the queue by copying their bytes, so they are owned by the queue until received.
+pub struct Queue<T> {
+  handle: QueueHandle_t,
+  _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T> Queue<T> {
+  /// Create a queue with space for `capacity` items.
+  pub fn new(capacity: usize) -> Result<Self, EspError> {
+    let handle = unsafe { xQueueGenericCreate(capacity as _, mem::size_of::<T>() as _, QUEUE_TYPE_BASE) };
+
+    if handle.is_null() {
+      return Err(EspErrorKind::Generic(GenericErrorKind::NoMem).into())
+    }
+
+    Ok(Self { handle, _marker: PhantomData })
+  }
+
+  /// Append `item`, waiting up to `timeout` for free space. Returns the item back on timeout.
+  pub fn send(&self, item: T, timeout: Option<Duration>) -> Result<(), T> {
+    self.send_generic(item, timeout, QUEUE_SEND_TO_BACK)
+  }
+
+  /// Prepend `item`, waiting up to `timeout` for free space. Returns the item back on timeout.
+  pub fn send_to_front(&self, item: T, timeout: Option<Duration>) -> Result<(), T> {
+    self.send_generic(item, timeout, QUEUE_SEND_TO_FRONT)
+  }
+
+  fn send_generic(&self, item: T, timeout: Option<Duration>, position: BaseType_t) -> Result<(), T> {
+    let item = mem::ManuallyDrop::new(item);
+
+    if unsafe { xQueueGenericSend(self.handle, &*item as *const T as *const _, ticks(timeout), position) } == PD_TRUE {
+      Ok(())
+    } else {
+      Err(mem::ManuallyDrop::into_inner(item))
+    }
+  }
+
+  /// Append `item` from an interrupt handler. Returns the item back if the queue is full.
+  pub fn send_from_isr(&self, item: T) -> Result<(), T> {
+    let item = mem::ManuallyDrop::new(item);
+    let mut woken = 0;
+
+    let res = unsafe {
+      xQueueGenericSendFromISR(self.handle, &*item as *const T as *const _, &mut woken, QUEUE_SEND_TO_BACK)
+    };
+    yield_from_isr(woken);
+
+    if res == PD_TRUE {
+      Ok(())
+    } else {
+      Err(mem::ManuallyDrop::into_inner(item))
+    }
+  }
+
+  /// Remove the first item, waiting up to `timeout` for one to arrive.
+  pub fn receive(&self, timeout: Option<Duration>) -> Option<T> {
+    let mut item = MaybeUninit::<T>::uninit();
+
+    if unsafe { xQueueReceive(self.handle, item.as_mut_ptr() as *mut _, ticks(timeout)) } == PD_TRUE {
+      Some(unsafe { item.assume_init() })
+    } else {
+      None
+    }
+  }
+
+  /// Remove the first item from an interrupt handler.
+  pub fn receive_from_isr(&self) -> Option<T> {
+    let mut item = MaybeUninit::<T>::uninit();
+    let mut woken = 0;
+
+    let res = unsafe { xQueueReceiveFromISR(self.handle, item.as_mut_ptr() as *mut _, &mut woken) };
+    yield_from_isr(woken);
+
+    if res == PD_TRUE {
+      Some(unsafe { item.assume_init() })
+    } else {
+      None
+    }
+  }
+
+  /// Number of items currently in the queue.
+  pub fn len(&self) -> usize {
+    unsafe { uxQueueMessagesWaiting(self.handle) as usize }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+impl<T> Drop for Queue<T> {
+  fn drop(&mut self) {
+    if mem::needs_drop::<T>() {
+      while self.receive(Some(Duration::from_secs(0))).is_some() {}
+    }
+
+    unsafe { vQueueDelete(self.handle) };
+  }
+}
+
+impl<T> fmt::Debug for Queue<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Queue").field("handle", &self.handle).field("len", &self.len()).finish()
+  }
+}
@@ -0,0 +1,80 @@
+use core::ptr;
+use core::time::Duration;
+
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+
+use super::*;
+
+/// A FreeRTOS binary or counting semaphore.
+#[derive(Debug)]
+pub struct Semaphore {
+  handle: QueueHandle_t,
+}
+
+unsafe impl Send for Semaphore {}
+unsafe impl Sync for Semaphore {}
+
+impl Semaphore {
+  /// Create a binary semaphore, which is initially taken.
+  pub fn binary() -> Result<Self, EspError> {
+    Self::from_handle(unsafe { xQueueGenericCreate(1, 0, QUEUE_TYPE_BINARY_SEMAPHORE) })
+  }
+
+  /// Create a counting semaphore with a count of `initial`, which can be given up to `max` times.
+  pub fn counting(max: u32, initial: u32) -> Result<Self, EspError> {
+    if initial > max {
+      return Err(EspErrorKind::Generic(GenericErrorKind::InvalidArg).into())
+    }
+
+    Self::from_handle(unsafe { xQueueCreateCountingSemaphore(max as _, initial as _) })
+  }
+
+  fn from_handle(handle: QueueHandle_t) -> Result<Self, EspError> {
+    if handle.is_null() {
+      return Err(EspErrorKind::Generic(GenericErrorKind::NoMem).into())
+    }
+
+    Ok(Self { handle })
+  }
+
+  /// Take the semaphore, waiting up to `timeout` for it to become available.
+  ///
+  /// Returns `false` on timeout.
+  pub fn take(&self, timeout: Option<Duration>) -> bool {
+    unsafe { xQueueSemaphoreTake(self.handle, ticks(timeout)) == PD_TRUE }
+  }
+
+  /// Take the semaphore from an interrupt handler without waiting.
+  pub fn take_from_isr(&self) -> bool {
+    let mut woken = 0;
+    let res = unsafe { xQueueReceiveFromISR(self.handle, ptr::null_mut(), &mut woken) };
+    yield_from_isr(woken);
+    res == PD_TRUE
+  }
+
+  /// Give the semaphore. Returns `false` if its count is already at the maximum.
+  pub fn give(&self) -> bool {
+    unsafe { xQueueGenericSend(self.handle, ptr::null(), 0, QUEUE_SEND_TO_BACK) == PD_TRUE }
+  }
+
+  /// Give the semaphore from an interrupt handler.
+  pub fn give_from_isr(&self) -> bool {
+    let mut woken = 0;
+    let res = unsafe { xQueueGiveFromISR(self.handle, &mut woken) };
+    yield_from_isr(woken);
+    res == PD_TRUE
+  }
+
+  /// The current count, i.e. how many times the semaphore can be taken without waiting.
+  pub fn count(&self) -> u32 {
+    unsafe { uxQueueMessagesWaiting(self.handle) as u32 }
+  }
+}
+
+impl Drop for Semaphore {
+  fn drop(&mut self) {
+    unsafe { vQueueDelete(self.handle) };
+  }
+}
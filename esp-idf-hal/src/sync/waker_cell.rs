@@ -0,0 +1,38 @@
+use core::task::Waker;
+use std::sync::Mutex;
+
+/// A waker registered by a future and woken by a driver task or, using [`pend_wake`](fn.pend_wake.html),
+/// by an interrupt handler.
+///
+/// Futures should register their waker before checking for completion, so that an event in between is not missed.
+#[derive(Debug, Default)]
+pub(crate) struct WakerCell {
+  waker: Mutex<Option<Waker>>,
+}
+
+impl WakerCell {
+  /// Store `waker`, unless it would wake the same task as the stored one.
+  pub fn register(&self, waker: &Waker) {
+    let mut current = self.waker.lock().unwrap_or_else(|err| err.into_inner());
+
+    match &*current {
+      Some(current) if current.will_wake(waker) => (),
+      _ => *current = Some(waker.clone()),
+    }
+  }
+
+  /// Remove the stored waker without waking it.
+  pub fn clear(&self) {
+    self.waker.lock().unwrap_or_else(|err| err.into_inner()).take();
+  }
+
+  /// Wake and remove the stored waker. Must not be called from an interrupt handler.
+  pub fn wake(&self) {
+    // Wake after releasing the lock, since waking may poll the future on this task.
+    let waker = self.waker.lock().unwrap_or_else(|err| err.into_inner()).take();
+
+    if let Some(waker) = waker {
+      waker.wake();
+    }
+  }
+}
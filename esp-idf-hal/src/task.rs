@@ -0,0 +1,232 @@
+//! FreeRTOS-aware threads.
+//!
+//! Threads spawned using a [`Builder`](struct.Builder.html) are normal `std` threads, but can
+//! additionally be given a priority, be pinned to a core and report their stack usage.
+
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+use std::ffi::CString;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+
+/// A CPU core of the ESP32.
+#[cfg(target_device = "esp32")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Core {
+  /// The protocol CPU (core 0), which also runs the WiFi stack.
+  Pro = 0,
+  /// The application CPU (core 1).
+  App = 1,
+}
+
+/// Minimum free stack space in bytes since the current task was started.
+pub fn stack_high_water_mark() -> usize {
+  unsafe { uxTaskGetStackHighWaterMark(ptr::null_mut()) as usize * mem::size_of::<StackType_t>() }
+}
+
+/// Thread factory which allows configuring FreeRTOS task properties.
+///
+/// ```ignore
+/// let handle = task::Builder::new()
+///   .name("worker")
+///   .stack_size(4096)
+///   .priority(5)
+///   .pinned_to_core(task::Core::App)
+///   .spawn(|| { /* … */ })?;
+///
+/// println!("Stack high-water mark: {:?}", handle.stack_high_water_mark());
+///
+/// let (result, high_water_mark) = handle.join_with_high_water_mark();
+/// ```
+#[derive(Debug, Default)]
+pub struct Builder {
+  name: Option<String>,
+  stack_size: Option<usize>,
+  priority: Option<u8>,
+  #[cfg(target_device = "esp32")]
+  core: Option<Core>,
+}
+
+impl Builder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Name of the thread, which is also used as the FreeRTOS task name.
+  pub fn name(mut self, name: impl Into<String>) -> Self {
+    self.name = Some(name.into());
+    self
+  }
+
+  /// Stack size in bytes.
+  pub fn stack_size(mut self, size: usize) -> Self {
+    self.stack_size = Some(size);
+    self
+  }
+
+  /// FreeRTOS task priority, must be lower than `configMAX_PRIORITIES`.
+  pub fn priority(mut self, priority: u8) -> Self {
+    self.priority = Some(priority);
+    self
+  }
+
+  /// Only run the thread on the given core.
+  #[cfg(target_device = "esp32")]
+  pub fn pinned_to_core(mut self, core: Core) -> Self {
+    self.core = Some(core);
+    self
+  }
+
+  /// Spawn a new thread.
+  pub fn spawn<F, T>(self, f: F) -> io::Result<JoinHandle<T>>
+  where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+  {
+    if let Some(priority) = self.priority {
+      if u32::from(priority) >= configMAX_PRIORITIES {
+        let err = EspError::from(EspErrorKind::Generic(GenericErrorKind::InvalidArg));
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, err))
+      }
+    }
+
+    let mut builder = thread::Builder::new();
+
+    if let Some(name) = &self.name {
+      builder = builder.name(name.clone());
+    }
+
+    if let Some(stack_size) = self.stack_size {
+      builder = builder.stack_size(stack_size);
+    }
+
+    let state = Arc::new(Mutex::new(TaskState::Starting));
+    let task_state = Arc::clone(&state);
+    let priority = self.priority;
+
+    let f = move || {
+      if let Some(priority) = priority {
+        unsafe { vTaskPrioritySet(ptr::null_mut(), priority.into()) };
+      }
+
+      *task_state.lock().unwrap_or_else(|err| err.into_inner()) = TaskState::Running(unsafe { xTaskGetCurrentTaskHandle() });
+
+      // Record the final high-water mark even if `f` panics, the task is deleted afterwards.
+      let _guard = FinishGuard(task_state);
+      f()
+    };
+
+    #[cfg(target_device = "esp32")]
+    let handle = {
+      let name = self.name.map(CString::new).transpose().map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+      let _cfg = PthreadCfg::set(name.as_deref(), self.core)?;
+      builder.spawn(f)?
+    };
+
+    #[cfg(not(target_device = "esp32"))]
+    let handle = builder.spawn(f)?;
+
+    Ok(JoinHandle { handle, state })
+  }
+}
+
+#[derive(Debug)]
+enum TaskState {
+  Starting,
+  Running(TaskHandle_t),
+  Finished(usize),
+}
+
+unsafe impl Send for TaskState {}
+
+struct FinishGuard(Arc<Mutex<TaskState>>);
+
+impl Drop for FinishGuard {
+  fn drop(&mut self) {
+    let mut state = self.0.lock().unwrap_or_else(|err| err.into_inner());
+    *state = TaskState::Finished(stack_high_water_mark());
+  }
+}
+
+/// Temporarily applies an `esp_pthread_cfg_t` for the current thread, restoring the previous one when dropped.
+#[cfg(target_device = "esp32")]
+struct PthreadCfg {
+  previous: esp_pthread_cfg_t,
+}
+
+#[cfg(target_device = "esp32")]
+impl PthreadCfg {
+  fn set(name: Option<&std::ffi::CStr>, core: Option<Core>) -> io::Result<Self> {
+    let to_io_error = |err: EspError| io::Error::new(io::ErrorKind::Other, err);
+
+    let mut previous = MaybeUninit::<esp_pthread_cfg_t>::uninit();
+    let previous = match esp_ok!(esp_pthread_get_cfg(previous.as_mut_ptr())) {
+      Ok(()) => unsafe { previous.assume_init() },
+      Err(_) => unsafe { esp_pthread_get_default_config() },
+    };
+
+    let mut cfg = previous;
+    cfg.inherit_cfg = false;
+    cfg.thread_name = name.map_or(ptr::null(), |name| name.as_ptr());
+    cfg.pin_to_core = core.map_or(tskNO_AFFINITY as _, |core| core as _);
+    esp_ok!(esp_pthread_set_cfg(&cfg)).map_err(to_io_error)?;
+
+    Ok(Self { previous })
+  }
+}
+
+#[cfg(target_device = "esp32")]
+impl Drop for PthreadCfg {
+  fn drop(&mut self) {
+    let _ = esp_ok!(esp_pthread_set_cfg(&self.previous));
+  }
+}
+
+/// An owned permission to join on a thread spawned by a [`Builder`](struct.Builder.html).
+#[derive(Debug)]
+pub struct JoinHandle<T> {
+  handle: thread::JoinHandle<T>,
+  state: Arc<Mutex<TaskState>>,
+}
+
+impl<T> JoinHandle<T> {
+  pub fn thread(&self) -> &thread::Thread {
+    self.handle.thread()
+  }
+
+  /// Wait for the thread to finish.
+  pub fn join(self) -> thread::Result<T> {
+    self.handle.join()
+  }
+
+  /// Wait for the thread to finish and also return its final stack high-water mark in bytes.
+  pub fn join_with_high_water_mark(self) -> (thread::Result<T>, usize) {
+    let state = Arc::clone(&self.state);
+    let result = self.handle.join();
+
+    // The state is always `Finished` here, since the guard is dropped before the thread exits.
+    let high_water_mark = match *state.lock().unwrap_or_else(|err| err.into_inner()) {
+      TaskState::Finished(high_water_mark) => high_water_mark,
+      _ => unreachable!(),
+    };
+
+    (result, high_water_mark)
+  }
+
+  /// Minimum free stack space in bytes since the thread was started.
+  ///
+  /// Once the thread has finished, this is the final value. Returns `None` if the thread has not started yet.
+  pub fn stack_high_water_mark(&self) -> Option<usize> {
+    // The state is locked while querying, so the task cannot finish in the meantime.
+    match *self.state.lock().unwrap_or_else(|err| err.into_inner()) {
+      TaskState::Starting => None,
+      TaskState::Running(handle) => Some(unsafe { uxTaskGetStackHighWaterMark(handle) as usize * mem::size_of::<StackType_t>() }),
+      TaskState::Finished(high_water_mark) => Some(high_water_mark),
+    }
+  }
+}
@@ -0,0 +1,278 @@
+//! High-resolution timers using `esp_timer`.
+//!
+//! Timer callbacks run on the `esp_timer` task, which also wakes the futures
+//! returned by [`Delay`](struct.Delay.html) and [`Interval`](struct.Interval.html).
+
+use core::fmt;
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll};
+use core::time::Duration;
+use std::sync::{Arc, Mutex};
+
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use esp_idf_bindgen::*;
+
+use crate::EspError;
+use crate::sync::WakerCell;
+
+/// Time since boot.
+pub fn uptime() -> Duration {
+  Duration::from_micros(unsafe { esp_timer_get_time() } as u64)
+}
+
+fn as_micros(duration: Duration) -> u64 {
+  duration.as_micros().min(u128::from(u64::max_value())) as u64
+}
+
+type Callback = Box<dyn FnMut() + Send + 'static>;
+
+enum State {
+  Active(Callback),
+  /// The `Timer` was dropped, the next invocation deletes the timer and frees the state.
+  Dropped(esp_timer_handle_t),
+}
+
+extern "C" fn timer_callback(arg: *mut libc::c_void) {
+  let state_ptr = arg as *mut Mutex<State>;
+  // SAFETY: The state is only freed below, by the last invocation of this timer.
+  let mut state = unsafe { &*state_ptr }.lock().unwrap_or_else(|err| err.into_inner());
+
+  match &mut *state {
+    State::Active(callback) => callback(),
+    State::Dropped(handle) => {
+      // An invocation dispatched before the timer was stopped sees the timer re-armed by
+      // `Timer::drop`, so deleting it fails and the state is kept for the final invocation.
+      if esp_ok!(esp_timer_delete(*handle)).is_ok() {
+        drop(state);
+        drop(unsafe { Box::from_raw(state_ptr) });
+      }
+    },
+  }
+}
+
+/// A one-shot or periodic `esp_timer` which calls a closure on the `esp_timer` task.
+///
+/// Dropping the timer waits for a running callback to return, so a timer must not be dropped from its own callback.
+pub struct Timer {
+  handle: esp_timer_handle_t,
+  state: *const Mutex<State>,
+}
+
+unsafe impl Send for Timer {}
+unsafe impl Sync for Timer {}
+
+impl Timer {
+  /// Create a stopped timer which calls `callback` each time it expires.
+  ///
+  /// The callback should return quickly, since it delays all other timers.
+  pub fn new<F>(callback: F) -> Result<Self, EspError>
+  where
+    F: FnMut() + Send + 'static,
+  {
+    let state = Box::into_raw(Box::new(Mutex::new(State::Active(Box::new(callback)))));
+
+    let mut args: esp_timer_create_args_t = unsafe { crate::zeroed_config() };
+    args.callback = Some(timer_callback);
+    args.arg = state as *mut _;
+    args.name = b"rust_timer\0".as_ptr() as *const _;
+
+    let mut handle = core::ptr::null_mut();
+    if let Err(err) = esp_ok!(esp_timer_create(&args, &mut handle)) {
+      drop(unsafe { Box::from_raw(state) });
+      return Err(err)
+    }
+
+    Ok(Self { handle, state })
+  }
+
+  /// Start the timer so it expires once after `timeout`.
+  pub fn start_once(&self, timeout: Duration) -> Result<(), EspError> {
+    esp_ok!(esp_timer_start_once(self.handle, as_micros(timeout)))
+  }
+
+  /// Start the timer so it expires every `period`.
+  pub fn start_periodic(&self, period: Duration) -> Result<(), EspError> {
+    esp_ok!(esp_timer_start_periodic(self.handle, as_micros(period)))
+  }
+
+  /// Stop the timer. Returns an error if it is not running.
+  pub fn stop(&self) -> Result<(), EspError> {
+    esp_ok!(esp_timer_stop(self.handle))
+  }
+}
+
+impl Drop for Timer {
+  fn drop(&mut self) {
+    let _ = esp_ok!(esp_timer_stop(self.handle));
+
+    // Stopping the timer does not wait for an invocation which was already dispatched, so the
+    // timer cannot be deleted here. Instead, locking the state waits for a running callback, and
+    // the timer is re-armed so that its final invocation on the `esp_timer` task, which runs after
+    // any earlier one, deletes it.
+    let mut state = unsafe { &*self.state }.lock().unwrap_or_else(|err| err.into_inner());
+    let callback = mem::replace(&mut *state, State::Dropped(self.handle));
+
+    // If re-arming fails, the timer and its state are leaked rather than risking a use after free.
+    let _ = esp_ok!(esp_timer_start_once(self.handle, 0));
+
+    drop(state);
+    drop(callback);
+  }
+}
+
+impl fmt::Debug for Timer {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Timer").field("handle", &self.handle).finish()
+  }
+}
+
+/// State shared between a future and its timer callback.
+#[derive(Debug, Default)]
+struct Shared {
+  expirations: AtomicU32,
+  waker: WakerCell,
+}
+
+impl Shared {
+  fn expire(&self) {
+    self.expirations.fetch_add(1, Ordering::AcqRel);
+    self.waker.wake();
+  }
+}
+
+fn shared_timer() -> Result<(Timer, Arc<Shared>), EspError> {
+  let shared = Arc::new(Shared::default());
+  let callback_shared = Arc::clone(&shared);
+  let timer = Timer::new(move || callback_shared.expire())?;
+  Ok((timer, shared))
+}
+
+/// A future which completes after a duration.
+///
+/// The timer starts when the future is first polled. Unlike [`executor::sleep`](../executor/fn.sleep.html),
+/// this works with any executor and has microsecond resolution.
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct Delay {
+  timer: Timer,
+  shared: Arc<Shared>,
+  duration: Duration,
+  started: bool,
+}
+
+impl Delay {
+  pub fn new(duration: Duration) -> Result<Self, EspError> {
+    let (timer, shared) = shared_timer()?;
+    Ok(Self { timer, shared, duration, started: false })
+  }
+}
+
+impl Future for Delay {
+  type Output = ();
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    // Register the waker before checking, so an expiration in between is not missed.
+    self.shared.waker.register(cx.waker());
+
+    if self.shared.expirations.load(Ordering::Acquire) > 0 {
+      return Poll::Ready(())
+    }
+
+    if !self.started {
+      self.timer.start_once(self.duration).expect("failed to start timer");
+      self.started = true;
+    }
+
+    Poll::Pending
+  }
+}
+
+/// A periodic timer, see [`Interval::tick`](#method.tick).
+#[derive(Debug)]
+pub struct Interval {
+  timer: Timer,
+  shared: Arc<Shared>,
+}
+
+impl Interval {
+  /// Create an interval which ticks every `period`, starting one `period` from now.
+  pub fn new(period: Duration) -> Result<Self, EspError> {
+    let (timer, shared) = shared_timer()?;
+    timer.start_periodic(period)?;
+    Ok(Self { timer, shared })
+  }
+
+  /// Wait for the next tick.
+  ///
+  /// Resolves to the number of periods elapsed since the previous tick, which is greater than `1` if ticks were missed.
+  pub fn tick(&mut self) -> Tick<'_> {
+    Tick { interval: self }
+  }
+}
+
+/// A future returned by [`Interval::tick`](struct.Interval.html#method.tick).
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct Tick<'i> {
+  interval: &'i mut Interval,
+}
+
+impl Future for Tick<'_> {
+  type Output = u32;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    let shared = &self.interval.shared;
+    shared.waker.register(cx.waker());
+
+    match shared.expirations.swap(0, Ordering::AcqRel) {
+      0 => Poll::Pending,
+      n => Poll::Ready(n),
+    }
+  }
+}
+
+/// A blocking delay implementing the `embedded-hal` delay traits.
+///
+/// Whole FreeRTOS ticks are spent in `vTaskDelay`, allowing other tasks to run,
+/// the remainder is busy-waited.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BlockingDelay;
+
+impl BlockingDelay {
+  fn delay(us: u64) {
+    let tick_us = 1_000_000 / u64::from(configTICK_RATE_HZ);
+    let ticks = us / tick_us;
+
+    if ticks > 0 {
+      unsafe { vTaskDelay(ticks.min(u64::from(TickType_t::max_value() - 1)) as TickType_t) };
+    }
+
+    let remainder = us - ticks * tick_us;
+    if remainder > 0 {
+      unsafe { ets_delay_us(remainder as u32) };
+    }
+  }
+}
+
+macro_rules! impl_delay {
+  ($($ty:ty),*) => {
+    $(
+      impl DelayUs<$ty> for BlockingDelay {
+        fn delay_us(&mut self, us: $ty) {
+          Self::delay(u64::from(us));
+        }
+      }
+
+      impl DelayMs<$ty> for BlockingDelay {
+        fn delay_ms(&mut self, ms: $ty) {
+          Self::delay(u64::from(ms) * 1000);
+        }
+      }
+    )*
+  }
+}
+
+impl_delay!(u8, u16, u32);
@@ -0,0 +1,277 @@
+//! Capacitive touch sensing using the IDF `touch_pad` driver.
+//!
+//! The [`Touch`](struct.Touch.html) driver measures all configured pads periodically in hardware.
+//! A [`TouchPad`](struct.TouchPad.html) is touched while its reading is below its threshold.
+//!
+//! ```ignore
+//! let pins = gpio::Pins::take().unwrap();
+//!
+//! let touch = touch::Touch::new(touch::Config::new())?;
+//! let mut button = touch.pad(pins.gpio4, 0)?;
+//!
+//! // Calibrate the threshold to two thirds of the untouched reading.
+//! let untouched = button.read_filtered()?;
+//! button.set_threshold(untouched * 2 / 3)?;
+//!
+//! button.wait_for_touch().await;
+//! ```
+
+use core::future::Future;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll};
+use core::time::Duration;
+use std::sync::Arc;
+
+use esp_idf_bindgen::*;
+
+use crate::EspError;
+use crate::gpio;
+use crate::sync::{pend_wake, PendWake, WakerCell};
+
+const PAD_COUNT: usize = 10;
+
+pub(crate) mod sealed {
+  use esp_idf_bindgen::touch_pad_t;
+
+  pub trait TouchPin {
+    const PAD: touch_pad_t;
+  }
+}
+
+/// A GPIO pin connected to a touch pad.
+pub trait TouchPin: gpio::Pin + sealed::TouchPin {}
+
+macro_rules! touch_pins {
+  ($($Gpio:ident => $pad:ident,)*) => {
+    $(
+      impl<MODE> sealed::TouchPin for gpio::$Gpio<MODE> {
+        const PAD: touch_pad_t = touch_pad_t::$pad;
+      }
+
+      impl<MODE> TouchPin for gpio::$Gpio<MODE> {}
+    )*
+  };
+}
+
+touch_pins! {
+  Gpio4 => TOUCH_PAD_NUM0,
+  Gpio0 => TOUCH_PAD_NUM1,
+  Gpio2 => TOUCH_PAD_NUM2,
+  Gpio15 => TOUCH_PAD_NUM3,
+  Gpio13 => TOUCH_PAD_NUM4,
+  Gpio12 => TOUCH_PAD_NUM5,
+  Gpio14 => TOUCH_PAD_NUM6,
+  Gpio27 => TOUCH_PAD_NUM7,
+  Gpio33 => TOUCH_PAD_NUM8,
+  Gpio32 => TOUCH_PAD_NUM9,
+}
+
+/// Configuration for the [`Touch`](struct.Touch.html) driver.
+#[derive(Debug, Clone)]
+pub struct Config {
+  sleep_cycles: u16,
+  measure_cycles: u16,
+  filter_period: Option<Duration>,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self { sleep_cycles: 0x1000, measure_cycles: 0x7fff, filter_period: Some(Duration::from_millis(10)) }
+  }
+}
+
+impl Config {
+  /// The default measurement timing of the driver, with readings filtered every 10 ms.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Cycles of the 150 kHz RTC slow clock between measurements.
+  pub fn sleep_cycles(mut self, cycles: u16) -> Self {
+    self.sleep_cycles = cycles;
+    self
+  }
+
+  /// Cycles of the 8 MHz clock per measurement. Longer measurements give higher readings.
+  pub fn measure_cycles(mut self, cycles: u16) -> Self {
+    self.measure_cycles = cycles;
+    self
+  }
+
+  /// Period of the IIR filter used by [`TouchPad::read_filtered`](struct.TouchPad.html#method.read_filtered).
+  /// `None` disables the filter.
+  pub fn filter_period(mut self, period: Option<Duration>) -> Self {
+    self.filter_period = period;
+    self
+  }
+}
+
+/// State shared between the [`Touch`](struct.Touch.html) driver, its pads and the interrupt handler.
+#[derive(Debug)]
+struct TouchState {
+  touched: AtomicU32,
+  wakers: [WakerCell; PAD_COUNT],
+}
+
+impl PendWake for TouchState {
+  fn wake(&self) {
+    let touched = self.touched.load(Ordering::Acquire);
+
+    for (i, waker) in self.wakers.iter().enumerate() {
+      if touched & 1 << i != 0 {
+        waker.wake();
+      }
+    }
+  }
+}
+
+extern "C" fn touch_isr(arg: *mut libc::c_void) {
+  // SAFETY: `arg` was created using `Arc::as_ptr` in `Touch::new`, and the
+  // driver holds a reference until the handler is deregistered.
+  let state = ManuallyDrop::new(unsafe { Arc::from_raw(arg as *const TouchState) });
+
+  let status = unsafe { touch_pad_get_status() };
+  unsafe { touch_pad_clear_status() };
+
+  if status != 0 {
+    state.touched.fetch_or(status, Ordering::AcqRel);
+    pend_wake(&state);
+  }
+}
+
+/// The touch pad driver.
+#[derive(Debug)]
+pub struct Touch {
+  state: Arc<TouchState>,
+}
+
+impl Touch {
+  /// Initialize the driver and start measuring in the background.
+  pub fn new(config: Config) -> Result<Self, EspError> {
+    esp_ok!(touch_pad_init())?;
+
+    let state = Arc::new(TouchState { touched: AtomicU32::new(0), wakers: Default::default() });
+
+    // Deinitializes the driver if configuring fails.
+    let touch = Self { state };
+
+    esp_ok!(touch_pad_set_meas_time(config.sleep_cycles, config.measure_cycles))?;
+    esp_ok!(touch_pad_set_voltage(
+      touch_high_volt_t::TOUCH_HVOLT_2V7,
+      touch_low_volt_t::TOUCH_LVOLT_0V5,
+      touch_volt_atten_t::TOUCH_HVOLT_ATTEN_1V,
+    ))?;
+    esp_ok!(touch_pad_set_fsm_mode(touch_fsm_mode_t::TOUCH_FSM_MODE_TIMER))?;
+    esp_ok!(touch_pad_set_trigger_mode(touch_trigger_mode_t::TOUCH_TRIGGER_BELOW))?;
+
+    if let Some(period) = config.filter_period {
+      esp_ok!(touch_pad_filter_start(period.as_millis() as u32))?;
+    }
+
+    esp_ok!(touch_pad_isr_register(Some(touch_isr), Arc::as_ptr(&touch.state) as *mut _))?;
+    esp_ok!(touch_pad_intr_enable())?;
+
+    Ok(touch)
+  }
+
+  /// Configure `pin` as a touch pad with an initial `threshold`. A threshold of 0 never triggers.
+  pub fn pad<P: TouchPin>(&self, pin: P, threshold: u16) -> Result<TouchPad<'_, P>, EspError> {
+    esp_ok!(touch_pad_config(P::PAD, threshold))?;
+    Ok(TouchPad { pin, state: &self.state })
+  }
+}
+
+impl Drop for Touch {
+  fn drop(&mut self) {
+    let _ = esp_ok!(touch_pad_intr_disable());
+    let _ = esp_ok!(touch_pad_isr_deregister(Some(touch_isr), Arc::as_ptr(&self.state) as *mut _));
+    let _ = esp_ok!(touch_pad_filter_delete());
+    let _ = esp_ok!(touch_pad_deinit());
+  }
+}
+
+/// A pin configured as a touch pad by the [`Touch`](struct.Touch.html) driver.
+#[derive(Debug)]
+pub struct TouchPad<'t, P> {
+  pin: P,
+  state: &'t TouchState,
+}
+
+impl<P: TouchPin> TouchPad<'_, P> {
+  /// The latest unfiltered reading. Lower readings mean higher capacitance.
+  pub fn read_raw(&self) -> Result<u16, EspError> {
+    let mut value = 0;
+    esp_ok!(touch_pad_read_raw_data(P::PAD, &mut value))?;
+    Ok(value)
+  }
+
+  /// The latest filtered reading, which requires a [`filter_period`](struct.Config.html#method.filter_period).
+  pub fn read_filtered(&self) -> Result<u16, EspError> {
+    let mut value = 0;
+    esp_ok!(touch_pad_read_filtered(P::PAD, &mut value))?;
+    Ok(value)
+  }
+
+  /// The current threshold.
+  pub fn threshold(&self) -> Result<u16, EspError> {
+    let mut threshold = 0;
+    esp_ok!(touch_pad_get_thresh(P::PAD, &mut threshold))?;
+    Ok(threshold)
+  }
+
+  /// Set the threshold below which the pad counts as touched.
+  pub fn set_threshold(&mut self, threshold: u16) -> Result<(), EspError> {
+    esp_ok!(touch_pad_set_thresh(P::PAD, threshold))
+  }
+
+  /// Wait for a measurement below the threshold.
+  ///
+  /// Measurements before the first poll are ignored. While the pad stays touched,
+  /// every new future resolves after the next measurement.
+  pub fn wait_for_touch(&mut self) -> TouchFuture<'_> {
+    TouchFuture { index: P::PAD as usize, state: self.state, polled: false, _pad: PhantomData }
+  }
+
+  /// Release the pin. The pad keeps being measured until the driver is dropped.
+  pub fn into_inner(self) -> P {
+    self.pin
+  }
+}
+
+/// A future returned by [`TouchPad::wait_for_touch`](struct.TouchPad.html#method.wait_for_touch).
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct TouchFuture<'p> {
+  index: usize,
+  state: &'p TouchState,
+  polled: bool,
+  _pad: PhantomData<&'p mut ()>,
+}
+
+impl Future for TouchFuture<'_> {
+  type Output = ();
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    let bit = 1 << self.index;
+
+    self.state.wakers[self.index].register(cx.waker());
+
+    let touched = self.state.touched.fetch_and(!bit, Ordering::AcqRel) & bit != 0;
+
+    if touched && self.polled {
+      return Poll::Ready(())
+    }
+
+    self.polled = true;
+    Poll::Pending
+  }
+}
+
+impl Drop for TouchFuture<'_> {
+  fn drop(&mut self) {
+    self.state.wakers[self.index].clear();
+  }
+}
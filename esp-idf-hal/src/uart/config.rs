@@ -0,0 +1,217 @@
+use esp_idf_bindgen::*;
+
+#[cfg(target_device = "esp32")]
+use crate::gpio::{IoPin, Pin};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataBits {
+  Five,
+  Six,
+  Seven,
+  Eight,
+}
+
+impl From<DataBits> for uart_word_length_t {
+  fn from(data_bits: DataBits) -> Self {
+    match data_bits {
+      DataBits::Five => uart_word_length_t::UART_DATA_5_BITS,
+      DataBits::Six => uart_word_length_t::UART_DATA_6_BITS,
+      DataBits::Seven => uart_word_length_t::UART_DATA_7_BITS,
+      DataBits::Eight => uart_word_length_t::UART_DATA_8_BITS,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+  None,
+  Even,
+  Odd,
+}
+
+impl From<Parity> for uart_parity_t {
+  fn from(parity: Parity) -> Self {
+    match parity {
+      Parity::None => uart_parity_t::UART_PARITY_DISABLE,
+      Parity::Even => uart_parity_t::UART_PARITY_EVEN,
+      Parity::Odd => uart_parity_t::UART_PARITY_ODD,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+  One,
+  OneAndHalf,
+  Two,
+}
+
+impl From<StopBits> for uart_stop_bits_t {
+  fn from(stop_bits: StopBits) -> Self {
+    match stop_bits {
+      StopBits::One => uart_stop_bits_t::UART_STOP_BITS_1,
+      StopBits::OneAndHalf => uart_stop_bits_t::UART_STOP_BITS_1_5,
+      StopBits::Two => uart_stop_bits_t::UART_STOP_BITS_2,
+    }
+  }
+}
+
+/// Hardware flow control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowControl {
+  None,
+  /// Deassert RTS when the RX FIFO is almost full.
+  Rts,
+  /// Pause transmitting while CTS is deasserted.
+  Cts,
+  RtsCts,
+}
+
+impl From<FlowControl> for uart_hw_flowcontrol_t {
+  fn from(flow_control: FlowControl) -> Self {
+    match flow_control {
+      FlowControl::None => uart_hw_flowcontrol_t::UART_HW_FLOWCTRL_DISABLE,
+      FlowControl::Rts => uart_hw_flowcontrol_t::UART_HW_FLOWCTRL_RTS,
+      FlowControl::Cts => uart_hw_flowcontrol_t::UART_HW_FLOWCTRL_CTS,
+      FlowControl::RtsCts => uart_hw_flowcontrol_t::UART_HW_FLOWCTRL_CTS_RTS,
+    }
+  }
+}
+
+/// Number of bytes in the RX FIFO at which RTS is deasserted.
+const RX_FLOW_CONTROL_THRESHOLD: u8 = 122;
+
+/// `UART_PIN_NO_CHANGE`, which is defined using a cast and therefore not generated.
+#[cfg(target_device = "esp32")]
+pub(crate) const PIN_NO_CHANGE: libc::c_int = -1;
+
+/// Configuration for a [`Uart`](struct.Uart.html).
+///
+/// ```ignore
+/// let config = uart::Config::new()
+///   .baud_rate(9600)
+///   .parity(Parity::Even)
+///   .pins(pins.gpio17, pins.gpio16);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+  pub(crate) baud_rate: u32,
+  pub(crate) data_bits: DataBits,
+  pub(crate) parity: Parity,
+  pub(crate) stop_bits: StopBits,
+  pub(crate) flow_control: FlowControl,
+  pub(crate) rx_buffer_size: usize,
+  pub(crate) tx_buffer_size: usize,
+  pub(crate) event_queue_size: usize,
+  #[cfg(target_device = "esp32")]
+  pub(crate) pins: [libc::c_int; 4],
+  #[cfg(target_device = "esp8266")]
+  pub(crate) swap_pins: bool,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      baud_rate: 115_200,
+      data_bits: DataBits::Eight,
+      parity: Parity::None,
+      stop_bits: StopBits::One,
+      flow_control: FlowControl::None,
+      rx_buffer_size: 256,
+      tx_buffer_size: 0,
+      event_queue_size: 16,
+      #[cfg(target_device = "esp32")]
+      pins: [PIN_NO_CHANGE; 4],
+      #[cfg(target_device = "esp8266")]
+      swap_pins: false,
+    }
+  }
+}
+
+impl Config {
+  /// 115200 baud, 8N1 without flow control.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn baud_rate(mut self, baud_rate: u32) -> Self {
+    self.baud_rate = baud_rate;
+    self
+  }
+
+  pub fn data_bits(mut self, data_bits: DataBits) -> Self {
+    self.data_bits = data_bits;
+    self
+  }
+
+  pub fn parity(mut self, parity: Parity) -> Self {
+    self.parity = parity;
+    self
+  }
+
+  pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+    self.stop_bits = stop_bits;
+    self
+  }
+
+  pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+    self.flow_control = flow_control;
+    self
+  }
+
+  /// Size of the RX ring buffer in bytes, must be greater than the hardware FIFO (128 bytes).
+  pub fn rx_buffer_size(mut self, size: usize) -> Self {
+    self.rx_buffer_size = size;
+    self
+  }
+
+  /// Size of the TX ring buffer in bytes. If `0`, writes block until all data is in the hardware FIFO.
+  pub fn tx_buffer_size(mut self, size: usize) -> Self {
+    self.tx_buffer_size = size;
+    self
+  }
+
+  /// Maximum number of undelivered [`Event`](enum.Event.html)s, at least `1`.
+  ///
+  /// Pending reads are woken by events, so the queue cannot be disabled.
+  pub fn event_queue_size(mut self, size: usize) -> Self {
+    self.event_queue_size = size.max(1);
+    self
+  }
+
+  /// Route TX and RX through the GPIO matrix, e.g. to move the console UART to other pins.
+  #[cfg(target_device = "esp32")]
+  pub fn pins(mut self, tx: impl IoPin, rx: impl Pin) -> Self {
+    self.pins[0] = tx.number().into();
+    self.pins[1] = rx.number().into();
+    self
+  }
+
+  /// Route RTS and CTS through the GPIO matrix.
+  #[cfg(target_device = "esp32")]
+  pub fn flow_control_pins(mut self, rts: impl IoPin, cts: impl Pin) -> Self {
+    self.pins[2] = rts.number().into();
+    self.pins[3] = cts.number().into();
+    self
+  }
+
+  /// Swap UART0 to GPIO 15 (TX) and GPIO 13 (RX), e.g. to keep the console pins free for a modem.
+  ///
+  /// The pins stay swapped after the `Uart` is dropped.
+  #[cfg(target_device = "esp8266")]
+  pub fn swap_pins(mut self, swap: bool) -> Self {
+    self.swap_pins = swap;
+    self
+  }
+
+  pub(crate) fn to_uart_config(&self) -> uart_config_t {
+    let mut config: uart_config_t = unsafe { crate::zeroed_config() };
+    config.baud_rate = self.baud_rate as _;
+    config.data_bits = self.data_bits.into();
+    config.parity = self.parity.into();
+    config.stop_bits = self.stop_bits.into();
+    config.flow_ctrl = self.flow_control.into();
+    config.rx_flow_ctrl_thresh = RX_FLOW_CONTROL_THRESHOLD;
+    config
+  }
+}
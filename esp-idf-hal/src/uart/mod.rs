@@ -0,0 +1,400 @@
+//! UART driver using the IDF `uart` driver.
+//!
+//! A [`Uart`](struct.Uart.html) can be used with blocking `std::io` or `embedded-hal` APIs,
+//! or read from asynchronously, e.g. on the [`executor`](../executor/index.html):
+//!
+//! ```ignore
+//! let config = uart::Config::new().baud_rate(9600).rx_buffer_size(1024);
+//! let mut modem = Uart::new(Port::Uart1, config)?;
+//!
+//! modem.write_all(b"AT\r\n")?;
+//!
+//! let mut buf = [0; 64];
+//! let n = modem.read_async(&mut buf).await?;
+//! ```
+//!
+//! Writes always block, since the driver reports no event when TX buffer space becomes
+//! available. With a [TX buffer](struct.Config.html#method.tx_buffer_size), they only
+//! block while it is full.
+
+use core::future::Future;
+use core::mem::MaybeUninit;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::io;
+use std::sync::Arc;
+
+use esp_idf_bindgen::*;
+
+use crate::{EspError, EspErrorKind, GenericErrorKind};
+use crate::sync::{self, channel, Receiver, Recv, WakerCell, PD_TRUE};
+use crate::task;
+
+mod config;
+pub use config::*;
+
+/// A UART controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+  Uart0,
+  Uart1,
+  #[cfg(target_device = "esp32")]
+  Uart2,
+}
+
+impl From<Port> for uart_port_t {
+  #[cfg(target_device = "esp32")]
+  fn from(port: Port) -> Self {
+    port as uart_port_t
+  }
+
+  #[cfg(target_device = "esp8266")]
+  fn from(port: Port) -> Self {
+    match port {
+      Port::Uart0 => uart_port_t::UART_NUM_0,
+      Port::Uart1 => uart_port_t::UART_NUM_1,
+    }
+  }
+}
+
+/// The UART used for the console, as configured in `sdkconfig`.
+pub fn console_port() -> Port {
+  match CONFIG_CONSOLE_UART_NUM {
+    1 => Port::Uart1,
+    #[cfg(target_device = "esp32")]
+    2 => Port::Uart2,
+    _ => Port::Uart0,
+  }
+}
+
+/// An event reported by the UART driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+  /// The given number of bytes were received.
+  Data(usize),
+  #[cfg(target_device = "esp32")]
+  Break,
+  /// The RX ring buffer is full, further data is lost until it is read.
+  BufferFull,
+  /// The hardware FIFO overflowed, further data is lost until it is read.
+  FifoOverflow,
+  FrameError,
+  ParityError,
+  #[cfg(target_device = "esp32")]
+  PatternDetected,
+  Other,
+}
+
+impl From<uart_event_t> for Event {
+  fn from(event: uart_event_t) -> Self {
+    match event.type_ {
+      uart_event_type_t::UART_DATA => Event::Data(event.size),
+      #[cfg(target_device = "esp32")]
+      uart_event_type_t::UART_BREAK => Event::Break,
+      uart_event_type_t::UART_BUFFER_FULL => Event::BufferFull,
+      uart_event_type_t::UART_FIFO_OVF => Event::FifoOverflow,
+      uart_event_type_t::UART_FRAME_ERR => Event::FrameError,
+      uart_event_type_t::UART_PARITY_ERR => Event::ParityError,
+      #[cfg(target_device = "esp32")]
+      uart_event_type_t::UART_PATTERN_DET => Event::PatternDetected,
+      _ => Event::Other,
+    }
+  }
+}
+
+/// How long the event task waits for an event before checking whether it should stop.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// State shared with the event task.
+#[derive(Debug, Default)]
+struct Shared {
+  rx_waker: WakerCell,
+  stop: AtomicBool,
+}
+
+fn to_io_error(err: EspError) -> io::Error {
+  io::Error::new(io::ErrorKind::Other, err)
+}
+
+/// An installed UART driver.
+///
+/// Driver events are received by a separate task, which forwards them to
+/// [`next_event`](#method.next_event) and wakes pending reads.
+#[derive(Debug)]
+pub struct Uart {
+  port: Port,
+  read_timeout: Option<Duration>,
+  shared: Arc<Shared>,
+  events: Receiver<Event>,
+  event_task: Option<task::JoinHandle<()>>,
+  #[cfg(target_device = "esp32")]
+  console: bool,
+}
+
+impl Uart {
+  /// Install the driver on `port`.
+  ///
+  /// If `port` is the [console port](fn.console_port.html), console output keeps bypassing the driver
+  /// unless it is rerouted using [`use_for_console`](#method.use_for_console).
+  pub fn new(port: Port, config: Config) -> Result<Self, EspError> {
+    esp_ok!(uart_param_config(port.into(), &config.to_uart_config()))?;
+
+    #[cfg(target_device = "esp32")]
+    {
+      let [tx, rx, rts, cts] = config.pins;
+      esp_ok!(uart_set_pin(port.into(), tx, rx, rts, cts))?;
+    }
+
+    #[cfg(target_device = "esp8266")]
+    {
+      if config.swap_pins {
+        if port != Port::Uart0 {
+          return Err(EspErrorKind::Generic(GenericErrorKind::InvalidArg).into())
+        }
+
+        esp_ok!(uart_enable_swap())?;
+      }
+    }
+
+    let mut queue = core::ptr::null_mut();
+    esp_ok!(uart_driver_install(
+      port.into(),
+      config.rx_buffer_size as _,
+      config.tx_buffer_size as _,
+      config.event_queue_size as _,
+      &mut queue,
+      0,
+    ))?;
+
+    let (sender, events) = match channel(config.event_queue_size) {
+      Ok(channel) => channel,
+      Err(err) => {
+        let _ = esp_ok!(uart_driver_delete(port.into()));
+        return Err(err)
+      },
+    };
+
+    let shared = Arc::new(Shared::default());
+    let task_shared = Arc::clone(&shared);
+    let queue = queue as usize;
+
+    let event_task = task::Builder::new()
+      .name("uart_events")
+      .stack_size(2048)
+      .spawn(move || {
+        while !task_shared.stop.load(Ordering::Acquire) {
+          let mut event = MaybeUninit::<uart_event_t>::uninit();
+
+          let res = unsafe {
+            xQueueReceive(queue as QueueHandle_t, event.as_mut_ptr() as *mut _, sync::ticks(Some(EVENT_POLL_INTERVAL)))
+          };
+
+          if res == PD_TRUE {
+            task_shared.rx_waker.wake();
+            let _ = sender.send(Event::from(unsafe { event.assume_init() }));
+          }
+        }
+      });
+
+    let event_task = match event_task {
+      Ok(event_task) => event_task,
+      Err(_) => {
+        let _ = esp_ok!(uart_driver_delete(port.into()));
+        return Err(EspErrorKind::Generic(GenericErrorKind::NoMem).into())
+      },
+    };
+
+    Ok(Self {
+      port,
+      read_timeout: None,
+      shared,
+      events,
+      event_task: Some(event_task),
+      #[cfg(target_device = "esp32")]
+      console: false,
+    })
+  }
+
+  pub fn port(&self) -> Port {
+    self.port
+  }
+
+  /// Reroute the console through this driver until the `Uart` is dropped.
+  ///
+  /// Console output is otherwise written directly to the hardware FIFO, interleaving with
+  /// data written by the driver. Fails with `InvalidArg` if this is not the [console port](fn.console_port.html).
+  #[cfg(target_device = "esp32")]
+  pub fn use_for_console(&mut self) -> Result<(), EspError> {
+    if self.port != console_port() {
+      return Err(EspErrorKind::Generic(GenericErrorKind::InvalidArg).into())
+    }
+
+    unsafe { esp_vfs_dev_uart_use_driver(self.port as _) };
+    self.console = true;
+    Ok(())
+  }
+
+  /// Change the baud rate.
+  pub fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), EspError> {
+    esp_ok!(uart_set_baudrate(self.port.into(), baud_rate))
+  }
+
+  /// Timeout for the first byte of a blocking read. `None` means waiting indefinitely.
+  pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+    self.read_timeout = timeout;
+  }
+
+  /// Discard all data in the RX buffer.
+  pub fn clear_input(&mut self) -> Result<(), EspError> {
+    esp_ok!(uart_flush_input(self.port.into()))
+  }
+
+  /// Read up to `buf.len()` bytes, waiting at most `timeout`, returning the number of bytes read.
+  fn read_available(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize, EspError> {
+    let len = unsafe { uart_read_bytes(self.port.into(), buf.as_mut_ptr() as *mut _, buf.len() as _, sync::ticks(timeout)) };
+
+    if len < 0 {
+      return Err(EspErrorKind::Generic(GenericErrorKind::Fail).into())
+    }
+
+    Ok(len as usize)
+  }
+
+  fn write_buffered(&mut self, buf: &[u8]) -> Result<usize, EspError> {
+    let len = unsafe { uart_write_bytes(self.port.into(), buf.as_ptr() as *const _, buf.len() as _) };
+
+    if len < 0 {
+      return Err(EspErrorKind::Generic(GenericErrorKind::Fail).into())
+    }
+
+    Ok(len as usize)
+  }
+
+  /// Read into `buf`, waiting until at least one byte is available.
+  pub fn read_async<'u>(&'u mut self, buf: &'u mut [u8]) -> ReadFuture<'u> {
+    ReadFuture { uart: self, buf }
+  }
+
+  /// Wait for the next driver event. Resolves to `None` if the event task stopped.
+  pub fn next_event(&mut self) -> Recv<'_, Event> {
+    self.events.recv()
+  }
+}
+
+impl Drop for Uart {
+  fn drop(&mut self) {
+    self.shared.stop.store(true, Ordering::Release);
+
+    if let Some(event_task) = self.event_task.take() {
+      let _ = event_task.join();
+    }
+
+    #[cfg(target_device = "esp32")]
+    {
+      if self.console {
+        unsafe { esp_vfs_dev_uart_use_nonblocking(self.port as _) };
+      }
+    }
+
+    let _ = esp_ok!(uart_driver_delete(self.port.into()));
+  }
+}
+
+/// A future returned by [`Uart::read_async`](struct.Uart.html#method.read_async).
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct ReadFuture<'u> {
+  uart: &'u mut Uart,
+  buf: &'u mut [u8],
+}
+
+impl Future for ReadFuture<'_> {
+  type Output = io::Result<usize>;
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    if self.buf.is_empty() {
+      return Poll::Ready(Ok(0))
+    }
+
+    // Register the waker before reading, so data received in between is not missed.
+    self.uart.shared.rx_waker.register(cx.waker());
+
+    let ReadFuture { uart, buf } = &mut *self;
+
+    match uart.read_available(buf, Some(Duration::from_secs(0))) {
+      Ok(0) => Poll::Pending,
+      res => Poll::Ready(res.map_err(to_io_error)),
+    }
+  }
+}
+
+impl io::Read for Uart {
+  /// Read into `buf`, waiting up to the [read timeout](#method.set_read_timeout) for the first byte.
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if buf.is_empty() {
+      return Ok(0)
+    }
+
+    let len = self.read_available(buf, Some(Duration::from_secs(0))).map_err(to_io_error)?;
+    if len > 0 {
+      return Ok(len)
+    }
+
+    if self.read_available(&mut buf[..1], self.read_timeout).map_err(to_io_error)? == 0 {
+      return Err(io::ErrorKind::TimedOut.into())
+    }
+
+    if buf.len() == 1 {
+      return Ok(1)
+    }
+
+    let len = self.read_available(&mut buf[1..], Some(Duration::from_secs(0))).map_err(to_io_error)?;
+    Ok(1 + len)
+  }
+}
+
+impl io::Write for Uart {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.write_buffered(buf).map_err(to_io_error)
+  }
+
+  /// Wait until all data is transmitted.
+  fn flush(&mut self) -> io::Result<()> {
+    esp_ok!(uart_wait_tx_done(self.port.into(), sync::ticks(None))).map_err(to_io_error)
+  }
+}
+
+impl embedded_hal::serial::Read<u8> for Uart {
+  type Error = EspError;
+
+  fn read(&mut self) -> nb::Result<u8, Self::Error> {
+    let mut byte = 0;
+
+    match self.read_available(core::slice::from_mut(&mut byte), Some(Duration::from_secs(0)))? {
+      0 => Err(nb::Error::WouldBlock),
+      _ => Ok(byte),
+    }
+  }
+}
+
+impl embedded_hal::serial::Write<u8> for Uart {
+  type Error = EspError;
+
+  fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+    match self.write_buffered(&[byte])? {
+      0 => Err(nb::Error::WouldBlock),
+      _ => Ok(()),
+    }
+  }
+
+  fn flush(&mut self) -> nb::Result<(), Self::Error> {
+    match esp_ok!(uart_wait_tx_done(self.port.into(), 0)) {
+      Err(err) if err.kind() == EspErrorKind::Generic(GenericErrorKind::Timeout) => Err(nb::Error::WouldBlock),
+      res => Ok(res?),
+    }
+  }
+}
+
+impl embedded_hal::blocking::serial::write::Default<u8> for Uart {}
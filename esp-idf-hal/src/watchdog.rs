@@ -0,0 +1,145 @@
+//! Task watchdog.
+//!
+//! Tasks subscribed to the task watchdog have to [`feed`](struct.WatchdogGuard.html#method.feed)
+//! it regularly, otherwise the watchdog triggers, which either prints a warning or panics,
+//! depending on the [configuration](struct.Watchdog.html#method.configure).
+
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+use core::time::Duration;
+use std::ffi::{c_void, CStr};
+
+use esp_idf_bindgen::*;
+
+use crate::EspError;
+
+/// Maximum number of tasks for which the watchdog hook can report the task name.
+const MAX_TRACKED_TASKS: usize = 16;
+
+struct Slot {
+  task: AtomicPtr<c_void>,
+  last_feed_ms: AtomicU32,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: Slot = Slot { task: AtomicPtr::new(ptr::null_mut()), last_feed_ms: AtomicU32::new(0) };
+
+static SLOTS: [Slot; MAX_TRACKED_TASKS] = [EMPTY_SLOT; MAX_TRACKED_TASKS];
+static TIMEOUT_MS: AtomicU32 = AtomicU32::new(CONFIG_ESP_TASK_WDT_TIMEOUT_S * 1000);
+/// The hook function pointer, stored directly so that setting it does not allocate.
+static HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+type Hook = fn(Option<&str>);
+
+fn now_ms() -> u32 {
+  (unsafe { esp_timer_get_time() } / 1000) as u32
+}
+
+/// The task watchdog.
+#[derive(Debug)]
+pub struct Watchdog {
+  _marker: PhantomData<()>,
+}
+
+impl Watchdog {
+  /// Set the watchdog timeout, rounded up to whole seconds, and whether to panic when it triggers.
+  ///
+  /// Timeouts are at least one second and at most `u32::MAX` milliseconds.
+  /// This also initializes the watchdog if it is not enabled in `sdkconfig`.
+  pub fn configure(timeout: Duration, panic: bool) -> Result<(), EspError> {
+    let timeout_s = timeout.as_secs().saturating_add(u64::from(timeout.subsec_nanos() > 0));
+    let timeout_s = timeout_s.max(1).min(u64::from(u32::max_value() / 1000)) as u32;
+
+    esp_ok!(esp_task_wdt_init(timeout_s, panic))?;
+    TIMEOUT_MS.store(timeout_s * 1000, Ordering::Relaxed);
+
+    Ok(())
+  }
+
+  /// Subscribe the current task to the watchdog.
+  ///
+  /// The task is unsubscribed when the returned guard is dropped.
+  pub fn subscribe() -> Result<WatchdogGuard, EspError> {
+    let task = unsafe { xTaskGetCurrentTaskHandle() };
+    esp_ok!(esp_task_wdt_add(task))?;
+
+    let slot = SLOTS.iter().position(|slot| {
+      slot.task.compare_exchange(ptr::null_mut(), task.cast(), Ordering::AcqRel, Ordering::Relaxed).is_ok()
+    });
+
+    let guard = WatchdogGuard { task, slot, _not_send: PhantomData };
+    guard.feed();
+    Ok(guard)
+  }
+
+  /// Set a function which is called with the name of the task which triggered the watchdog.
+  ///
+  /// The name is `None` if the task was not subscribed using [`subscribe`](#method.subscribe),
+  /// e.g. an idle task. The hook runs in an interrupt handler, so it must not block or allocate.
+  pub fn set_hook(hook: fn(Option<&str>)) {
+    HOOK.store(hook as *mut (), Ordering::Release);
+  }
+}
+
+/// A subscription of the current task to the [`Watchdog`](struct.Watchdog.html).
+#[derive(Debug)]
+pub struct WatchdogGuard {
+  task: TaskHandle_t,
+  slot: Option<usize>,
+  _not_send: PhantomData<*const ()>,
+}
+
+impl WatchdogGuard {
+  /// Reset the watchdog timer of the current task.
+  pub fn feed(&self) {
+    let _ = esp_ok!(esp_task_wdt_reset());
+
+    if let Some(slot) = self.slot {
+      SLOTS[slot].last_feed_ms.store(now_ms(), Ordering::Relaxed);
+    }
+  }
+}
+
+impl Drop for WatchdogGuard {
+  fn drop(&mut self) {
+    let _ = esp_ok!(esp_task_wdt_delete(self.task));
+
+    if let Some(slot) = self.slot {
+      SLOTS[slot].task.store(ptr::null_mut(), Ordering::Release);
+    }
+  }
+}
+
+#[no_mangle]
+extern "C" fn esp_task_wdt_isr_user_handler() {
+  let hook = HOOK.load(Ordering::Acquire);
+
+  if hook.is_null() {
+    return
+  }
+
+  // SAFETY: `hook` was created from a `Hook` in `set_hook`.
+  let hook = unsafe { mem::transmute::<*mut (), Hook>(hook) };
+
+  let now = now_ms();
+  let timeout = TIMEOUT_MS.load(Ordering::Relaxed);
+  let mut found = false;
+
+  for slot in SLOTS.iter() {
+    let task = slot.task.load(Ordering::Acquire);
+
+    if task.is_null() || now.wrapping_sub(slot.last_feed_ms.load(Ordering::Relaxed)) < timeout {
+      continue
+    }
+
+    let name = unsafe { CStr::from_ptr(pcTaskGetTaskName(task.cast())) };
+    hook(Some(name.to_str().unwrap_or("<invalid>")));
+    found = true;
+  }
+
+  if !found {
+    hook(None);
+  }
+}
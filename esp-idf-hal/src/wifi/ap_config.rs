@@ -66,14 +66,14 @@ impl ApConfig {
 
 /// Builder for [`ApConfig`](struct.ApConfig.html).
 pub struct ApConfigBuilder {
-  ssid: Option<Ssid>,
-  password: Password,
-  channel: Option<NonZeroU8>,
-  auth_mode: AuthMode,
-  max_connection: Option<NonZeroU8>,
-  ssid_hidden: bool,
-  beacon_interval: Option<NonZeroU16>,
-  pairwise_cipher: Cipher,
+  pub(crate) ssid: Option<Ssid>,
+  pub(crate) password: Password,
+  pub(crate) channel: Option<NonZeroU8>,
+  pub(crate) auth_mode: AuthMode,
+  pub(crate) max_connection: Option<NonZeroU8>,
+  pub(crate) ssid_hidden: bool,
+  pub(crate) beacon_interval: Option<NonZeroU16>,
+  pub(crate) pairwise_cipher: Cipher,
 }
 
 impl fmt::Debug for ApConfigBuilder {
@@ -2,14 +2,15 @@ use core::mem::{self, transmute, MaybeUninit};
 use core::num::NonZeroU8;
 use std::str::Utf8Error;
 use std::sync::atomic::{AtomicBool, AtomicU8, Ordering::SeqCst};
-use core::task::{Poll, Context, Waker};
+use core::future::Future;
+use core::task::{Poll, Context};
 use core::pin::Pin;
 
 use core::fmt;
 use macaddr::MacAddr6;
 use pin_project::pin_project;
 
-use crate::{EspError, nvs::NonVolatileStorage, interface::{Interface, IpInfo}};
+use crate::{EspError, nvs::NonVolatileStorage, interface::{Interface, IpInfo}, sync::{self, Sender, Receiver}};
 
 use esp_idf_bindgen::*;
 
@@ -59,6 +60,15 @@ impl fmt::Display for WifiConfigError {
   }
 }
 
+impl std::error::Error for WifiConfigError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Utf8Error(utf8_error) => Some(utf8_error),
+      _ => None,
+    }
+  }
+}
+
 #[derive(Debug)]
 pub struct Ap {
   mode: ApMode,
@@ -289,6 +299,12 @@ fn leave_sta_mode() -> Result<(), EspError> {
 
 static WIFI_ACTIVE: AtomicBool = AtomicBool::new(false);
 
+/// Whether the WiFi driver is initialized, i.e. a [`Wifi`](struct.Wifi.html) instance exists.
+#[cfg(target_device = "esp32")]
+pub(crate) fn is_active() -> bool {
+  WIFI_ACTIVE.load(SeqCst)
+}
+
 impl Wifi {
   /// Take the WiFi peripheral if it is not already in use.
   pub fn take() -> Option<Wifi> {
@@ -346,7 +362,7 @@ impl Wifi {
       ConnectFutureState::Starting
     };
 
-    ConnectFuture { waker: None, mode: sta_mode, state, handlers: None, wifi: self }
+    ConnectFuture { mode: sta_mode, state, handlers: None, sender: None, receiver: None, wifi: self }
   }
 }
 
@@ -424,20 +440,31 @@ enum ConnectFutureState {
   Connected { ip_info: IpInfo, ssid: Ssid, bssid: MacAddr6, channel: Option<NonZeroU8>, auth_mode: AuthMode },
 }
 
+/// Events sent from `wifi_sta_handler` to a `ConnectFuture`.
+#[derive(Debug)]
+enum StaEvent {
+  Started,
+  Connected { ssid: Ssid, bssid: MacAddr6, channel: Option<NonZeroU8>, auth_mode: AuthMode },
+  Disconnected(ConnectionError),
+  GotIp(IpInfo),
+}
+
 /// A future representing an ongoing connection to an access point.
 #[must_use = "futures do nothing unless polled"]
 #[pin_project]
 #[derive(Debug)]
 pub struct ConnectFuture<'w> {
-  waker: Option<Waker>,
   mode: Option<StaMode>,
   state: ConnectFutureState,
+  // `handlers` are declared before `sender` so they are unregistered before `sender` is dropped.
   handlers: Option<[EventHandler; 4]>,
+  sender: Option<Box<Sender<StaEvent>>>,
+  receiver: Option<Receiver<StaEvent>>,
   wifi: &'w mut Wifi,
 }
 
 /// The type returned when a [`ConnectFuture`](struct.ConnectFuture.html) succeeds.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct ConnectionInfo {
   ip_info: IpInfo,
   ssid: Ssid,
@@ -487,6 +514,8 @@ impl fmt::Display for ConnectionError {
   }
 }
 
+impl std::error::Error for ConnectionError {}
+
 /// The error type for operations on a [`Wifi`](struct.Wifi.html) instance.
 #[derive(Debug, Clone)]
 pub enum WifiError {
@@ -511,7 +540,41 @@ impl fmt::Display for WifiError {
   }
 }
 
-impl core::future::Future for ConnectFuture<'_> {
+impl std::error::Error for WifiError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Internal(esp_error) => Some(esp_error),
+      Self::ConnectionError(error) => Some(error),
+    }
+  }
+}
+
+#[cfg(target_device = "esp32")]
+impl ConnectFuture<'_> {
+  fn start(&mut self) -> Result<(), EspError> {
+    let (sender, receiver) = sync::channel(8)?;
+    let sender = Box::new(sender);
+    let arg = &*sender as *const Sender<StaEvent> as *mut libc::c_void;
+
+    self.handlers.replace([
+      EventHandler::register(unsafe { WIFI_EVENT }, wifi_event_t::WIFI_EVENT_STA_START as _, wifi_sta_handler, arg)?,
+      EventHandler::register(unsafe { WIFI_EVENT }, wifi_event_t::WIFI_EVENT_STA_CONNECTED as _, wifi_sta_handler, arg)?,
+      EventHandler::register(unsafe { WIFI_EVENT }, wifi_event_t::WIFI_EVENT_STA_DISCONNECTED as _, wifi_sta_handler, arg)?,
+      EventHandler::register(unsafe { IP_EVENT }, ip_event_t::IP_EVENT_STA_GOT_IP as _, wifi_sta_handler, arg)?,
+    ]);
+    self.sender.replace(sender);
+    self.receiver.replace(receiver);
+
+    esp_ok!(esp_wifi_start())
+  }
+
+  fn stop_events(&mut self) {
+    self.handlers = None;
+    self.sender = None;
+  }
+}
+
+impl Future for ConnectFuture<'_> {
   type Output = Result<ConnectionInfo, WifiError>;
 
   #[cfg(target_device = "esp8266")]
@@ -521,34 +584,48 @@ impl core::future::Future for ConnectFuture<'_> {
 
   #[cfg(target_device = "esp32")]
   fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-    match self.state {
-      ConnectFutureState::Starting => {
-        self.waker.replace(cx.waker().clone());
-
-        let register_handlers = |arg: *mut ConnectFuture| -> Result<[EventHandler; 4], EspError> {
-          Ok([
-            EventHandler::register(unsafe { WIFI_EVENT }, wifi_event_t::WIFI_EVENT_STA_START as _, wifi_sta_handler, arg as _)?,
-            EventHandler::register(unsafe { WIFI_EVENT }, wifi_event_t::WIFI_EVENT_STA_CONNECTED as _, wifi_sta_handler, arg as _)?,
-            EventHandler::register(unsafe { WIFI_EVENT }, wifi_event_t::WIFI_EVENT_STA_DISCONNECTED as _, wifi_sta_handler, arg as _)?,
-            EventHandler::register(unsafe { IP_EVENT }, ip_event_t::IP_EVENT_STA_GOT_IP as _, wifi_sta_handler, arg as _)?,
-          ])
+    let this = &mut *self;
+
+    if let (ConnectFutureState::Starting, None) = (&this.state, &this.receiver) {
+      if let Err(err) = this.start() {
+        this.state = ConnectFutureState::Failed(err.into());
+      }
+    }
+
+    if let Some(receiver) = this.receiver.as_mut() {
+      while matches!(this.state, ConnectFutureState::Starting | ConnectFutureState::ConnectedWithoutIp { .. }) {
+        let event = match Pin::new(&mut receiver.recv()).poll(cx) {
+          Poll::Ready(Some(event)) => event,
+          _ => break,
         };
 
-        match register_handlers(&mut *self as *mut _) {
-          Ok(handlers) => { self.handlers.replace(handlers); },
-          Err(err) => {
-            return Poll::Ready(Err(WifiError::from(err)));
+        this.state = match (mem::replace(&mut this.state, ConnectFutureState::Starting), event) {
+          (state, StaEvent::Started) => match esp_ok!(esp_wifi_connect()) {
+            Ok(()) => state,
+            Err(err) => ConnectFutureState::Failed(err.into()),
           },
-        }
-
-        if let Err(err) = esp_ok!(esp_wifi_start()) {
-          return Poll::Ready(Err(WifiError::from(err)));
-        }
+          (_, StaEvent::Connected { ssid, bssid, channel, auth_mode }) => {
+            ConnectFutureState::ConnectedWithoutIp { ssid, bssid, channel, auth_mode }
+          },
+          (_, StaEvent::Disconnected(error)) => {
+            ConnectFutureState::Failed(WifiError::ConnectionError(error))
+          },
+          (ConnectFutureState::ConnectedWithoutIp { ssid, bssid, channel, auth_mode }, StaEvent::GotIp(ip_info)) => {
+            ConnectFutureState::Connected { ip_info, ssid, bssid, channel, auth_mode }
+          },
+          (state, StaEvent::GotIp(_)) => state,
+        };
+      }
+    }
 
+    match this.state {
+      ConnectFutureState::Starting => {
         Poll::Pending
       },
       ConnectFutureState::Failed(ref err) => {
-        return Poll::Ready(Err(WifiError::from(err.clone())));
+        let err = err.clone();
+        this.stop_events();
+        Poll::Ready(Err(err))
       },
       ConnectFutureState::ConnectedWithoutIp { .. } => {
         Poll::Pending
@@ -562,6 +639,8 @@ impl core::future::Future for ConnectFuture<'_> {
       } => {
         eprintln!("Ended STA connection");
 
+        this.stop_events();
+
         let connection_info = ConnectionInfo {
           ip_info,
           ssid,
@@ -570,12 +649,12 @@ impl core::future::Future for ConnectFuture<'_> {
           auth_mode,
         };
 
-        let mode = self.mode.take().unwrap();
-        let inner = match mem::take(&mut self.wifi.inner) {
+        let mode = this.mode.take().unwrap();
+        let inner = match mem::take(&mut this.wifi.inner) {
           WifiInner::Ap(ap) => WifiInner::ApSta(ap, Sta { mode }),
           _ => WifiInner::Sta(Sta { mode }),
         };
-        self.wifi.inner = inner;
+        this.wifi.inner = inner;
 
         Poll::Ready(Ok(connection_info))
       },
@@ -591,8 +670,8 @@ extern "C" fn wifi_sta_handler(
   event_data: *mut libc::c_void,
 ) {
   // SAFETY: `wifi_sta_handler` is only registered while the `event_handler_arg` is
-  //         pointing to a `ConnectFuture` contained in a `Pin`.
-  let mut f = unsafe { Pin::new_unchecked(&mut *(event_handler_arg as *mut ConnectFuture)) };
+  //         pointing to the `Sender` owned by a `ConnectFuture`.
+  let sender = unsafe { &*(event_handler_arg as *const Sender<StaEvent>) };
 
   if event_base == unsafe { WIFI_EVENT } {
     let event_id: wifi_event_t = unsafe { transmute(event_id) };
@@ -601,10 +680,7 @@ extern "C" fn wifi_sta_handler(
 
     match event_id {
       wifi_event_t::WIFI_EVENT_STA_START => {
-        if let Err(err) = esp_ok!(esp_wifi_connect()) {
-          f.state = ConnectFutureState::Failed(err.into());
-          f.waker.as_ref().map(|w| w.wake_by_ref());
-        }
+        let _ = sender.send(StaEvent::Started);
       },
       wifi_event_t::WIFI_EVENT_STA_CONNECTED => {
         let event = unsafe { &*(event_data as *const wifi_event_sta_connected_t) };
@@ -616,9 +692,7 @@ extern "C" fn wifi_sta_handler(
         let channel = NonZeroU8::new(event.channel);
         let auth_mode = AuthMode::from(event.authmode);
 
-        f.state = ConnectFutureState::ConnectedWithoutIp { ssid, bssid, channel, auth_mode };
-
-        eprintln!("EVENT_STATE: {:?}", f.state);
+        let _ = sender.send(StaEvent::Connected { ssid, bssid, channel, auth_mode });
       },
       wifi_event_t::WIFI_EVENT_STA_DISCONNECTED => {
         let event = unsafe { &*(event_data as *const wifi_event_sta_disconnected_t) };
@@ -629,15 +703,7 @@ extern "C" fn wifi_sta_handler(
         let bssid = MacAddr6::from(event.bssid);
         let reason: wifi_err_reason_t = unsafe { transmute(event.reason as u32) };
 
-        let error = ConnectionError {
-          ssid, bssid, reason
-        };
-
-        f.state = ConnectFutureState::Failed(WifiError::ConnectionError(error));
-
-        eprintln!("EVENT_STATE: {:?}", f.state);
-
-        f.waker.as_ref().map(|w| w.wake_by_ref());
+        let _ = sender.send(StaEvent::Disconnected(ConnectionError { ssid, bssid, reason }));
       },
       _ => (),
     }
@@ -650,22 +716,13 @@ extern "C" fn wifi_sta_handler(
       ip_event_t::IP_EVENT_STA_GOT_IP => {
         let event = unsafe { &*(event_data as *const ip_event_got_ip_t) };
 
-        let ip_info = unsafe { IpInfo::from_native_unchecked(event.ip_info) };
-
         eprintln!("EVENT_DATA: {:?}", event);
 
-        if let ConnectFutureState::ConnectedWithoutIp { ssid, bssid, channel, auth_mode } = mem::replace(&mut f.state, ConnectFutureState::Starting) {
-          f.state = ConnectFutureState::Connected { ip_info, ssid, bssid, channel, auth_mode };
-        } else {
-          unreachable!();
-        }
-
-        eprintln!("EVENT_STATE: {:?}", f.state);
+        let ip_info = unsafe { IpInfo::from_native_unchecked(event.ip_info) };
 
-        f.waker.as_ref().map(|w| w.wake_by_ref());
+        let _ = sender.send(StaEvent::GotIp(ip_info));
       },
       _ => (),
     }
   }
 }
-
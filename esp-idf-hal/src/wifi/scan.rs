@@ -3,7 +3,7 @@ use core::future::Future;
 use core::mem::{self, MaybeUninit};
 use core::pin::Pin;
 use core::ptr;
-use core::task::{Poll, Context, Waker};
+use core::task::{Poll, Context};
 use std::time::Duration;
 
 use esp_idf_bindgen::{
@@ -19,6 +19,8 @@ use esp_idf_bindgen::{
 use macaddr::MacAddr6;
 use pin_project::pin_project;
 
+use crate::sync::{self, Sender, Receiver};
+
 use super::*;
 
 /// Scan type used for scanning nearby WiFi networks.
@@ -135,7 +137,8 @@ impl ApRecord {
 
 #[derive(Debug)]
 enum ScanFutureState {
-  Starting(wifi_scan_config_t, StaMode, Option<Waker>),
+  Starting(wifi_scan_config_t, StaMode),
+  Scanning(StaMode, Receiver<()>),
   Done,
 }
 
@@ -144,7 +147,9 @@ enum ScanFutureState {
 #[pin_project]
 #[derive(Debug)]
 pub struct ScanFuture<'w> {
+  // `handler` is declared before `sender` so it is unregistered before `sender` is dropped.
   handler: Option<EventHandler>,
+  sender: Option<Box<Sender<()>>>,
   state: ScanFutureState,
   wifi: &'w mut Wifi,
 }
@@ -195,7 +200,8 @@ impl<'w> ScanFuture<'w> {
 
     Self {
       handler: None,
-      state: ScanFutureState::Starting(config, StaMode::enter(), None),
+      sender: None,
+      state: ScanFutureState::Starting(config, StaMode::enter()),
       wifi,
     }
   }
@@ -211,26 +217,46 @@ impl Future for ScanFuture<'_> {
 
   #[cfg(target_device = "esp32")]
   fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-    match self.state {
-      ScanFutureState::Starting(config, _, ref mut waker) => {
-        waker.replace(cx.waker().clone());
+    let this = &mut *self;
 
-        esp_ok!(esp_wifi_start())?;
+    if let ScanFutureState::Starting(config, _) = this.state {
+      esp_ok!(esp_wifi_start())?;
 
-        let arg = &mut *self as *mut _;
-        self.handler.replace(EventHandler::register(
-          unsafe { WIFI_EVENT }, wifi_event_t::WIFI_EVENT_SCAN_DONE as _, wifi_scan_done_handler, arg as _
-        )?);
+      let (sender, receiver) = sync::channel(1)?;
+      let sender = Box::new(sender);
 
-        if let Err(err) = esp_ok!(esp_wifi_scan_start(&config, false)) {
-          return Poll::Ready(Err(err.into()))
-        };
+      let arg = &*sender as *const Sender<()>;
+      this.handler.replace(EventHandler::register(
+        unsafe { WIFI_EVENT }, wifi_event_t::WIFI_EVENT_SCAN_DONE as _, wifi_scan_done_handler, arg as _
+      )?);
+      this.sender.replace(sender);
 
-        Poll::Pending
-      },
-      ScanFutureState::Done => {
-        Poll::Ready(Ok(get_ap_records()?))
+      if let ScanFutureState::Starting(_, mode) = mem::replace(&mut this.state, ScanFutureState::Done) {
+        this.state = ScanFutureState::Scanning(mode, receiver);
       }
+
+      if let Err(err) = esp_ok!(esp_wifi_scan_start(&config, false)) {
+        this.state = ScanFutureState::Done;
+        this.handler = None;
+        this.sender = None;
+        return Poll::Ready(Err(err.into()))
+      };
+    }
+
+    match this.state {
+      ScanFutureState::Scanning(_, ref mut receiver) => {
+        if Pin::new(&mut receiver.recv()).poll(cx).is_pending() {
+          return Poll::Pending
+        }
+
+        this.state = ScanFutureState::Done;
+        this.handler = None;
+        this.sender = None;
+
+        Poll::Ready(Ok(get_ap_records()?))
+      },
+      ScanFutureState::Done => panic!("`ScanFuture` polled after completion"),
+      ScanFutureState::Starting(..) => unreachable!(),
     }
   }
 }
@@ -269,9 +295,7 @@ extern "C" fn wifi_scan_done_handler(
   _event_data: *mut libc::c_void,
 ) {
   // SAFETY: `wifi_scan_done_handler` is only registered while the `event_handler_arg` is
-  //         pointing to a `ScanFuture` contained in a `Pin`.
-  let mut f = unsafe { Pin::new_unchecked(&mut *(event_handler_arg as *mut ScanFuture)) };
-  if let ScanFutureState::Starting(_, _, Some(waker)) = mem::replace(&mut f.state, ScanFutureState::Done) {
-    waker.wake();
-  }
+  //         pointing to the `Sender` owned by a `ScanFuture`.
+  let sender = unsafe { &*(event_handler_arg as *const Sender<()>) };
+  let _ = sender.send(());
 }
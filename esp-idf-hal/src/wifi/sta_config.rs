@@ -2,6 +2,7 @@ use core::fmt;
 use core::mem;
 use core::num::{NonZeroU8, NonZeroU16};
 
+use macaddr::MacAddr6;
 use esp_idf_bindgen::{
   wifi_config_t,
   wifi_sta_config_t,
@@ -59,8 +60,8 @@ impl From<SortMethod> for wifi_sort_method_t {
 /// Scan threshold used when connecting to an access point.
 #[derive(Debug, Clone, Copy)]
 pub struct ScanThreshold {
-  rssi: i8,
-  auth_mode: AuthMode,
+  pub(crate) rssi: i8,
+  pub(crate) auth_mode: AuthMode,
 }
 
 impl Default for ScanThreshold {
@@ -122,14 +123,14 @@ impl StaConfig {
 
 /// Builder for [`StaConfig`](struct.StaConfig.html).
 pub struct StaConfigBuilder {
-  ssid: Option<Ssid>,
-  password: Password,
-  scan_method: ScanMethod,
-  bssid: Option<[u8; 6]>,
-  channel: Option<NonZeroU8>,
-  listen_interval: Option<NonZeroU16>,
-  sort_method: SortMethod,
-  threshold: Option<ScanThreshold>,
+  pub(crate) ssid: Option<Ssid>,
+  pub(crate) password: Password,
+  pub(crate) scan_method: ScanMethod,
+  pub(crate) bssid: Option<[u8; 6]>,
+  pub(crate) channel: Option<NonZeroU8>,
+  pub(crate) listen_interval: Option<NonZeroU16>,
+  pub(crate) sort_method: SortMethod,
+  pub(crate) threshold: Option<ScanThreshold>,
 }
 
 impl fmt::Debug for StaConfigBuilder {
@@ -173,6 +174,20 @@ impl StaConfigBuilder {
     self
   }
 
+  /// Only connect to the access point with the given BSSID, e.g. from a cached
+  /// [`ConnectionInfo`](struct.ConnectionInfo.html) for a fast reconnect.
+  pub fn bssid(&mut self, bssid: impl Into<Option<MacAddr6>>) -> &mut Self {
+    self.bssid = bssid.into().map(|bssid| bssid.into_array());
+    self
+  }
+
+  /// Start scanning on the given channel, e.g. from a cached
+  /// [`ConnectionInfo`](struct.ConnectionInfo.html) for a fast reconnect.
+  pub fn channel(&mut self, channel: impl Into<Option<NonZeroU8>>) -> &mut Self {
+    self.channel = channel.into();
+    self
+  }
+
   pub fn build(&self) -> StaConfig {
     StaConfig(wifi_config_t {
       sta: wifi_sta_config_t {
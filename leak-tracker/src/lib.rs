@@ -0,0 +1,675 @@
+//! A global allocator wrapper which tracks allocations per thread, used for finding memory leaks.
+//!
+//! The tracker only relies on the hooks of a [`Platform`](trait.Platform.html), so its accounting
+//! can be tested on the host by wrapping `std::alloc::System`. `esp_idf_hal::heap` re-exports it
+//! together with the `FreeRtos` platform.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::str;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Maximum length of a [`Tag`](struct.Tag.html) in bytes, the same as the FreeRTOS task name length.
+pub const TAG_LEN: usize = 16;
+
+/// Maximum number of threads tracked separately, further threads are accounted to a shared `<other>` entry.
+pub const MAX_THREADS: usize = 32;
+
+/// Number of largest outstanding allocations which are recorded.
+pub const MAX_LARGEST: usize = 16;
+
+/// A short name which allocations are accounted to, e.g. a task name.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Tag {
+  bytes: [u8; TAG_LEN],
+  len: u8,
+}
+
+impl Tag {
+  /// Create a tag from `name`, truncated to [`TAG_LEN`](constant.TAG_LEN.html) bytes.
+  pub const fn new(name: &str) -> Self {
+    Self::from_bytes(name.as_bytes())
+  }
+
+  /// Create a tag from UTF-8 `bytes`, truncated to [`TAG_LEN`](constant.TAG_LEN.html) bytes.
+  pub const fn from_bytes(name: &[u8]) -> Self {
+    let mut bytes = [0; TAG_LEN];
+    let mut len = 0;
+
+    while len < TAG_LEN && len < name.len() {
+      bytes[len] = name[len];
+      len += 1;
+    }
+
+    Self { bytes, len: len as u8 }
+  }
+
+  pub fn as_str(&self) -> &str {
+    let bytes = &self.bytes[..usize::from(self.len)];
+
+    // Truncating may have split a multi-byte character.
+    match str::from_utf8(bytes) {
+      Ok(s) => s,
+      Err(err) => unsafe { str::from_utf8_unchecked(&bytes[..err.valid_up_to()]) },
+    }
+  }
+}
+
+impl fmt::Debug for Tag {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.as_str().fmt(f)
+  }
+}
+
+impl fmt::Display for Tag {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.as_str().fmt(f)
+  }
+}
+
+/// Platform hooks used by a [`LeakTracker`](struct.LeakTracker.html).
+///
+/// None of these functions may allocate.
+pub trait Platform {
+  /// A unique identifier of the current thread.
+  ///
+  /// Identifiers may be reused once a thread exits, in which case the new
+  /// thread continues the statistics of the old one.
+  fn current_thread() -> usize;
+
+  /// The tag for allocations of the current thread until [`LeakTracker::set_tag`](struct.LeakTracker.html#method.set_tag) is called.
+  fn thread_tag() -> Tag {
+    Tag::new("")
+  }
+
+  /// Currently free heap in bytes, used for the low heap check.
+  fn free_heap() -> Option<usize> {
+    None
+  }
+
+  /// Run `f` with exclusive access to the statistics of all trackers.
+  ///
+  /// Since this runs on every allocation, it must not allocate, and it must not wait by
+  /// yielding to other threads, which never lets a lower priority thread holding the lock run.
+  fn critical_section<R>(f: impl FnOnce() -> R) -> R;
+}
+
+/// Allocation statistics of a single thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadStats {
+  pub tag: Tag,
+  /// Number of allocations made by this thread.
+  pub allocations: usize,
+  /// Number of allocations made by this thread which were freed again, by any thread.
+  pub deallocations: usize,
+  /// Bytes allocated by this thread which are not freed yet.
+  pub outstanding_bytes: usize,
+  /// Highest value of `outstanding_bytes`.
+  pub peak_bytes: usize,
+}
+
+impl ThreadStats {
+  const fn new(tag: Tag) -> Self {
+    Self { tag, allocations: 0, deallocations: 0, outstanding_bytes: 0, peak_bytes: 0 }
+  }
+
+  /// Number of allocations made by this thread which are not freed yet.
+  pub fn outstanding_allocations(&self) -> usize {
+    self.allocations - self.deallocations
+  }
+}
+
+/// An outstanding allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocation {
+  pub address: usize,
+  pub size: usize,
+  pub tag: Tag,
+}
+
+const OTHER: Tag = Tag::new("<other>");
+
+#[derive(Clone, Copy)]
+struct State {
+  ids: [usize; MAX_THREADS],
+  threads: [ThreadStats; MAX_THREADS],
+  thread_count: usize,
+  largest: [Option<(usize, Allocation)>; MAX_LARGEST],
+}
+
+impl State {
+  const fn new() -> Self {
+    Self {
+      ids: [0; MAX_THREADS],
+      threads: [ThreadStats::new(OTHER); MAX_THREADS],
+      // Slot 0 is used for threads which do not fit into the table.
+      thread_count: 1,
+      largest: [None; MAX_LARGEST],
+    }
+  }
+
+  fn slot<P: Platform>(&mut self) -> usize {
+    let id = P::current_thread();
+
+    if let Some(slot) = self.ids[1..self.thread_count].iter().position(|&i| i == id) {
+      return slot + 1
+    }
+
+    if self.thread_count == MAX_THREADS {
+      return 0
+    }
+
+    let slot = self.thread_count;
+    self.thread_count += 1;
+    self.ids[slot] = id;
+    self.threads[slot] = ThreadStats::new(P::thread_tag());
+    slot
+  }
+
+  fn allocated(&mut self, slot: usize, address: usize, size: usize) {
+    let stats = &mut self.threads[slot];
+    stats.allocations += 1;
+    stats.outstanding_bytes += size;
+    stats.peak_bytes = stats.peak_bytes.max(stats.outstanding_bytes);
+
+    let smallest = self.largest.iter_mut().min_by_key(|entry| entry.map_or(0, |(_, allocation)| allocation.size)).unwrap();
+    if !matches!(smallest, Some((_, allocation)) if allocation.size >= size) {
+      *smallest = Some((slot, Allocation { address, size, tag: stats.tag }));
+    }
+  }
+
+  fn deallocated(&mut self, slot: usize, address: usize, size: usize) {
+    let stats = &mut self.threads[slot];
+    stats.deallocations += 1;
+    stats.outstanding_bytes -= size;
+
+    if let Some(entry) = self.largest.iter_mut().find(|entry| matches!(entry, Some((_, allocation)) if allocation.address == address)) {
+      *entry = None;
+    }
+  }
+}
+
+/// A snapshot of the state of a [`LeakTracker`](struct.LeakTracker.html).
+#[derive(Clone, Copy)]
+pub struct Report {
+  state: State,
+}
+
+impl Report {
+  /// Statistics of all threads which allocated memory.
+  pub fn threads(&self) -> impl Iterator<Item = &ThreadStats> {
+    self.state.threads[..self.state.thread_count].iter().filter(|stats| stats.allocations > 0)
+  }
+
+  /// Combined statistics of all threads with the given `tag`.
+  pub fn tag(&self, tag: &str) -> ThreadStats {
+    self.threads().filter(|stats| stats.tag.as_str() == tag).fold(ThreadStats::new(Tag::new(tag)), |mut sum, stats| {
+      sum.allocations += stats.allocations;
+      sum.deallocations += stats.deallocations;
+      sum.outstanding_bytes += stats.outstanding_bytes;
+      sum.peak_bytes += stats.peak_bytes;
+      sum
+    })
+  }
+
+  /// The largest outstanding allocations, largest first.
+  pub fn largest(&self) -> impl Iterator<Item = Allocation> {
+    let mut largest = self.state.largest;
+    largest.sort_unstable_by_key(|entry| core::cmp::Reverse(entry.map(|(_, allocation)| allocation.size)));
+    IntoIterator::into_iter(largest).flatten().map(|(_, allocation)| allocation)
+  }
+
+  /// Total number of outstanding allocations.
+  pub fn outstanding_allocations(&self) -> usize {
+    self.threads().map(ThreadStats::outstanding_allocations).sum()
+  }
+
+  /// Total number of outstanding bytes.
+  pub fn outstanding_bytes(&self) -> usize {
+    self.threads().map(|stats| stats.outstanding_bytes).sum()
+  }
+}
+
+impl fmt::Debug for Report {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Report")
+      .field("threads", &self.threads().collect::<Vec<_>>())
+      .field("largest", &self.largest().collect::<Vec<_>>())
+      .finish()
+  }
+}
+
+impl fmt::Display for Report {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "Heap: {} bytes in {} allocations outstanding", self.outstanding_bytes(), self.outstanding_allocations())?;
+    writeln!(f, "{:<16} {:>10} {:>10} {:>12} {:>12}", "Tag", "Allocs", "Frees", "Outstanding", "Peak")?;
+    for stats in self.threads() {
+      writeln!(f, "{:<16} {:>10} {:>10} {:>12} {:>12}", stats.tag, stats.allocations, stats.deallocations, stats.outstanding_bytes, stats.peak_bytes)?;
+    }
+    writeln!(f, "Largest outstanding allocations:")?;
+    for allocation in self.largest() {
+      writeln!(f, "{:>10} bytes at {:#010x} ({})", allocation.size, allocation.address, allocation.tag)?;
+    }
+    Ok(())
+  }
+}
+
+/// Stored in front of every allocation so it can be accounted to the allocating
+/// thread when it is freed.
+#[derive(Clone, Copy)]
+struct Header {
+  slot: usize,
+  size: usize,
+}
+
+/// Layout including the header and the offset of the user data.
+fn outer_layout(layout: Layout) -> Option<(Layout, usize)> {
+  let align = layout.align().max(mem::align_of::<Header>());
+  let offset = (mem::size_of::<Header>() + align - 1) & !(align - 1);
+  let outer = Layout::from_size_align(layout.size().checked_add(offset)?, align).ok()?;
+  Some((outer, offset))
+}
+
+/// A global allocator wrapper which tracks allocations per thread.
+///
+/// Every allocation is prefixed with a small header recording the allocating thread,
+/// so this should only be used for debugging memory leaks:
+///
+/// ```ignore
+/// use std::alloc::System;
+/// use esp_idf_hal::heap::{FreeRtos, LeakTracker};
+///
+/// #[global_allocator]
+/// static ALLOCATOR: LeakTracker<System, FreeRtos> = LeakTracker::new(System);
+/// ```
+pub struct LeakTracker<A, P> {
+  inner: A,
+  /// Only accessed inside `P::critical_section`.
+  state: UnsafeCell<State>,
+  low_heap_threshold: AtomicUsize,
+  low_heap_reported: AtomicBool,
+  /// Free heap when it dropped below the threshold, `NO_LOW_HEAP` if there is nothing to report.
+  low_heap_free: AtomicUsize,
+  _platform: PhantomData<fn() -> P>,
+}
+
+unsafe impl<A: Sync, P> Sync for LeakTracker<A, P> {}
+
+const NO_LOW_HEAP: usize = usize::MAX;
+
+impl<A, P> LeakTracker<A, P> {
+  /// Track allocations made using `inner`.
+  pub const fn new(inner: A) -> Self {
+    Self {
+      inner,
+      state: UnsafeCell::new(State::new()),
+      low_heap_threshold: AtomicUsize::new(0),
+      low_heap_reported: AtomicBool::new(false),
+      low_heap_free: AtomicUsize::new(NO_LOW_HEAP),
+      _platform: PhantomData,
+    }
+  }
+
+  /// Record when the free heap drops below `bytes`, `0` disables the check.
+  ///
+  /// Allocations only record the free heap, since they may happen on tasks with small stacks.
+  /// Poll [`take_low_heap_report`](#method.take_low_heap_report) from a task with enough stack
+  /// to print the report. Dropping below the threshold is recorded again after the free heap
+  /// recovered above it.
+  pub fn set_low_heap_threshold(&self, bytes: usize) {
+    self.low_heap_threshold.store(bytes, Ordering::Relaxed);
+  }
+}
+
+impl<A, P: Platform> LeakTracker<A, P> {
+  fn with_state<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+    // SAFETY: `state` is only accessed here, inside the critical section.
+    P::critical_section(|| f(unsafe { &mut *self.state.get() }))
+  }
+
+  /// Take a snapshot of the current allocation statistics.
+  ///
+  /// The [`Report`](struct.Report.html) is a few kilobytes large, so this needs a task with enough stack.
+  pub fn report(&self) -> Report {
+    Report { state: self.with_state(|state| *state) }
+  }
+
+  /// The free heap and a snapshot of the current allocation statistics, if the free heap dropped
+  /// below the [low heap threshold](#method.set_low_heap_threshold) since the last call.
+  pub fn take_low_heap_report(&self) -> Option<(usize, Report)> {
+    match self.low_heap_free.swap(NO_LOW_HEAP, Ordering::Relaxed) {
+      NO_LOW_HEAP => None,
+      free => Some((free, self.report())),
+    }
+  }
+
+  /// Account all allocations of the current thread to `tag`.
+  pub fn set_tag(&self, tag: &str) {
+    self.with_state(|state| {
+      let slot = state.slot::<P>();
+
+      if slot != 0 {
+        state.threads[slot].tag = Tag::new(tag);
+      }
+    })
+  }
+
+  fn check_low_heap(&self) {
+    let threshold = self.low_heap_threshold.load(Ordering::Relaxed);
+
+    if threshold == 0 {
+      return
+    }
+
+    if let Some(free) = P::free_heap() {
+      if free >= threshold {
+        self.low_heap_reported.store(false, Ordering::Relaxed);
+      } else if !self.low_heap_reported.swap(true, Ordering::Relaxed) {
+        self.low_heap_free.store(free, Ordering::Relaxed);
+      }
+    }
+  }
+
+  unsafe fn track(&self, outer: *mut u8, offset: usize, size: usize) -> *mut u8 {
+    if outer.is_null() {
+      return outer
+    }
+
+    let ptr = outer.add(offset);
+
+    let slot = self.with_state(|state| {
+      let slot = state.slot::<P>();
+      state.allocated(slot, ptr as usize, size);
+      slot
+    });
+
+    ptr::write_unaligned(ptr.sub(mem::size_of::<Header>()) as *mut Header, Header { slot, size });
+
+    self.check_low_heap();
+
+    ptr
+  }
+
+  /// Account the allocation with the header in front of `ptr`, previously at `address`, as freed.
+  unsafe fn untrack(&self, ptr: *mut u8, address: usize) {
+    let header = ptr::read_unaligned(ptr.sub(mem::size_of::<Header>()) as *const Header);
+    self.with_state(|state| state.deallocated(header.slot, address, header.size));
+  }
+}
+
+impl<A: fmt::Debug, P> fmt::Debug for LeakTracker<A, P> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("LeakTracker")
+      .field("inner", &self.inner)
+      .field("low_heap_threshold", &self.low_heap_threshold)
+      .finish()
+  }
+}
+
+unsafe impl<A: GlobalAlloc, P: Platform> GlobalAlloc for LeakTracker<A, P> {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    match outer_layout(layout) {
+      Some((outer, offset)) => self.track(self.inner.alloc(outer), offset, layout.size()),
+      None => ptr::null_mut(),
+    }
+  }
+
+  unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+    match outer_layout(layout) {
+      Some((outer, offset)) => self.track(self.inner.alloc_zeroed(outer), offset, layout.size()),
+      None => ptr::null_mut(),
+    }
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    let (outer, offset) = outer_layout(layout).unwrap();
+    self.untrack(ptr, ptr as usize);
+    self.inner.dealloc(ptr.sub(offset), outer);
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    let (outer, offset) = outer_layout(layout).unwrap();
+
+    let new_outer_size = match new_size.checked_add(offset) {
+      Some(size) => size,
+      None => return ptr::null_mut(),
+    };
+
+    let new_outer = self.inner.realloc(ptr.sub(offset), outer, new_outer_size);
+    if new_outer.is_null() {
+      return new_outer
+    }
+
+    // The old allocation is gone now, so account the new one as a fresh allocation.
+    self.untrack(new_outer.add(offset), ptr as usize);
+    self.track(new_outer, offset, new_size)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::alloc::System;
+  use std::cell::Cell;
+  use std::sync::Mutex;
+  use std::thread;
+
+  use super::*;
+
+  static LOCK: Mutex<()> = Mutex::new(());
+  static NEXT_THREAD: AtomicUsize = AtomicUsize::new(1);
+  static FREE_HEAP: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+  thread_local! {
+    static THREAD: Cell<usize> = const { Cell::new(0) };
+  }
+
+  struct Host;
+
+  impl Platform for Host {
+    fn current_thread() -> usize {
+      THREAD.with(|id| {
+        if id.get() == 0 {
+          id.set(NEXT_THREAD.fetch_add(1, Ordering::Relaxed));
+        }
+
+        id.get()
+      })
+    }
+
+    fn thread_tag() -> Tag {
+      Tag::new("host")
+    }
+
+    fn free_heap() -> Option<usize> {
+      Some(FREE_HEAP.load(Ordering::Relaxed))
+    }
+
+    fn critical_section<R>(f: impl FnOnce() -> R) -> R {
+      let _guard = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+      f()
+    }
+  }
+
+  fn tracker() -> LeakTracker<System, Host> {
+    LeakTracker::new(System)
+  }
+
+  #[test]
+  fn tag_truncation() {
+    assert_eq!(Tag::new("a_very_long_task_name").as_str(), "a_very_long_task");
+    assert_eq!(Tag::new("ääääääääää").as_str(), "ääääääää");
+  }
+
+  #[test]
+  fn outer_layout_alignment() {
+    let (outer, offset) = outer_layout(Layout::from_size_align(10, 1).unwrap()).unwrap();
+    assert_eq!(offset, mem::size_of::<Header>());
+    assert_eq!(outer.size(), 10 + offset);
+
+    let (outer, offset) = outer_layout(Layout::from_size_align(10, 64).unwrap()).unwrap();
+    assert_eq!(offset, 64);
+    assert_eq!(outer.align(), 64);
+
+    assert!(outer_layout(Layout::from_size_align(isize::MAX as usize - 4, 1).unwrap()).is_none());
+  }
+
+  #[test]
+  fn alloc_dealloc() {
+    let tracker = tracker();
+    tracker.set_tag("alloc");
+
+    let layout = Layout::from_size_align(100, 32).unwrap();
+    let ptr = unsafe { tracker.alloc(layout) };
+    assert_eq!(ptr as usize % 32, 0);
+
+    let zeroed = unsafe { tracker.alloc_zeroed(Layout::new::<[u64; 8]>()) };
+    assert_eq!(unsafe { *(zeroed as *const [u64; 8]) }, [0; 8]);
+
+    let stats = tracker.report().tag("alloc");
+    assert_eq!((stats.allocations, stats.deallocations, stats.outstanding_bytes), (2, 0, 164));
+
+    unsafe { tracker.dealloc(ptr, layout) };
+    unsafe { tracker.dealloc(zeroed, Layout::new::<[u64; 8]>()) };
+
+    let report = tracker.report();
+    let stats = report.tag("alloc");
+    assert_eq!((stats.allocations, stats.deallocations, stats.outstanding_bytes, stats.peak_bytes), (2, 2, 0, 164));
+    assert_eq!(report.outstanding_allocations(), 0);
+    assert_eq!(report.outstanding_bytes(), 0);
+  }
+
+  #[test]
+  fn realloc() {
+    let tracker = tracker();
+    tracker.set_tag("realloc");
+
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    let ptr = unsafe { tracker.alloc(layout) };
+    unsafe { ptr::copy_nonoverlapping(b"0123456789abcdef".as_ptr(), ptr, 16) };
+
+    let ptr = unsafe { tracker.realloc(ptr, layout, 4096) };
+    assert_eq!(ptr as usize % 8, 0);
+    assert_eq!(unsafe { core::slice::from_raw_parts(ptr, 16) }, b"0123456789abcdef");
+
+    let report = tracker.report();
+    let stats = report.tag("realloc");
+    assert_eq!((stats.allocations, stats.deallocations, stats.outstanding_bytes), (2, 1, 4096));
+    assert_eq!(report.largest().map(|allocation| (allocation.address, allocation.size)).collect::<Vec<_>>(), [(ptr as usize, 4096)]);
+
+    unsafe { tracker.dealloc(ptr, Layout::from_size_align(4096, 8).unwrap()) };
+    assert_eq!(tracker.report().tag("realloc").outstanding_bytes, 0);
+    assert_eq!(tracker.report().largest().count(), 0);
+  }
+
+  #[test]
+  fn per_thread_slots() {
+    let tracker = tracker();
+    let layout = Layout::from_size_align(256, 4).unwrap();
+
+    let (leaked, freed) = thread::scope(|scope| {
+      let leaked = scope.spawn(|| {
+        tracker.set_tag("leaky");
+        unsafe { tracker.alloc(layout) as usize }
+      });
+
+      let freed = scope.spawn(|| {
+        tracker.set_tag("tidy");
+        unsafe { tracker.alloc(layout) as usize }
+      });
+
+      (leaked.join().unwrap(), freed.join().unwrap())
+    });
+
+    // Freeing on another thread is accounted to the allocating thread.
+    unsafe { tracker.dealloc(freed as *mut u8, layout) };
+
+    let report = tracker.report();
+    assert_eq!(report.threads().count(), 2);
+    assert_eq!(report.tag("leaky").outstanding_bytes, 256);
+    assert_eq!(report.tag("leaky").outstanding_allocations(), 1);
+    assert_eq!(report.tag("tidy").outstanding_bytes, 0);
+    assert_eq!(report.tag("tidy").deallocations, 1);
+    assert_eq!(report.tag("host").allocations, 0);
+
+    unsafe { tracker.dealloc(leaked as *mut u8, layout) };
+  }
+
+  #[test]
+  fn threads_beyond_table_are_other() {
+    let tracker = tracker();
+    let layout = Layout::from_size_align(8, 4).unwrap();
+
+    let ptrs = (0..(MAX_THREADS + 8)).map(|_| {
+      thread::scope(|scope| scope.spawn(|| unsafe { tracker.alloc(layout) as usize }).join().unwrap())
+    }).collect::<Vec<_>>();
+
+    let report = tracker.report();
+    assert_eq!(report.tag("host").allocations, MAX_THREADS - 1);
+    assert_eq!(report.tag("<other>").allocations, 9);
+
+    for ptr in ptrs {
+      unsafe { tracker.dealloc(ptr as *mut u8, layout) };
+    }
+
+    assert_eq!(tracker.report().outstanding_bytes(), 0);
+  }
+
+  #[test]
+  fn largest_allocations() {
+    let tracker = tracker();
+
+    let mut ptrs = (1..=(MAX_LARGEST + 4)).map(|i| {
+      let layout = Layout::from_size_align(i * 100, 4).unwrap();
+      (unsafe { tracker.alloc(layout) }, layout)
+    }).collect::<Vec<_>>();
+
+    let sizes = tracker.report().largest().map(|allocation| allocation.size).collect::<Vec<_>>();
+    assert_eq!(sizes, (5..=(MAX_LARGEST + 4)).rev().map(|i| i * 100).collect::<Vec<_>>());
+
+    let (largest, layout) = ptrs.pop().unwrap();
+    unsafe { tracker.dealloc(largest, layout) };
+
+    let report = tracker.report();
+    assert!(report.largest().all(|allocation| allocation.address != largest as usize));
+    assert_eq!(report.largest().count(), MAX_LARGEST - 1);
+
+    for (ptr, layout) in ptrs {
+      unsafe { tracker.dealloc(ptr, layout) };
+    }
+
+    assert_eq!(tracker.report().largest().count(), 0);
+  }
+
+  #[test]
+  fn low_heap() {
+    let tracker = tracker();
+    let layout = Layout::new::<u32>();
+    let alloc = || unsafe { tracker.dealloc(tracker.alloc(layout), layout) };
+
+    tracker.set_low_heap_threshold(1000);
+    FREE_HEAP.store(500, Ordering::Relaxed);
+    alloc();
+    FREE_HEAP.store(400, Ordering::Relaxed);
+    alloc();
+
+    let (free, report) = tracker.take_low_heap_report().unwrap();
+    assert_eq!(free, 500);
+    assert_eq!(report.outstanding_bytes(), 0);
+    assert!(tracker.take_low_heap_report().is_none());
+
+    alloc();
+    assert!(tracker.take_low_heap_report().is_none());
+
+    FREE_HEAP.store(2000, Ordering::Relaxed);
+    alloc();
+    FREE_HEAP.store(300, Ordering::Relaxed);
+    alloc();
+    assert_eq!(tracker.take_low_heap_report().map(|(free, _)| free), Some(300));
+
+    FREE_HEAP.store(usize::MAX, Ordering::Relaxed);
+  }
+}
@@ -0,0 +1,206 @@
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{Error, NvsPartition, Value};
+
+fn parse_int<T: TryFrom<i128>>(value: &str) -> Option<T> {
+  let value = value.trim();
+
+  let (negative, digits) = match value.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, value),
+  };
+
+  let n = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+    Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+    None => digits.parse::<i128>().ok()?,
+  };
+
+  T::try_from(if negative { -n } else { n }).ok()
+}
+
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+  let digits = value.chars().filter(|c| !c.is_whitespace()).collect::<Vec<_>>();
+
+  if digits.len() % 2 != 0 {
+    return None
+  }
+
+  digits.chunks(2).map(|pair| {
+    let byte = pair.iter().collect::<String>();
+    u8::from_str_radix(&byte, 16).ok()
+  }).collect()
+}
+
+fn parse_value(encoding: &str, data: Vec<u8>) -> Result<Value, String> {
+  let text = || String::from_utf8(data.clone()).map_err(|_| "value is not valid UTF-8".to_owned());
+  let int_err = || format!("invalid {} value", encoding);
+
+  Ok(match encoding {
+    "u8" => Value::U8(parse_int(&text()?).ok_or_else(int_err)?),
+    "i8" => Value::I8(parse_int(&text()?).ok_or_else(int_err)?),
+    "u16" => Value::U16(parse_int(&text()?).ok_or_else(int_err)?),
+    "i16" => Value::I16(parse_int(&text()?).ok_or_else(int_err)?),
+    "u32" => Value::U32(parse_int(&text()?).ok_or_else(int_err)?),
+    "i32" => Value::I32(parse_int(&text()?).ok_or_else(int_err)?),
+    "u64" => Value::U64(parse_int(&text()?).ok_or_else(int_err)?),
+    "i64" => Value::I64(parse_int(&text()?).ok_or_else(int_err)?),
+    "string" => Value::String(CString::new(data).map_err(|_| "string contains a NUL byte".to_owned())?),
+    "hex2bin" => Value::Blob(parse_hex(&text()?).ok_or_else(|| "invalid hex2bin value".to_owned())?),
+    "base64" => Value::Blob(base64::decode(text()?.trim()).map_err(|err| format!("invalid base64 value: {}", err))?),
+    "binary" => Value::Blob(data),
+    encoding => return Err(format!("unknown encoding {:?}", encoding)),
+  })
+}
+
+pub(crate) fn read(reader: impl io::Read, base_dir: &Path) -> Result<NvsPartition, Error> {
+  let mut reader = csv::ReaderBuilder::new()
+    .comment(Some(b'#'))
+    .flexible(true)
+    .trim(csv::Trim::All)
+    .from_reader(reader);
+
+  let mut partition = NvsPartition::new();
+  let mut namespace = None;
+
+  for record in reader.records() {
+    let record = record?;
+    let line = record.position().map_or(0, |pos| pos.line());
+    let invalid = |message: String| Error::InvalidRow { line, message };
+
+    let field = |i| record.get(i).unwrap_or("");
+    let (key, ty, encoding, value) = (field(0), field(1), field(2).to_lowercase(), field(3));
+
+    let data = match ty {
+      "namespace" => {
+        partition.namespace_mut(key)?;
+        namespace = Some(key.to_owned());
+        continue
+      },
+      "data" if encoding == "binary" => return Err(invalid("binary encoding is only supported for file entries".into())),
+      "data" => value.as_bytes().to_vec(),
+      "file" => fs::read(base_dir.join(value))?,
+      ty => return Err(invalid(format!("unknown type {:?}", ty))),
+    };
+
+    let namespace = namespace.as_ref().ok_or_else(|| invalid("entry before first namespace".into()))?;
+    let value = parse_value(&encoding, data).map_err(invalid)?;
+    partition.namespace_mut(namespace)?.set(key, value)?;
+  }
+
+  Ok(partition)
+}
+
+fn hex(data: &[u8]) -> String {
+  data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn write(partition: &NvsPartition, writer: impl io::Write) -> Result<(), Error> {
+  let mut writer = csv::Writer::from_writer(writer);
+
+  writer.write_record(["key", "type", "encoding", "value"])?;
+
+  for namespace in partition.namespaces() {
+    writer.write_record([namespace.name(), "namespace", "", ""])?;
+
+    for entry in namespace.entries() {
+      let value = match entry.value() {
+        Value::U8(v) => v.to_string(),
+        Value::I8(v) => v.to_string(),
+        Value::U16(v) => v.to_string(),
+        Value::I16(v) => v.to_string(),
+        Value::U32(v) => v.to_string(),
+        Value::I32(v) => v.to_string(),
+        Value::U64(v) => v.to_string(),
+        Value::I64(v) => v.to_string(),
+        Value::String(s) => s.to_str().map_err(|_| {
+          Error::InvalidValue(format!("string for key {:?} is not valid UTF-8", entry.key()))
+        })?.to_owned(),
+        Value::Blob(data) => hex(data),
+      };
+
+      writer.write_record([entry.key(), "data", entry.value().encoding(), &value])?;
+    }
+  }
+
+  writer.flush()?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const CSV: &str = "\
+key,type,encoding,value
+# A comment
+config,namespace,,
+u8,data,u8,255
+i8,data,i8,-128
+u16,data,u16,0xabcd
+i16,data,i16,-1234
+u32,data,u32,4294967295
+i32,data,i32,-0x10
+u64,data,u64,18446744073709551615
+i64,data,i64,-9223372036854775808
+ssid,data,string,My Network
+hex,data,hex2bin,00ff 10ab
+b64,data,base64,aGVsbG8=
+other,namespace,,
+u8,data,u8,1
+";
+
+  fn parse(csv: &str) -> Result<NvsPartition, Error> {
+    NvsPartition::from_csv(csv.as_bytes(), Path::new("."))
+  }
+
+  #[test]
+  fn parse_values() {
+    let partition = parse(CSV).unwrap();
+
+    let config = partition.namespace("config").unwrap();
+    assert_eq!(config.get("u8"), Some(&Value::U8(255)));
+    assert_eq!(config.get("i8"), Some(&Value::I8(-128)));
+    assert_eq!(config.get("u16"), Some(&Value::U16(0xabcd)));
+    assert_eq!(config.get("i16"), Some(&Value::I16(-1234)));
+    assert_eq!(config.get("u32"), Some(&Value::U32(u32::MAX)));
+    assert_eq!(config.get("i32"), Some(&Value::I32(-16)));
+    assert_eq!(config.get("u64"), Some(&Value::U64(u64::MAX)));
+    assert_eq!(config.get("i64"), Some(&Value::I64(i64::MIN)));
+    assert_eq!(config.get("ssid"), Some(&Value::String(CString::new("My Network").unwrap())));
+    assert_eq!(config.get("hex"), Some(&Value::Blob(vec![0x00, 0xff, 0x10, 0xab])));
+    assert_eq!(config.get("b64"), Some(&Value::Blob(b"hello".to_vec())));
+
+    assert_eq!(partition.namespace("other").unwrap().get("u8"), Some(&Value::U8(1)));
+  }
+
+  #[test]
+  fn parse_write_round_trip() {
+    let partition = parse(CSV).unwrap();
+
+    let mut csv = Vec::new();
+    partition.to_csv(&mut csv).unwrap();
+
+    assert_eq!(parse(std::str::from_utf8(&csv).unwrap()).unwrap(), partition);
+  }
+
+  #[test]
+  fn invalid_rows() {
+    assert!(matches!(parse("key,type,encoding,value\nu8,data,u8,1\n"), Err(Error::InvalidRow { line: 2, .. })));
+    assert!(matches!(parse("key,type,encoding,value\nns,namespace,,\nu8,data,u8,256\n"), Err(Error::InvalidRow { line: 3, .. })));
+    assert!(matches!(parse("key,type,encoding,value\nns,namespace,,\nx,data,hex2bin,abc\n"), Err(Error::InvalidRow { .. })));
+    assert!(matches!(parse("key,type,encoding,value\nns,namespace,,\nx,data,float,1.0\n"), Err(Error::InvalidRow { .. })));
+  }
+
+  #[test]
+  fn write_rejects_invalid_utf8() {
+    let mut partition = NvsPartition::new();
+    partition.namespace_mut("ns").unwrap().set("s", CString::new(vec![0xff, 0xfe]).unwrap()).unwrap();
+
+    assert!(matches!(partition.to_csv(Vec::new()), Err(Error::InvalidValue(_))));
+  }
+}
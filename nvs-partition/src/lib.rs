@@ -0,0 +1,218 @@
+//! Reading and writing ESP-IDF NVS partition images on the host.
+//!
+//! The binary layout and the CSV format are compatible with `nvs_partition_gen.py`
+//! from ESP-IDF, so images generated here can be flashed directly and images
+//! dumped from a device can be inspected.
+#![warn(missing_debug_implementations)]
+
+use std::fmt;
+use std::io;
+
+mod page;
+pub use page::Version;
+
+mod value;
+pub use value::Value;
+
+mod read;
+mod write;
+mod csv;
+
+/// Maximum length of keys and namespace names, excluding the `NUL` terminator.
+pub const MAX_KEY_LEN: usize = 15;
+
+/// The error type for reading and writing NVS partitions.
+#[derive(Debug)]
+pub enum Error {
+  /// An I/O error.
+  Io(io::Error),
+  /// An error while reading or writing CSV.
+  Csv(::csv::Error),
+  /// A key or namespace name is empty, too long or contains a `NUL` byte.
+  InvalidKey(String),
+  /// More namespaces than fit into a partition.
+  TooManyNamespaces,
+  /// A CSV row is malformed.
+  InvalidRow { line: u64, message: String },
+  /// A value exceeds the maximum size for its type.
+  ValueTooLong { key: String, len: usize, max: usize },
+  /// The partition size is not a multiple of the page size or is too small.
+  InvalidSize(usize),
+  /// The values do not fit into the partition.
+  NotEnoughSpace,
+  /// A page or entry is malformed or has a wrong checksum.
+  Corrupt { page: usize, entry: Option<usize>, message: &'static str },
+  /// Chunks of a blob are missing or do not add up to its size.
+  InvalidBlob(String),
+  /// A value cannot be stored or written the way the device expects it.
+  InvalidValue(String),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(err) => err.fmt(f),
+      Self::Csv(err) => err.fmt(f),
+      Self::InvalidKey(key) => write!(f, "invalid key or namespace name {:?}, must be 1 to {} bytes without NUL", key, MAX_KEY_LEN),
+      Self::TooManyNamespaces => write!(f, "too many namespaces, maximum is {}", page::MAX_NAMESPACES),
+      Self::InvalidRow { line, message } => write!(f, "line {}: {}", line, message),
+      Self::ValueTooLong { key, len, max } => write!(f, "value for key {:?} is {} bytes long, but maximum is {} bytes", key, len, max),
+      Self::InvalidSize(size) => write!(f, "invalid partition size {:#x}, must be a multiple of {:#x} and at least {:#x}", size, page::PAGE_SIZE, page::MIN_PARTITION_SIZE),
+      Self::NotEnoughSpace => write!(f, "values do not fit into partition"),
+      Self::Corrupt { page, entry: Some(entry), message } => write!(f, "page {}, entry {}: {}", page, entry, message),
+      Self::Corrupt { page, entry: None, message } => write!(f, "page {}: {}", page, message),
+      Self::InvalidBlob(key) => write!(f, "blob {:?} has missing or mismatching chunks", key),
+      Self::InvalidValue(message) => message.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Io(err) => Some(err),
+      Self::Csv(err) => Some(err),
+      _ => None,
+    }
+  }
+}
+
+impl From<io::Error> for Error {
+  fn from(err: io::Error) -> Self {
+    Self::Io(err)
+  }
+}
+
+impl From<::csv::Error> for Error {
+  fn from(err: ::csv::Error) -> Self {
+    Self::Csv(err)
+  }
+}
+
+fn validate_key(key: &str) -> Result<(), Error> {
+  if key.is_empty() || key.len() > MAX_KEY_LEN || key.contains('\0') {
+    return Err(Error::InvalidKey(key.to_owned()))
+  }
+
+  Ok(())
+}
+
+/// A key-value pair in a [`Namespace`](struct.Namespace.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+  key: String,
+  value: Value,
+}
+
+impl Entry {
+  pub fn key(&self) -> &str {
+    &self.key
+  }
+
+  pub fn value(&self) -> &Value {
+    &self.value
+  }
+}
+
+/// A namespace in an [`NvsPartition`](struct.NvsPartition.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Namespace {
+  name: String,
+  entries: Vec<Entry>,
+}
+
+impl Namespace {
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Iterate over all entries in the order they are written.
+  pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+    self.entries.iter()
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Value> {
+    self.entries.iter().find(|entry| entry.key == key).map(|entry| &entry.value)
+  }
+
+  /// Set `key` to `value`, replacing an existing value in place.
+  ///
+  /// Values are converted the same way `NvsSet` stores them on the device,
+  /// e.g. `&str` is stored as a blob and `CString` as a string.
+  pub fn set(&mut self, key: &str, value: impl Into<Value>) -> Result<(), Error> {
+    validate_key(key)?;
+
+    let value = value.into();
+
+    match self.entries.iter_mut().find(|entry| entry.key == key) {
+      Some(entry) => entry.value = value,
+      None => self.entries.push(Entry { key: key.to_owned(), value }),
+    }
+
+    Ok(())
+  }
+
+  pub fn remove(&mut self, key: &str) -> Option<Value> {
+    let pos = self.entries.iter().position(|entry| entry.key == key)?;
+    Some(self.entries.remove(pos).value)
+  }
+}
+
+/// The contents of an NVS partition.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NvsPartition {
+  namespaces: Vec<Namespace>,
+}
+
+impl NvsPartition {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Iterate over all namespaces in the order they are written.
+  pub fn namespaces(&self) -> impl Iterator<Item = &Namespace> {
+    self.namespaces.iter()
+  }
+
+  pub fn namespace(&self, name: &str) -> Option<&Namespace> {
+    self.namespaces.iter().find(|ns| ns.name == name)
+  }
+
+  /// Get the namespace with the given `name`, creating it if it does not exist.
+  pub fn namespace_mut(&mut self, name: &str) -> Result<&mut Namespace, Error> {
+    validate_key(name)?;
+
+    if let Some(pos) = self.namespaces.iter().position(|ns| ns.name == name) {
+      return Ok(&mut self.namespaces[pos])
+    }
+
+    if self.namespaces.len() >= page::MAX_NAMESPACES {
+      return Err(Error::TooManyNamespaces)
+    }
+
+    self.namespaces.push(Namespace { name: name.to_owned(), entries: Vec::new() });
+    Ok(self.namespaces.last_mut().unwrap())
+  }
+
+  /// Parse a partition image, e.g. one read back from a device using `esptool.py read_flash`.
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+    read::read(bytes)
+  }
+
+  /// Generate a partition image of `size` bytes.
+  pub fn to_bytes(&self, size: usize, version: Version) -> Result<Vec<u8>, Error> {
+    write::write(self, size, version)
+  }
+
+  /// Read a CSV file in the format used by `nvs_partition_gen.py`.
+  ///
+  /// Paths of `file` entries are resolved relative to `base_dir`.
+  pub fn from_csv(reader: impl io::Read, base_dir: &std::path::Path) -> Result<Self, Error> {
+    csv::read(reader, base_dir)
+  }
+
+  /// Write a CSV file in the format used by `nvs_partition_gen.py`.
+  pub fn to_csv(&self, writer: impl io::Write) -> Result<(), Error> {
+    csv::write(self, writer)
+  }
+}
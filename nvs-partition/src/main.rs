@@ -0,0 +1,74 @@
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::process;
+
+use nvs_partition::{NvsPartition, Version};
+
+const USAGE: &str = "\
+Usage:
+  nvs-partition generate <input.csv> <output.bin> <size> [--version <1|2>]
+  nvs-partition parse <input.bin> [<output.csv>]";
+
+fn parse_size(size: &str) -> Option<usize> {
+  match size.strip_prefix("0x").or_else(|| size.strip_prefix("0X")) {
+    Some(hex) => usize::from_str_radix(hex, 16).ok(),
+    None => size.parse().ok(),
+  }
+}
+
+fn generate(args: &[String]) -> Result<(), Box<dyn Error>> {
+  let (input, output, size, version) = match args {
+    [input, output, size] => (input, output, size, Version::V2),
+    [input, output, size, flag, version] if flag == "--version" => {
+      let version = match version.as_str() {
+        "1" => Version::V1,
+        "2" => Version::V2,
+        _ => return Err(format!("invalid version: {}", version).into()),
+      };
+      (input, output, size, version)
+    },
+    _ => return Err(USAGE.into()),
+  };
+
+  let size = parse_size(size).ok_or_else(|| format!("invalid size: {}", size))?;
+
+  let input = Path::new(input);
+  let base_dir = input.parent().unwrap_or_else(|| Path::new("."));
+  let partition = NvsPartition::from_csv(File::open(input)?, base_dir)?;
+
+  fs::write(output, partition.to_bytes(size, version)?)?;
+
+  Ok(())
+}
+
+fn parse(args: &[String]) -> Result<(), Box<dyn Error>> {
+  let partition = match args {
+    [input] | [input, _] => NvsPartition::from_bytes(&fs::read(input)?)?,
+    _ => return Err(USAGE.into()),
+  };
+
+  match args.get(1) {
+    Some(output) => partition.to_csv(File::create(output)?)?,
+    None => partition.to_csv(io::stdout())?,
+  }
+
+  Ok(())
+}
+
+fn main() {
+  let args = env::args().skip(1).collect::<Vec<_>>();
+
+  let res = match args.split_first() {
+    Some((command, args)) if command == "generate" => generate(args),
+    Some((command, args)) if command == "parse" => parse(args),
+    _ => Err(USAGE.into()),
+  };
+
+  if let Err(err) = res {
+    eprintln!("{}", err);
+    process::exit(1);
+  }
+}
@@ -0,0 +1,292 @@
+use std::convert::TryFrom;
+
+pub(crate) const PAGE_SIZE: usize = 4096;
+/// One page is always kept empty for garbage collection.
+pub(crate) const MIN_PARTITION_SIZE: usize = 3 * PAGE_SIZE;
+
+pub(crate) const ENTRY_SIZE: usize = 32;
+pub(crate) const ENTRY_COUNT: usize = 126;
+
+const BITMAP_OFFSET: usize = 32;
+const FIRST_ENTRY_OFFSET: usize = 64;
+
+/// Maximum size of strings and of blobs in version 1 images.
+pub(crate) const MAX_SINGLE_PAGE_DATA_SIZE: usize = 1984;
+
+pub(crate) const CHUNK_ANY: u8 = 0xff;
+
+/// Namespace index `0` holds the namespace entries themselves, `255` means “any namespace”.
+pub(crate) const MAX_NAMESPACES: usize = 254;
+
+pub(crate) const STATE_ACTIVE: u32 = 0xffff_fffe;
+pub(crate) const STATE_FULL: u32 = 0xffff_fffc;
+pub(crate) const STATE_FREEING: u32 = 0xffff_fff8;
+
+/// Version of the NVS page format.
+///
+/// Version 2 stores blobs in chunks which may span multiple pages and is used
+/// since ESP-IDF 4.0. Version 1 only supports blobs which fit into a single page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Version {
+  V1,
+  #[default]
+  V2,
+}
+
+impl From<Version> for u8 {
+  fn from(version: Version) -> Self {
+    match version {
+      Version::V1 => 0xff,
+      Version::V2 => 0xfe,
+    }
+  }
+}
+
+impl TryFrom<u8> for Version {
+  type Error = ();
+
+  fn try_from(version: u8) -> Result<Self, ()> {
+    match version {
+      0xff => Ok(Self::V1),
+      0xfe => Ok(Self::V2),
+      _ => Err(()),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum ItemType {
+  U8 = 0x01,
+  I8 = 0x11,
+  U16 = 0x02,
+  I16 = 0x12,
+  U32 = 0x04,
+  I32 = 0x14,
+  U64 = 0x08,
+  I64 = 0x18,
+  Str = 0x21,
+  Blob = 0x41,
+  BlobData = 0x42,
+  BlobIndex = 0x48,
+}
+
+impl TryFrom<u8> for ItemType {
+  type Error = ();
+
+  fn try_from(ty: u8) -> Result<Self, ()> {
+    Ok(match ty {
+      0x01 => Self::U8,
+      0x11 => Self::I8,
+      0x02 => Self::U16,
+      0x12 => Self::I16,
+      0x04 => Self::U32,
+      0x14 => Self::I32,
+      0x08 => Self::U64,
+      0x18 => Self::I64,
+      0x21 => Self::Str,
+      0x41 => Self::Blob,
+      0x42 => Self::BlobData,
+      0x48 => Self::BlobIndex,
+      _ => return Err(()),
+    })
+  }
+}
+
+/// CRC32 as computed by `esp_rom_crc32_le` and Python's `zlib.crc32`.
+pub(crate) fn crc32(init: u32, data: &[u8]) -> u32 {
+  let mut crc = !init;
+
+  for &byte in data {
+    crc ^= u32::from(byte);
+
+    for _ in 0..8 {
+      crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+    }
+  }
+
+  !crc
+}
+
+/// State of an entry in the page bitmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EntryState {
+  Empty,
+  Written,
+  Erased,
+  Invalid,
+}
+
+/// The header of an item, occupying the first of its `span` entries.
+#[derive(Debug, Clone)]
+pub(crate) struct EntryHeader {
+  pub ns: u8,
+  pub ty: u8,
+  pub span: u8,
+  pub chunk_index: u8,
+  pub key: [u8; 16],
+  pub data: [u8; 8],
+}
+
+impl EntryHeader {
+  pub fn new(ns: u8, ty: ItemType, span: u8, key: &str) -> Self {
+    let mut key_bytes = [0; 16];
+    key_bytes[..key.len()].copy_from_slice(key.as_bytes());
+
+    Self { ns, ty: ty as u8, span, chunk_index: CHUNK_ANY, key: key_bytes, data: [0xff; 8] }
+  }
+
+  pub fn key(&self) -> Option<&str> {
+    let len = self.key.iter().position(|&b| b == 0)?;
+    std::str::from_utf8(&self.key[..len]).ok()
+  }
+
+  /// Set the data field of a string, blob or blob chunk header.
+  pub fn set_varlen(&mut self, data: &[u8]) {
+    self.data[0..2].copy_from_slice(&(data.len() as u16).to_le_bytes());
+    self.data[4..8].copy_from_slice(&crc32(0xffff_ffff, data).to_le_bytes());
+  }
+
+  pub fn varlen_size(&self) -> usize {
+    u16::from_le_bytes([self.data[0], self.data[1]]) as usize
+  }
+
+  pub fn varlen_crc(&self) -> u32 {
+    u32::from_le_bytes([self.data[4], self.data[5], self.data[6], self.data[7]])
+  }
+
+  fn crc(bytes: &[u8; ENTRY_SIZE]) -> u32 {
+    crc32(crc32(0xffff_ffff, &bytes[0..4]), &bytes[8..32])
+  }
+
+  pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+    let mut bytes = [0xff; ENTRY_SIZE];
+    bytes[0] = self.ns;
+    bytes[1] = self.ty;
+    bytes[2] = self.span;
+    bytes[3] = self.chunk_index;
+    bytes[8..24].copy_from_slice(&self.key);
+    bytes[24..32].copy_from_slice(&self.data);
+
+    let crc = Self::crc(&bytes);
+    bytes[4..8].copy_from_slice(&crc.to_le_bytes());
+    bytes
+  }
+
+  /// Parse an entry header, returning `None` if its checksum does not match.
+  pub fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> Option<Self> {
+    if u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) != Self::crc(bytes) {
+      return None
+    }
+
+    let mut key = [0; 16];
+    key.copy_from_slice(&bytes[8..24]);
+    let mut data = [0; 8];
+    data.copy_from_slice(&bytes[24..32]);
+
+    Some(Self { ns: bytes[0], ty: bytes[1], span: bytes[2], chunk_index: bytes[3], key, data })
+  }
+}
+
+/// A single 4 KiB page.
+#[derive(Debug, Clone)]
+pub(crate) struct Page {
+  buf: Vec<u8>,
+  entry_count: usize,
+}
+
+impl Page {
+  pub fn new(seqno: u32, version: Version) -> Self {
+    let mut buf = vec![0xff; PAGE_SIZE];
+    buf[0..4].copy_from_slice(&STATE_ACTIVE.to_le_bytes());
+    buf[4..8].copy_from_slice(&seqno.to_le_bytes());
+    buf[8] = version.into();
+
+    let crc = crc32(0xffff_ffff, &buf[4..28]);
+    buf[28..32].copy_from_slice(&crc.to_le_bytes());
+
+    Self { buf, entry_count: 0 }
+  }
+
+  pub fn state(&self) -> u32 {
+    u32::from_le_bytes([self.buf[0], self.buf[1], self.buf[2], self.buf[3]])
+  }
+
+  pub fn set_state(&mut self, state: u32) {
+    self.buf[0..4].copy_from_slice(&state.to_le_bytes());
+  }
+
+  pub fn entry_count(&self) -> usize {
+    self.entry_count
+  }
+
+  /// Write `data` into the next entries, padding the last one with `0xff`, and mark them as written.
+  pub fn write_entries(&mut self, data: &[u8]) {
+    let count = data.len().div_ceil(ENTRY_SIZE);
+    assert!(self.entry_count + count <= ENTRY_COUNT, "page overflow");
+
+    let offset = FIRST_ENTRY_OFFSET + self.entry_count * ENTRY_SIZE;
+    self.buf[offset..(offset + data.len())].copy_from_slice(data);
+
+    for _ in 0..count {
+      let bit = self.entry_count * 2;
+      self.buf[BITMAP_OFFSET + bit / 8] &= !(1 << (bit % 8));
+      self.entry_count += 1;
+    }
+  }
+
+  pub fn into_bytes(self) -> Vec<u8> {
+    self.buf
+  }
+}
+
+/// Read-only view of a page in an existing image.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PageRef<'a> {
+  buf: &'a [u8],
+}
+
+impl<'a> PageRef<'a> {
+  pub fn new(buf: &'a [u8]) -> Self {
+    debug_assert_eq!(buf.len(), PAGE_SIZE);
+    Self { buf }
+  }
+
+  pub fn state(&self) -> u32 {
+    u32::from_le_bytes([self.buf[0], self.buf[1], self.buf[2], self.buf[3]])
+  }
+
+  pub fn seqno(&self) -> u32 {
+    u32::from_le_bytes([self.buf[4], self.buf[5], self.buf[6], self.buf[7]])
+  }
+
+  pub fn version(&self) -> Option<Version> {
+    Version::try_from(self.buf[8]).ok()
+  }
+
+  pub fn header_crc_valid(&self) -> bool {
+    let crc = u32::from_le_bytes([self.buf[28], self.buf[29], self.buf[30], self.buf[31]]);
+    crc == crc32(0xffff_ffff, &self.buf[4..28])
+  }
+
+  pub fn entry_state(&self, index: usize) -> EntryState {
+    let bit = index * 2;
+    match (self.buf[BITMAP_OFFSET + bit / 8] >> (bit % 8)) & 0b11 {
+      0b11 => EntryState::Empty,
+      0b10 => EntryState::Written,
+      0b00 => EntryState::Erased,
+      _ => EntryState::Invalid,
+    }
+  }
+
+  pub fn entry(&self, index: usize) -> &'a [u8; ENTRY_SIZE] {
+    let offset = FIRST_ENTRY_OFFSET + index * ENTRY_SIZE;
+    <&[u8; ENTRY_SIZE]>::try_from(&self.buf[offset..(offset + ENTRY_SIZE)]).unwrap()
+  }
+
+  /// Data of the `count` entries following the entry at `index`.
+  pub fn data(&self, index: usize, count: usize) -> &'a [u8] {
+    let offset = FIRST_ENTRY_OFFSET + (index + 1) * ENTRY_SIZE;
+    &self.buf[offset..(offset + count * ENTRY_SIZE)]
+  }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::CString;
+
+use crate::{Error, NvsPartition, Value};
+use crate::page::*;
+
+/// An item found in a page, before blob chunks are reassembled.
+#[derive(Debug)]
+enum Item {
+  Value(Value),
+  BlobIndex { size: usize, chunk_count: u8, chunk_start: u8 },
+}
+
+fn corrupt(page: usize, entry: usize, message: &'static str) -> Error {
+  Error::Corrupt { page, entry: Some(entry), message }
+}
+
+fn primitive(ty: ItemType, data: &[u8; 8]) -> Value {
+  let mut b = [0; 8];
+  b.copy_from_slice(data);
+
+  match ty {
+    ItemType::U8 => Value::U8(b[0]),
+    ItemType::I8 => Value::I8(b[0] as i8),
+    ItemType::U16 => Value::U16(u16::from_le_bytes([b[0], b[1]])),
+    ItemType::I16 => Value::I16(i16::from_le_bytes([b[0], b[1]])),
+    ItemType::U32 => Value::U32(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+    ItemType::I32 => Value::I32(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+    ItemType::U64 => Value::U64(u64::from_le_bytes(b)),
+    ItemType::I64 => Value::I64(i64::from_le_bytes(b)),
+    _ => unreachable!(),
+  }
+}
+
+pub(crate) fn read(bytes: &[u8]) -> Result<NvsPartition, Error> {
+  if !bytes.len().is_multiple_of(PAGE_SIZE) {
+    return Err(Error::InvalidSize(bytes.len()))
+  }
+
+  let mut pages = bytes.chunks(PAGE_SIZE).map(PageRef::new).enumerate()
+    .filter(|(_, page)| matches!(page.state(), STATE_ACTIVE | STATE_FULL | STATE_FREEING))
+    .collect::<Vec<_>>();
+  pages.sort_by_key(|(_, page)| page.seqno());
+
+  let mut namespaces = HashMap::<u8, String>::new();
+  // Items in the order they were written, later writes replacing earlier ones.
+  let mut items = Vec::<((u8, String), Item)>::new();
+  let mut chunks = HashMap::<(u8, String, u8), Vec<u8>>::new();
+
+  for (page_index, page) in pages {
+    if !page.header_crc_valid() {
+      return Err(Error::Corrupt { page: page_index, entry: None, message: "invalid page header checksum" })
+    }
+
+    if page.version().is_none() {
+      return Err(Error::Corrupt { page: page_index, entry: None, message: "unknown page version" })
+    }
+
+    let mut i = 0;
+    while i < ENTRY_COUNT {
+      match page.entry_state(i) {
+        EntryState::Empty => break,
+        EntryState::Erased | EntryState::Invalid => {
+          i += 1;
+          continue
+        },
+        EntryState::Written => (),
+      }
+
+      let header = EntryHeader::from_bytes(page.entry(i)).ok_or_else(|| corrupt(page_index, i, "invalid entry checksum"))?;
+      let span = header.span as usize;
+      if span == 0 || i + span > ENTRY_COUNT {
+        return Err(corrupt(page_index, i, "invalid entry span"))
+      }
+
+      let key = header.key().ok_or_else(|| corrupt(page_index, i, "invalid key"))?.to_owned();
+      let ty = ItemType::try_from(header.ty).map_err(|_| corrupt(page_index, i, "unknown item type"))?;
+
+      let varlen_data = || {
+        let data = &page.data(i, span - 1)[..header.varlen_size().min((span - 1) * ENTRY_SIZE)];
+        if crc32(0xffff_ffff, data) == header.varlen_crc() {
+          Ok(data)
+        } else {
+          Err(corrupt(page_index, i, "invalid data checksum"))
+        }
+      };
+
+      let item = match ty {
+        ItemType::U8 if header.ns == 0 => {
+          namespaces.insert(header.data[0], key.clone());
+          None
+        },
+        ItemType::Str => {
+          let s = CString::from_vec_with_nul(varlen_data()?.to_vec()).map_err(|_| corrupt(page_index, i, "invalid string"))?;
+          Some(Item::Value(Value::String(s)))
+        },
+        ItemType::Blob => Some(Item::Value(Value::Blob(varlen_data()?.to_vec()))),
+        ItemType::BlobData => {
+          chunks.insert((header.ns, key.clone(), header.chunk_index), varlen_data()?.to_vec());
+          None
+        },
+        ItemType::BlobIndex => Some(Item::BlobIndex {
+          size: u32::from_le_bytes([header.data[0], header.data[1], header.data[2], header.data[3]]) as usize,
+          chunk_count: header.data[4],
+          chunk_start: header.data[5],
+        }),
+        ty => Some(Item::Value(primitive(ty, &header.data))),
+      };
+
+      if let Some(item) = item {
+        let id = (header.ns, key);
+        items.retain(|(other, _)| *other != id);
+        items.push((id, item));
+      }
+
+      i += span;
+    }
+  }
+
+  let mut partition = NvsPartition::new();
+
+  for ((ns, key), item) in items {
+    let name = match namespaces.get(&ns) {
+      Some(name) => name,
+      None => continue,
+    };
+
+    let value = match item {
+      Item::Value(value) => value,
+      Item::BlobIndex { size, chunk_count, chunk_start } => {
+        let mut data = Vec::with_capacity(size);
+
+        for chunk_index in (0..chunk_count).map(|i| chunk_start.wrapping_add(i)) {
+          match chunks.remove(&(ns, key.clone(), chunk_index)) {
+            Some(chunk) => data.extend(chunk),
+            None => return Err(Error::InvalidBlob(key)),
+          }
+        }
+
+        if data.len() != size {
+          return Err(Error::InvalidBlob(key))
+        }
+
+        Value::Blob(data)
+      },
+    };
+
+    partition.namespace_mut(name)?.set(&key, value)?;
+  }
+
+  Ok(partition)
+}
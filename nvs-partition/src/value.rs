@@ -0,0 +1,234 @@
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use macaddr::MacAddr6;
+
+use crate::Error;
+use crate::page::ItemType;
+
+const SSID_MAX_LEN: usize = 32;
+const PASSWORD_MAX_LEN: usize = 64;
+
+/// A value stored in an NVS partition.
+///
+/// The `From` implementations mirror the `NvsSet` implementations in `esp-idf-hal`,
+/// so a value produced here is stored exactly like the same Rust value written on the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+  U8(u8),
+  I8(i8),
+  U16(u16),
+  I16(i16),
+  U32(u32),
+  I32(i32),
+  U64(u64),
+  I64(i64),
+  /// A `NUL`-terminated string.
+  String(CString),
+  /// Arbitrary binary data.
+  Blob(Vec<u8>),
+}
+
+impl Value {
+  pub(crate) fn item_type(&self) -> ItemType {
+    match self {
+      Self::U8(_) => ItemType::U8,
+      Self::I8(_) => ItemType::I8,
+      Self::U16(_) => ItemType::U16,
+      Self::I16(_) => ItemType::I16,
+      Self::U32(_) => ItemType::U32,
+      Self::I32(_) => ItemType::I32,
+      Self::U64(_) => ItemType::U64,
+      Self::I64(_) => ItemType::I64,
+      Self::String(_) => ItemType::Str,
+      Self::Blob(_) => ItemType::Blob,
+    }
+  }
+
+  /// Name of the encoding used for this value in CSV files.
+  pub fn encoding(&self) -> &'static str {
+    match self {
+      Self::U8(_) => "u8",
+      Self::I8(_) => "i8",
+      Self::U16(_) => "u16",
+      Self::I16(_) => "i16",
+      Self::U32(_) => "u32",
+      Self::I32(_) => "i32",
+      Self::U64(_) => "u64",
+      Self::I64(_) => "i64",
+      Self::String(_) => "string",
+      Self::Blob(_) => "hex2bin",
+    }
+  }
+
+  /// A string stored like `NvsSet for Ssid`, which must be at most 32 bytes long.
+  pub fn ssid(ssid: &str) -> Result<Self, Error> {
+    Self::wifi_string("SSID", ssid, SSID_MAX_LEN)
+  }
+
+  /// A string stored like `NvsSet for Password`, which must be at most 64 bytes long.
+  pub fn password(password: &str) -> Result<Self, Error> {
+    Self::wifi_string("password", password, PASSWORD_MAX_LEN)
+  }
+
+  fn wifi_string(name: &str, value: &str, max_len: usize) -> Result<Self, Error> {
+    if value.len() > max_len {
+      return Err(Error::InvalidValue(format!("{} is {} bytes long, but maximum is {} bytes", name, value.len(), max_len)))
+    }
+
+    CString::new(value)
+      .map(Self::String)
+      .map_err(|_| Error::InvalidValue(format!("{} contains a NUL byte", name)))
+  }
+
+  /// Little-endian representation of a primitive value, `None` for strings and blobs.
+  pub(crate) fn primitive_bytes(&self) -> Option<Vec<u8>> {
+    Some(match *self {
+      Self::U8(v) => v.to_le_bytes().to_vec(),
+      Self::I8(v) => v.to_le_bytes().to_vec(),
+      Self::U16(v) => v.to_le_bytes().to_vec(),
+      Self::I16(v) => v.to_le_bytes().to_vec(),
+      Self::U32(v) => v.to_le_bytes().to_vec(),
+      Self::I32(v) => v.to_le_bytes().to_vec(),
+      Self::U64(v) => v.to_le_bytes().to_vec(),
+      Self::I64(v) => v.to_le_bytes().to_vec(),
+      Self::String(_) | Self::Blob(_) => return None,
+    })
+  }
+}
+
+macro_rules! value_from {
+  ($ty:ty as $as_ty:ty, $variant:ident) => {
+    impl From<$ty> for Value {
+      fn from(value: $ty) -> Self {
+        Self::$variant(value as $as_ty)
+      }
+    }
+  };
+  ($ty:ty, $variant:ident) => {
+    value_from!($ty as $ty, $variant);
+  };
+}
+
+value_from!(bool as u8, U8);
+
+value_from!( i8,  I8);
+value_from!(i16, I16);
+value_from!(i32, I32);
+value_from!(i64, I64);
+value_from!( u8,  U8);
+value_from!(u16, U16);
+value_from!(u32, U32);
+value_from!(u64, U64);
+
+impl From<&CStr> for Value {
+  fn from(value: &CStr) -> Self {
+    Self::String(value.to_owned())
+  }
+}
+
+impl From<CString> for Value {
+  fn from(value: CString) -> Self {
+    Self::String(value)
+  }
+}
+
+impl From<&[u8]> for Value {
+  fn from(value: &[u8]) -> Self {
+    Self::Blob(value.to_vec())
+  }
+}
+
+impl From<Vec<u8>> for Value {
+  fn from(value: Vec<u8>) -> Self {
+    Self::Blob(value)
+  }
+}
+
+/// Stored as a blob, like `NvsSet for &str`.
+impl From<&str> for Value {
+  fn from(value: &str) -> Self {
+    Self::Blob(value.as_bytes().to_vec())
+  }
+}
+
+/// Stored as a blob, like `NvsSet for String`.
+impl From<String> for Value {
+  fn from(value: String) -> Self {
+    Self::Blob(value.into_bytes())
+  }
+}
+
+/// Stored as `u32` using `f32::to_bits`, like `NvsSet for f32`.
+impl From<f32> for Value {
+  fn from(value: f32) -> Self {
+    Self::U32(value.to_bits())
+  }
+}
+
+/// Stored as `u64` using `f64::to_bits`, like `NvsSet for f64`.
+impl From<f64> for Value {
+  fn from(value: f64) -> Self {
+    Self::U64(value.to_bits())
+  }
+}
+
+/// Stored as `u32` in host byte order, like `NvsSet for Ipv4Addr`.
+impl From<Ipv4Addr> for Value {
+  fn from(value: Ipv4Addr) -> Self {
+    Self::U32(u32::from(value))
+  }
+}
+
+/// Stored as a 6 byte blob, like `NvsSet for MacAddr6`.
+impl From<MacAddr6> for Value {
+  fn from(value: MacAddr6) -> Self {
+    Self::Blob(value.as_bytes().to_vec())
+  }
+}
+
+/// Stored as `u64` nanoseconds, like `NvsSet for Duration`, which rejects longer durations.
+impl TryFrom<Duration> for Value {
+  type Error = Error;
+
+  fn try_from(value: Duration) -> Result<Self, Error> {
+    u64::try_from(value.as_nanos())
+      .map(Self::U64)
+      .map_err(|_| Error::InvalidValue(format!("duration {:?} does not fit into u64 nanoseconds", value)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn device_encodings() {
+    assert_eq!(Value::from(true), Value::U8(1));
+    assert_eq!(Value::from(1.5f32), Value::U32(0x3fc0_0000));
+    assert_eq!(Value::from(-2.0f64), Value::U64(0xc000_0000_0000_0000));
+    assert_eq!(Value::from(Ipv4Addr::new(192, 168, 4, 1)), Value::U32(0xc0a8_0401));
+    assert_eq!(Value::from(MacAddr6::new(1, 2, 3, 4, 5, 6)), Value::Blob(vec![1, 2, 3, 4, 5, 6]));
+    assert_eq!(Value::from("text"), Value::Blob(b"text".to_vec()));
+  }
+
+  #[test]
+  fn duration() {
+    assert_eq!(Value::try_from(Duration::from_millis(1500)).unwrap(), Value::U64(1_500_000_000));
+    assert_eq!(Value::try_from(Duration::from_nanos(u64::MAX)).unwrap(), Value::U64(u64::MAX));
+    assert!(matches!(Value::try_from(Duration::from_secs(u64::MAX)), Err(Error::InvalidValue(_))));
+  }
+
+  #[test]
+  fn ssid_and_password() {
+    assert_eq!(Value::ssid("network").unwrap(), Value::String(CString::new("network").unwrap()));
+    assert!(Value::ssid(&"a".repeat(SSID_MAX_LEN)).is_ok());
+    assert!(matches!(Value::ssid(&"a".repeat(SSID_MAX_LEN + 1)), Err(Error::InvalidValue(_))));
+    assert!(matches!(Value::ssid("a\0b"), Err(Error::InvalidValue(_))));
+
+    assert!(Value::password(&"a".repeat(PASSWORD_MAX_LEN)).is_ok());
+    assert!(matches!(Value::password(&"a".repeat(PASSWORD_MAX_LEN + 1)), Err(Error::InvalidValue(_))));
+  }
+}
@@ -0,0 +1,347 @@
+use crate::{Error, NvsPartition, Value};
+use crate::page::*;
+
+/// Lays out items the same way `nvs_partition_gen.py` does.
+struct Writer {
+  version: Version,
+  pages: Vec<Page>,
+  /// Bytes left for pages other than the reserved one.
+  remaining: usize,
+}
+
+impl Writer {
+  fn new(size: usize, version: Version) -> Result<Self, Error> {
+    if !size.is_multiple_of(PAGE_SIZE) || size < MIN_PARTITION_SIZE {
+      return Err(Error::InvalidSize(size))
+    }
+
+    let mut writer = Self { version, pages: Vec::new(), remaining: size - PAGE_SIZE };
+    writer.new_page()?;
+    Ok(writer)
+  }
+
+  fn page(&mut self) -> &mut Page {
+    self.pages.last_mut().unwrap()
+  }
+
+  fn new_page(&mut self) -> Result<(), Error> {
+    if self.remaining == 0 {
+      return Err(Error::NotEnoughSpace)
+    }
+
+    if let Some(page) = self.pages.last_mut() {
+      if page.state() == STATE_ACTIVE {
+        page.set_state(STATE_FULL);
+      }
+    }
+
+    self.remaining -= PAGE_SIZE;
+    let seqno = self.pages.len() as u32;
+    self.pages.push(Page::new(seqno, self.version));
+
+    Ok(())
+  }
+
+  fn write_primitive(&mut self, ns: u8, key: &str, ty: ItemType, bytes: &[u8]) -> Result<(), Error> {
+    if self.page().entry_count() >= ENTRY_COUNT {
+      self.new_page()?;
+    }
+
+    let mut header = EntryHeader::new(ns, ty, 1, key);
+    header.data[..bytes.len()].copy_from_slice(bytes);
+    self.page().write_entries(&header.to_bytes());
+
+    Ok(())
+  }
+
+  /// Write a string or a version 1 blob, which must fit into a single page.
+  fn write_single_page(&mut self, ns: u8, key: &str, ty: ItemType, data: &[u8]) -> Result<(), Error> {
+    if data.len() > MAX_SINGLE_PAGE_DATA_SIZE {
+      return Err(Error::ValueTooLong { key: key.to_owned(), len: data.len(), max: MAX_SINGLE_PAGE_DATA_SIZE })
+    }
+
+    let data_entry_count = data.len().div_ceil(ENTRY_SIZE);
+
+    if self.page().entry_count() + data_entry_count + 1 >= ENTRY_COUNT {
+      self.new_page()?;
+    }
+
+    let mut header = EntryHeader::new(ns, ty, data_entry_count as u8 + 1, key);
+    header.set_varlen(data);
+
+    let page = self.page();
+    page.write_entries(&header.to_bytes());
+    page.write_entries(data);
+
+    Ok(())
+  }
+
+  /// Write a version 2 blob, split into chunks filling up the remaining space of each page.
+  fn write_chunked_blob(&mut self, ns: u8, key: &str, data: &[u8]) -> Result<(), Error> {
+    if self.page().entry_count() >= ENTRY_COUNT {
+      self.new_page()?;
+    }
+
+    let mut chunk_count = 0u8;
+    let mut offset = 0;
+
+    loop {
+      let tailroom = (ENTRY_COUNT - self.page().entry_count() - 1) * ENTRY_SIZE;
+      let chunk_size = tailroom.min(data.len() - offset);
+      let chunk = &data[offset..(offset + chunk_size)];
+      offset += chunk_size;
+
+      let mut header = EntryHeader::new(ns, ItemType::BlobData, 0, key);
+      header.span = chunk_size.div_ceil(ENTRY_SIZE) as u8 + 1;
+      header.chunk_index = chunk_count;
+      header.set_varlen(chunk);
+
+      let page = self.page();
+      page.write_entries(&header.to_bytes());
+      page.write_entries(chunk);
+
+      chunk_count = chunk_count.checked_add(1).ok_or(Error::NotEnoughSpace)?;
+
+      if offset < data.len() || tailroom - chunk_size < ENTRY_SIZE {
+        self.new_page()?;
+      }
+
+      if offset == data.len() {
+        break
+      }
+    }
+
+    let mut header = EntryHeader::new(ns, ItemType::BlobIndex, 1, key);
+    header.data[0..4].copy_from_slice(&(data.len() as u32).to_le_bytes());
+    header.data[4] = chunk_count;
+    header.data[5] = 0;
+    self.page().write_entries(&header.to_bytes());
+
+    Ok(())
+  }
+
+  fn write_value(&mut self, ns: u8, key: &str, value: &Value) -> Result<(), Error> {
+    match value {
+      Value::String(s) => self.write_single_page(ns, key, ItemType::Str, s.as_bytes_with_nul()),
+      Value::Blob(data) if self.version == Version::V1 => self.write_single_page(ns, key, ItemType::Blob, data),
+      Value::Blob(data) => self.write_chunked_blob(ns, key, data),
+      value => {
+        let bytes = value.primitive_bytes().unwrap();
+        self.write_primitive(ns, key, value.item_type(), &bytes)
+      },
+    }
+  }
+
+  /// Fill the remaining size with empty pages and append the reserved page.
+  fn finish(mut self) -> Vec<u8> {
+    while self.remaining > 0 {
+      self.new_page().unwrap();
+    }
+
+    if let Some(page) = self.pages.last_mut() {
+      page.set_state(STATE_FULL);
+    }
+
+    let mut bytes = Vec::with_capacity((self.pages.len() + 1) * PAGE_SIZE);
+    for page in self.pages {
+      bytes.extend(page.into_bytes());
+    }
+    bytes.resize(bytes.len() + PAGE_SIZE, 0xff);
+    bytes
+  }
+}
+
+pub(crate) fn write(partition: &NvsPartition, size: usize, version: Version) -> Result<Vec<u8>, Error> {
+  let mut writer = Writer::new(size, version)?;
+
+  for (i, namespace) in partition.namespaces().enumerate() {
+    let ns = i as u8 + 1;
+
+    writer.write_primitive(0, namespace.name(), ItemType::U8, &[ns])?;
+
+    for entry in namespace.entries() {
+      writer.write_value(ns, entry.key(), entry.value())?;
+    }
+  }
+
+  Ok(writer.finish())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::ffi::CString;
+
+  use super::*;
+
+  fn all_values() -> NvsPartition {
+    let mut partition = NvsPartition::new();
+
+    let ns = partition.namespace_mut("values").unwrap();
+    ns.set("u8", Value::U8(0xab)).unwrap();
+    ns.set("i8", Value::I8(-5)).unwrap();
+    ns.set("u16", Value::U16(0xabcd)).unwrap();
+    ns.set("i16", Value::I16(-1234)).unwrap();
+    ns.set("u32", Value::U32(0xdead_beef)).unwrap();
+    ns.set("i32", Value::I32(-123_456)).unwrap();
+    ns.set("u64", Value::U64(0x0123_4567_89ab_cdef)).unwrap();
+    ns.set("i64", Value::I64(i64::MIN)).unwrap();
+    ns.set("string", CString::new("hello world").unwrap()).unwrap();
+    ns.set("empty", CString::default()).unwrap();
+    ns.set("blob", vec![0, 1, 2, 0xff, 0xfe]).unwrap();
+
+    partition.namespace_mut("other").unwrap().set("u8", Value::U8(1)).unwrap();
+
+    partition
+  }
+
+  fn page(bytes: &[u8], index: usize) -> PageRef<'_> {
+    PageRef::new(&bytes[(index * PAGE_SIZE)..((index + 1) * PAGE_SIZE)])
+  }
+
+  fn written_entries(page: PageRef<'_>) -> usize {
+    (0..ENTRY_COUNT).filter(|&i| page.entry_state(i) == EntryState::Written).count()
+  }
+
+  #[test]
+  fn round_trip_all_values() {
+    let partition = all_values();
+
+    for &version in &[Version::V1, Version::V2] {
+      let bytes = partition.to_bytes(MIN_PARTITION_SIZE, version).unwrap();
+      assert_eq!(bytes.len(), MIN_PARTITION_SIZE);
+      assert_eq!(page(&bytes, 0).version(), Some(version));
+      assert_eq!(NvsPartition::from_bytes(&bytes).unwrap(), partition);
+    }
+  }
+
+  #[test]
+  fn invalid_size() {
+    let partition = all_values();
+
+    assert!(matches!(partition.to_bytes(2 * PAGE_SIZE, Version::V2), Err(Error::InvalidSize(_))));
+    assert!(matches!(partition.to_bytes(MIN_PARTITION_SIZE + 1, Version::V2), Err(Error::InvalidSize(_))));
+  }
+
+  #[test]
+  fn multi_page() {
+    let mut partition = NvsPartition::new();
+    let ns = partition.namespace_mut("ns").unwrap();
+    for i in 0..300u32 {
+      ns.set(&format!("key{}", i), Value::U32(i)).unwrap();
+    }
+
+    let bytes = partition.to_bytes(5 * PAGE_SIZE, Version::V2).unwrap();
+
+    assert_eq!(written_entries(page(&bytes, 0)), ENTRY_COUNT);
+    assert_eq!(written_entries(page(&bytes, 1)), ENTRY_COUNT);
+    assert_eq!(written_entries(page(&bytes, 2)), 301 - 2 * ENTRY_COUNT);
+    assert_eq!(page(&bytes, 0).state(), STATE_FULL);
+    assert_eq!(page(&bytes, 1).state(), STATE_FULL);
+    assert_eq!((0..4).map(|i| page(&bytes, i).seqno()).collect::<Vec<_>>(), [0, 1, 2, 3]);
+    assert_eq!(NvsPartition::from_bytes(&bytes).unwrap(), partition);
+  }
+
+  #[test]
+  fn full_page() {
+    let mut partition = NvsPartition::new();
+    let ns = partition.namespace_mut("ns").unwrap();
+    for i in 0..(ENTRY_COUNT - 1) {
+      ns.set(&format!("key{}", i), Value::U8(i as u8)).unwrap();
+    }
+
+    let bytes = partition.to_bytes(MIN_PARTITION_SIZE, Version::V2).unwrap();
+    assert_eq!(written_entries(page(&bytes, 0)), ENTRY_COUNT);
+    assert_eq!(page(&bytes, 0).state(), STATE_FULL);
+    assert_eq!(written_entries(page(&bytes, 1)), 0);
+    assert_eq!(NvsPartition::from_bytes(&bytes).unwrap(), partition);
+
+    partition.namespace_mut("ns").unwrap().set("next", Value::U8(0)).unwrap();
+
+    let bytes = partition.to_bytes(MIN_PARTITION_SIZE, Version::V2).unwrap();
+    assert_eq!(written_entries(page(&bytes, 0)), ENTRY_COUNT);
+    assert_eq!(written_entries(page(&bytes, 1)), 1);
+    assert_eq!(NvsPartition::from_bytes(&bytes).unwrap(), partition);
+  }
+
+  #[test]
+  fn not_enough_space() {
+    let mut partition = NvsPartition::new();
+    let ns = partition.namespace_mut("ns").unwrap();
+    for i in 0..(2 * ENTRY_COUNT) {
+      ns.set(&format!("key{}", i), Value::U8(i as u8)).unwrap();
+    }
+
+    assert!(matches!(partition.to_bytes(MIN_PARTITION_SIZE, Version::V2), Err(Error::NotEnoughSpace)));
+    assert!(partition.to_bytes(MIN_PARTITION_SIZE + PAGE_SIZE, Version::V2).is_ok());
+  }
+
+  #[test]
+  fn chunked_blob() {
+    let data = (0..9000).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+
+    let mut partition = NvsPartition::new();
+    let ns = partition.namespace_mut("ns").unwrap();
+    ns.set("before", Value::U32(1)).unwrap();
+    ns.set("blob", data.clone()).unwrap();
+    ns.set("after", Value::U32(2)).unwrap();
+
+    let bytes = partition.to_bytes(5 * PAGE_SIZE, Version::V2).unwrap();
+
+    let index = (0..4)
+      .flat_map(|p| (0..ENTRY_COUNT).map(move |i| (p, i)))
+      .filter(|&(p, i)| page(&bytes, p).entry_state(i) == EntryState::Written)
+      .filter_map(|(p, i)| EntryHeader::from_bytes(page(&bytes, p).entry(i)))
+      .find(|header| header.ty == ItemType::BlobIndex as u8)
+      .unwrap();
+    assert_eq!(u32::from_le_bytes([index.data[0], index.data[1], index.data[2], index.data[3]]), 9000);
+    assert_eq!(index.data[4], 3);
+    assert_eq!(index.data[5], 0);
+
+    let read = NvsPartition::from_bytes(&bytes).unwrap();
+    assert_eq!(read.namespace("ns").unwrap().get("blob"), Some(&Value::Blob(data)));
+    assert_eq!(read, partition);
+  }
+
+  #[test]
+  fn single_page_limits() {
+    let mut partition = NvsPartition::new();
+    partition.namespace_mut("ns").unwrap().set("blob", vec![0; MAX_SINGLE_PAGE_DATA_SIZE + 1]).unwrap();
+
+    assert!(matches!(partition.to_bytes(MIN_PARTITION_SIZE, Version::V1), Err(Error::ValueTooLong { .. })));
+    assert!(partition.to_bytes(MIN_PARTITION_SIZE, Version::V2).is_ok());
+
+    partition.namespace_mut("ns").unwrap().set("blob", vec![0; MAX_SINGLE_PAGE_DATA_SIZE]).unwrap();
+    let bytes = partition.to_bytes(MIN_PARTITION_SIZE, Version::V1).unwrap();
+    assert_eq!(NvsPartition::from_bytes(&bytes).unwrap(), partition);
+  }
+
+  #[test]
+  fn crc_mismatch() {
+    let mut partition = NvsPartition::new();
+    partition.namespace_mut("ns").unwrap().set("string", CString::new("hello").unwrap()).unwrap();
+
+    let bytes = partition.to_bytes(MIN_PARTITION_SIZE, Version::V2).unwrap();
+    let entry_offset = |i: usize| 64 + i * ENTRY_SIZE;
+
+    let mut header = bytes.clone();
+    header[5] ^= 1;
+    assert!(matches!(
+      NvsPartition::from_bytes(&header),
+      Err(Error::Corrupt { page: 0, entry: None, message: "invalid page header checksum" })
+    ));
+
+    let mut entry = bytes.clone();
+    entry[entry_offset(1) + 8] ^= 1;
+    assert!(matches!(
+      NvsPartition::from_bytes(&entry),
+      Err(Error::Corrupt { page: 0, entry: Some(1), message: "invalid entry checksum" })
+    ));
+
+    let mut data = bytes;
+    data[entry_offset(2)] ^= 1;
+    assert!(matches!(
+      NvsPartition::from_bytes(&data),
+      Err(Error::Corrupt { page: 0, entry: Some(1), message: "invalid data checksum" })
+    ));
+  }
+}
@@ -0,0 +1,109 @@
+use core::fmt;
+
+/// Maximum duration of a level in ticks.
+pub const MAX_DURATION: u16 = 0x7fff;
+
+/// Convert `ns` to ticks of `tick_ps` picoseconds, rounding to the nearest tick and saturating at [`MAX_DURATION`](constant.MAX_DURATION.html).
+///
+/// Ticks are given in picoseconds, since a tick of the 80 MHz APB clock is 12.5 ns.
+pub const fn ns_to_ticks(ns: u32, tick_ps: u32) -> u16 {
+  let tick_ps = tick_ps as u64;
+  let ticks = (ns as u64 * 1000 + tick_ps / 2) / tick_ps;
+
+  if ticks > MAX_DURATION as u64 {
+    MAX_DURATION
+  } else {
+    ticks as u16
+  }
+}
+
+/// Two levels with their durations in ticks, the unit of data sent and received by the RMT.
+///
+/// A duration of zero marks the end of a transmission.
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Item(u32);
+
+impl Item {
+  /// Create an item. Durations are truncated to 15 bits.
+  pub const fn new(level0: bool, duration0: u16, level1: bool, duration1: u16) -> Self {
+    Self(
+      (duration0 & MAX_DURATION) as u32 |
+      (level0 as u32) << 15 |
+      ((duration1 & MAX_DURATION) as u32) << 16 |
+      (level1 as u32) << 31
+    )
+  }
+
+  /// The raw value in the layout of `rmt_item32_t`.
+  pub const fn from_bits(bits: u32) -> Self {
+    Self(bits)
+  }
+
+  pub const fn bits(self) -> u32 {
+    self.0
+  }
+
+  pub const fn level0(self) -> bool {
+    self.0 & (1 << 15) != 0
+  }
+
+  pub const fn duration0(self) -> u16 {
+    (self.0 & MAX_DURATION as u32) as u16
+  }
+
+  pub const fn level1(self) -> bool {
+    self.0 & (1 << 31) != 0
+  }
+
+  pub const fn duration1(self) -> u16 {
+    (self.0 >> 16 & MAX_DURATION as u32) as u16
+  }
+}
+
+impl fmt::Debug for Item {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Item")
+      .field("level0", &self.level0())
+      .field("duration0", &self.duration0())
+      .field("level1", &self.level1())
+      .field("duration1", &self.duration1())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fields() {
+    let item = Item::new(true, 0x1234, false, 0x7fff);
+    assert!(item.level0());
+    assert_eq!(item.duration0(), 0x1234);
+    assert!(!item.level1());
+    assert_eq!(item.duration1(), 0x7fff);
+    assert_eq!(item.bits(), 0x7fff_9234);
+    assert_eq!(Item::from_bits(item.bits()), item);
+
+    // Durations are truncated to 15 bits, without changing the levels.
+    let item = Item::new(false, 0xffff, true, 0x8001);
+    assert!(!item.level0());
+    assert_eq!(item.duration0(), 0x7fff);
+    assert!(item.level1());
+    assert_eq!(item.duration1(), 1);
+  }
+
+  #[test]
+  fn ticks() {
+    // A clock divider of 1 gives ticks of 12.5 ns.
+    assert_eq!(ns_to_ticks(400, 12_500), 32);
+    assert_eq!(ns_to_ticks(850, 12_500), 68);
+    assert_eq!(ns_to_ticks(18, 12_500), 1);
+    assert_eq!(ns_to_ticks(19, 12_500), 2);
+
+    assert_eq!(ns_to_ticks(9_000_000, 1_000_000), 9000);
+    assert_eq!(ns_to_ticks(9_000_000, 100_000), MAX_DURATION);
+    assert_eq!(ns_to_ticks(u32::MAX, 12_500), MAX_DURATION);
+  }
+}
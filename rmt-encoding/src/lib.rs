@@ -0,0 +1,15 @@
+//! Encoders and decoders converting data to and from RMT items.
+//!
+//! The encoders only depend on the duration of a channel tick, so they can be tested on the host.
+//! `esp_idf_hal::rmt` re-exports them together with the driver.
+
+#![warn(missing_debug_implementations)]
+
+mod item;
+pub use item::*;
+
+mod nec;
+pub use nec::*;
+
+mod ws2812;
+pub use ws2812::*;
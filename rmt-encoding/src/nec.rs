@@ -0,0 +1,223 @@
+use core::iter;
+
+use crate::item::{ns_to_ticks, Item};
+
+const LEADER_MARK_NS: u32 = 9_000_000;
+const LEADER_SPACE_NS: u32 = 4_500_000;
+const REPEAT_SPACE_NS: u32 = 2_250_000;
+const BIT_MARK_NS: u32 = 560_000;
+const ZERO_SPACE_NS: u32 = 560_000;
+const ONE_SPACE_NS: u32 = 1_690_000;
+
+/// A message of the NEC infrared protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NecMessage {
+  /// A command sent to a device.
+  ///
+  /// Addresses up to `0xff` use the standard protocol, which sends the address followed by its inverse.
+  /// Larger addresses use the extended protocol, which sends 16 address bits. Extended addresses whose
+  /// high byte is the inverse of the low byte are decoded as standard addresses.
+  Command { address: u16, command: u8 },
+  /// Sent while a button is held after the initial command.
+  Repeat,
+}
+
+/// Encoder and decoder for the NEC infrared protocol.
+///
+/// Marks are encoded as high levels, so the channel should use a 38 kHz carrier.
+/// Decoding only uses durations, so it works with both active-high and active-low receivers.
+#[derive(Debug, Clone, Copy)]
+pub struct Nec {
+  leader_mark: u16,
+  leader_space: u16,
+  repeat_space: u16,
+  bit_mark: u16,
+  zero_space: u16,
+  one_space: u16,
+}
+
+/// Whether `actual` is within 25 % of `expected`.
+fn matches(actual: u16, expected: u16) -> bool {
+  let (actual, expected) = (u32::from(actual), u32::from(expected));
+  actual + expected / 4 >= expected && actual <= expected + expected / 4
+}
+
+impl Nec {
+  /// Create an encoder for a channel with ticks of `tick_ps` picoseconds, which should be between 300 ns and 10 µs,
+  /// e.g. 1 µs using a clock divider of 80.
+  pub const fn new(tick_ps: u32) -> Self {
+    Self {
+      leader_mark: ns_to_ticks(LEADER_MARK_NS, tick_ps),
+      leader_space: ns_to_ticks(LEADER_SPACE_NS, tick_ps),
+      repeat_space: ns_to_ticks(REPEAT_SPACE_NS, tick_ps),
+      bit_mark: ns_to_ticks(BIT_MARK_NS, tick_ps),
+      zero_space: ns_to_ticks(ZERO_SPACE_NS, tick_ps),
+      one_space: ns_to_ticks(ONE_SPACE_NS, tick_ps),
+    }
+  }
+
+  /// Encode `message`, ending with a final mark.
+  pub fn encode(&self, message: NecMessage) -> impl Iterator<Item = Item> {
+    let (space, bits) = match message {
+      NecMessage::Command { address, command } => {
+        let address = if address <= 0xff { address | (!address & 0xff) << 8 } else { address };
+        let bits = u32::from(address) | u32::from(command) << 16 | u32::from(!command) << 24;
+        (self.leader_space, Some(bits))
+      },
+      NecMessage::Repeat => (self.repeat_space, None),
+    };
+
+    let (bit_mark, zero_space, one_space) = (self.bit_mark, self.zero_space, self.one_space);
+
+    let bits = bits.into_iter().flat_map(move |bits| {
+      (0..32).map(move |bit| Item::new(true, bit_mark, false, if bits >> bit & 1 == 1 { one_space } else { zero_space }))
+    });
+
+    iter::once(Item::new(true, self.leader_mark, false, space))
+      .chain(bits)
+      .chain(iter::once(Item::new(true, bit_mark, false, 0)))
+  }
+
+  /// Decode a message from received items. Returns `None` if the items are not a valid message.
+  pub fn decode(&self, items: &[Item]) -> Option<NecMessage> {
+    let (leader, bits) = items.split_first()?;
+
+    if !matches(leader.duration0(), self.leader_mark) {
+      return None
+    }
+
+    if matches(leader.duration1(), self.repeat_space) {
+      return Some(NecMessage::Repeat)
+    }
+
+    if !matches(leader.duration1(), self.leader_space) || bits.len() < 32 {
+      return None
+    }
+
+    let mut value = 0u32;
+
+    for (i, item) in bits[..32].iter().enumerate() {
+      if !matches(item.duration0(), self.bit_mark) {
+        return None
+      }
+
+      if matches(item.duration1(), self.one_space) {
+        value |= 1 << i;
+      } else if !matches(item.duration1(), self.zero_space) {
+        return None
+      }
+    }
+
+    let [address_low, address_high, command, command_inverse] = value.to_le_bytes();
+
+    if command != !command_inverse {
+      return None
+    }
+
+    let address = if address_high == !address_low {
+      u16::from(address_low)
+    } else {
+      u16::from_le_bytes([address_low, address_high])
+    };
+
+    Some(NecMessage::Command { address, command })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A clock divider of 80.
+  const TICK_PS: u32 = 1_000_000;
+
+  fn round_trip(message: NecMessage) -> Option<NecMessage> {
+    let nec = Nec::new(TICK_PS);
+    let items = nec.encode(message).collect::<Vec<_>>();
+    nec.decode(&items)
+  }
+
+  #[test]
+  fn encode_standard() {
+    let nec = Nec::new(TICK_PS);
+    let items = nec.encode(NecMessage::Command { address: 0x12, command: 0x34 }).collect::<Vec<_>>();
+
+    assert_eq!(items.len(), 1 + 32 + 1);
+    assert_eq!(items[0], Item::new(true, 9000, false, 4500));
+    assert_eq!(items[33], Item::new(true, 560, false, 0));
+
+    // Address, inverted address, command and inverted command, least significant bit first.
+    let value = items[1..33].iter().enumerate().fold(0u32, |value, (i, item)| {
+      assert_eq!(item.duration0(), 560);
+      value | u32::from(item.duration1() == 1690) << i
+    });
+    assert_eq!(value.to_le_bytes(), [0x12, 0xed, 0x34, 0xcb]);
+  }
+
+  #[test]
+  fn round_trip_standard() {
+    for &(address, command) in &[(0x00, 0x00), (0x12, 0x34), (0xff, 0xff)] {
+      let message = NecMessage::Command { address, command };
+      assert_eq!(round_trip(message), Some(message));
+    }
+  }
+
+  #[test]
+  fn round_trip_extended() {
+    for &(address, command) in &[(0x0100, 0x01), (0x1234, 0x56), (0xffff, 0x00)] {
+      let message = NecMessage::Command { address, command };
+      assert_eq!(round_trip(message), Some(message));
+    }
+
+    // Indistinguishable from the standard address `0x01`.
+    assert_eq!(
+      round_trip(NecMessage::Command { address: 0xfe01, command: 0x02 }),
+      Some(NecMessage::Command { address: 0x01, command: 0x02 }),
+    );
+  }
+
+  #[test]
+  fn repeat() {
+    let nec = Nec::new(TICK_PS);
+
+    let items = nec.encode(NecMessage::Repeat).collect::<Vec<_>>();
+    assert_eq!(items, [Item::new(true, 9000, false, 2250), Item::new(true, 560, false, 0)]);
+    assert_eq!(nec.decode(&items), Some(NecMessage::Repeat));
+  }
+
+  #[test]
+  fn tolerance() {
+    assert!(matches(75, 100));
+    assert!(!matches(74, 100));
+    assert!(matches(125, 100));
+    assert!(!matches(126, 100));
+
+    let nec = Nec::new(TICK_PS);
+    let message = NecMessage::Command { address: 0x12, command: 0x34 };
+    let items = nec.encode(message).collect::<Vec<_>>();
+
+    let scale = |items: &[Item], percent: u32| -> Vec<Item> {
+      let scale = |duration: u16| (u32::from(duration) * percent / 100) as u16;
+      items.iter().map(|item| Item::new(item.level0(), scale(item.duration0()), item.level1(), scale(item.duration1()))).collect()
+    };
+
+    // Scaled durations are rounded down, so exactly 75 % of an odd duration is out of tolerance.
+    assert_eq!(nec.decode(&scale(&items, 76)), Some(message));
+    assert_eq!(nec.decode(&scale(&items, 125)), Some(message));
+    assert_eq!(nec.decode(&scale(&items, 70)), None);
+    assert_eq!(nec.decode(&scale(&items, 130)), None);
+  }
+
+  #[test]
+  fn invalid() {
+    let nec = Nec::new(TICK_PS);
+    let mut items = nec.encode(NecMessage::Command { address: 0x12, command: 0x34 }).collect::<Vec<_>>();
+
+    assert_eq!(nec.decode(&[]), None);
+    assert_eq!(nec.decode(&items[..32]), None);
+
+    // Flip a bit of the inverted command.
+    items[32] = Item::new(true, 560, false, if items[32].duration1() == 560 { 1690 } else { 560 });
+    assert_eq!(nec.decode(&items), None);
+  }
+}
@@ -0,0 +1,117 @@
+use core::iter;
+
+use crate::item::{ns_to_ticks, Item};
+
+/// An RGB color.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Rgb {
+  pub r: u8,
+  pub g: u8,
+  pub b: u8,
+}
+
+impl Rgb {
+  pub const fn new(r: u8, g: u8, b: u8) -> Self {
+    Self { r, g, b }
+  }
+}
+
+const T0H_NS: u32 = 400;
+const T0L_NS: u32 = 850;
+const T1H_NS: u32 = 800;
+const T1L_NS: u32 = 450;
+const RESET_NS: u32 = 50_000;
+
+/// Encoder for WS2812 addressable LEDs, also known as NeoPixels.
+///
+/// The timings require a tick of at most 100 ns, i.e. a clock divider of at most 8.
+#[derive(Debug, Clone, Copy)]
+pub struct Ws2812 {
+  zero: Item,
+  one: Item,
+  reset: Item,
+}
+
+impl Ws2812 {
+  /// Create an encoder for a channel with ticks of `tick_ps` picoseconds.
+  pub const fn new(tick_ps: u32) -> Self {
+    let reset = ns_to_ticks(RESET_NS / 2, tick_ps);
+
+    Self {
+      zero: Item::new(true, ns_to_ticks(T0H_NS, tick_ps), false, ns_to_ticks(T0L_NS, tick_ps)),
+      one: Item::new(true, ns_to_ticks(T1H_NS, tick_ps), false, ns_to_ticks(T1L_NS, tick_ps)),
+      reset: Item::new(false, reset, false, reset),
+    }
+  }
+
+  fn encode_byte(&self, byte: u8) -> impl Iterator<Item = Item> {
+    let (zero, one) = (self.zero, self.one);
+    (0..8).rev().map(move |bit| if byte >> bit & 1 == 1 { one } else { zero })
+  }
+
+  /// Encode `bytes`, most significant bit first, followed by the reset code.
+  pub fn encode_bytes<'a>(&'a self, bytes: &'a [u8]) -> impl Iterator<Item = Item> + 'a {
+    bytes.iter()
+      .flat_map(move |&byte| self.encode_byte(byte))
+      .chain(iter::once(self.reset))
+  }
+
+  /// Encode `colors` in the GRB order used by WS2812 LEDs, followed by the reset code.
+  pub fn encode<'a>(&'a self, colors: &'a [Rgb]) -> impl Iterator<Item = Item> + 'a {
+    colors.iter()
+      .flat_map(|color| IntoIterator::into_iter([color.g, color.r, color.b]))
+      .flat_map(move |byte| self.encode_byte(byte))
+      .chain(iter::once(self.reset))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A clock divider of 2.
+  const TICK_PS: u32 = 25_000;
+
+  fn bits(ws2812: &Ws2812, items: &[Item]) -> Vec<bool> {
+    items.iter().map(|&item| {
+      assert!(item == ws2812.zero || item == ws2812.one, "{:?} is not a bit", item);
+      item == ws2812.one
+    }).collect()
+  }
+
+  fn to_bits(bytes: &[u8]) -> Vec<bool> {
+    bytes.iter().flat_map(|byte| (0..8).rev().map(move |bit| byte >> bit & 1 == 1)).collect()
+  }
+
+  #[test]
+  fn timings() {
+    let ws2812 = Ws2812::new(TICK_PS);
+
+    assert_eq!(ws2812.zero, Item::new(true, 16, false, 34));
+    assert_eq!(ws2812.one, Item::new(true, 32, false, 18));
+    assert_eq!(ws2812.reset, Item::new(false, 1000, false, 1000));
+  }
+
+  #[test]
+  fn encode_grb_msb_first() {
+    let ws2812 = Ws2812::new(TICK_PS);
+
+    let items = ws2812.encode(&[Rgb::new(0x0f, 0x80, 0x01), Rgb::new(0xaa, 0x00, 0xff)]).collect::<Vec<_>>();
+    assert_eq!(items.len(), 2 * 24 + 1);
+
+    let (reset, items) = items.split_last().unwrap();
+    assert_eq!(*reset, ws2812.reset);
+    assert_eq!(bits(&ws2812, items), to_bits(&[0x80, 0x0f, 0x01, 0x00, 0xaa, 0xff]));
+  }
+
+  #[test]
+  fn encode_bytes() {
+    let ws2812 = Ws2812::new(TICK_PS);
+
+    let items = ws2812.encode_bytes(&[0b1010_0001]).collect::<Vec<_>>();
+    assert_eq!(items.last(), Some(&ws2812.reset));
+    assert_eq!(bits(&ws2812, &items[..8]), [true, false, true, false, false, false, false, true]);
+
+    assert_eq!(ws2812.encode(&[]).collect::<Vec<_>>(), [ws2812.reset]);
+  }
+}